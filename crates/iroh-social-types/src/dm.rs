@@ -1,10 +1,25 @@
-use crate::types::MediaAttachment;
+use crate::types::{MediaAttachment, Post};
 use serde::{Deserialize, Serialize};
 
-pub const DM_ALPN: &[u8] = b"iroh-social/dm/1";
+pub const DM_ALPN: &[u8] = b"iroh-social/dm/2";
 
 // -- Wire types (sent over QUIC) --
 
+/// Frame exchanged on a persistent DM connection.
+/// Every request travels on its own bi-stream of the shared connection,
+/// length-prefixed: [4-byte big-endian len][JSON payload].
+/// The receiver answers on the same stream with a single reply frame.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DmFrame {
+    Handshake(DmHandshake),
+    Envelope(EncryptedEnvelope),
+    /// Reply to an `Envelope` once it has been decrypted and processed.
+    /// Carries the ID of the `DirectMessage` it contained, `None` for signals.
+    Ack {
+        message_id: Option<String>,
+    },
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DmHandshake {
     Init { noise_message: Vec<u8> },
@@ -33,6 +48,26 @@ pub enum DmPayload {
     Typing,
     Read { message_id: String },
     Delivered { message_id: String },
+    FeedKey(FeedKey),
+    AudiencePost(Box<AudiencePost>),
+}
+
+/// A post the sender shared with an audience list the recipient is on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudiencePost {
+    /// Name of the list, shown as the post's audience marker.
+    pub audience: String,
+    pub post: Post,
+}
+
+/// Symmetric key a private profile seals its posts under. Sent to each
+/// approved follower over the DM ratchet and replaced by a new one whenever
+/// a follower is removed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedKey {
+    pub key_id: String,
+    pub key: Vec<u8>,
+    pub created_at: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,6 +88,58 @@ pub struct ConversationMeta {
     pub last_message_at: u64,
     pub last_message_preview: String,
     pub unread_count: u32,
+    /// The user confirmed the peer's safety number.
+    #[serde(default)]
+    pub verified: bool,
+    /// The peer's alias previously belonged to a different verified identity.
+    #[serde(default)]
+    pub identity_changed: bool,
+    /// Opened by a peer outside the accept policy and not yet accepted.
+    #[serde(default)]
+    pub is_request: bool,
+}
+
+/// Who may start a conversation that lands directly in the inbox.
+/// Conversations from anyone else are held as message requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DmPolicy {
+    #[default]
+    Everyone,
+    /// Only users we follow.
+    Following,
+    /// Only users we follow who also follow us.
+    Mutuals,
+}
+
+impl DmPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DmPolicy::Everyone => "everyone",
+            DmPolicy::Following => "following",
+            DmPolicy::Mutuals => "mutuals",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "everyone" => Some(DmPolicy::Everyone),
+            "following" => Some(DmPolicy::Following),
+            "mutuals" => Some(DmPolicy::Mutuals),
+            _ => None,
+        }
+    }
+}
+
+/// Safety number for a conversation, compared out of band to verify the peer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SafetyNumber {
+    pub peer_pubkey: String,
+    /// Twelve 5-digit groups, identical on both sides.
+    pub digits: String,
+    /// Payload for QR comparison, identical on both sides.
+    pub scannable: String,
+    pub verified: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -68,4 +155,7 @@ pub struct StoredMessage {
     pub read: bool,
     pub delivered: bool,
     pub reply_to: Option<String>,
+    /// Set when the message expired in the outbox without being delivered.
+    #[serde(default)]
+    pub failed: bool,
 }
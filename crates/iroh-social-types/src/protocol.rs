@@ -1,4 +1,5 @@
-use crate::types::{Interaction, Post, Profile};
+use crate::dm::AudiencePost;
+use crate::types::{FollowDecision, FollowRequest, Interaction, Post, PostEdit, Profile};
 use iroh_gossip::TopicId;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
@@ -6,10 +7,32 @@ use sha2::{Digest, Sha256};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum GossipMessage {
     NewPost(Post),
-    DeletePost { id: String, author: String },
+    DeletePost {
+        id: String,
+        author: String,
+    },
     ProfileUpdate(Profile),
     NewInteraction(Interaction),
-    DeleteInteraction { id: String, author: String },
+    DeleteInteraction {
+        id: String,
+        author: String,
+    },
+    EditPost(PostEdit),
+    /// Any message but a `ProfileUpdate` from a private profile, readable
+    /// only by its approved followers.
+    Sealed(SealedMessage),
+}
+
+/// A `GossipMessage` encrypted under the author's feed key, which only
+/// their approved followers hold.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedMessage {
+    pub author: String,
+    /// The `FeedKey` the message is sealed under.
+    pub key_id: String,
+    pub nonce: Vec<u8>,
+    /// The inner `GossipMessage` as JSON, encrypted under the feed key.
+    pub ciphertext: Vec<u8>,
 }
 
 pub fn user_feed_topic(pubkey: &str) -> TopicId {
@@ -31,6 +54,25 @@ pub struct SyncRequest {
     pub newest_timestamp: u64,
     /// Newest interaction timestamp the client has for this author (0 = no interactions).
     pub newest_interaction_timestamp: u64,
+    /// Client understands interaction kinds other than `Like` (reactions,
+    /// poll votes). Older clients omit this and are only counted and sent likes.
+    #[serde(default)]
+    pub reactions: bool,
+    /// Client understands `SyncFrame::Edits`. Older clients omit this and
+    /// are never sent edits.
+    #[serde(default)]
+    pub edits: bool,
+    /// Newest edit timestamp the client has for this author (0 = no edits).
+    #[serde(default)]
+    pub newest_edit_timestamp: u64,
+    /// Client understands `SyncFrame::Sealed`. A private profile refuses
+    /// clients that omit this.
+    #[serde(default)]
+    pub sealed: bool,
+    /// Client understands `SyncFrame::AudiencePosts`. Older clients omit
+    /// this and are never sent posts shared with an audience.
+    #[serde(default)]
+    pub audiences: bool,
 }
 
 /// Phase 1: Server responds with its counts and whether timestamp catch-up suffices.
@@ -64,4 +106,48 @@ pub enum SyncMode {
 pub enum SyncFrame {
     Posts(Vec<Post>),
     Interactions(Vec<Interaction>),
+    /// Edits newer than the client's `newest_edit_timestamp`, after all posts.
+    Edits(Vec<PostEdit>),
+    /// Posts and edits of a private profile, in place of `Posts` and `Edits`.
+    Sealed(Vec<SealedMessage>),
+    /// Posts the author shared with an audience list the client is on,
+    /// alongside `Posts`.
+    AudiencePosts(Vec<AudiencePost>),
+}
+
+pub const POST_FETCH_ALPN: &[u8] = b"iroh-social/post-fetch/1";
+
+/// Most post IDs one `PostFetchRequest` may ask for.
+pub const MAX_POST_FETCH_IDS: usize = 50;
+
+/// Ask a peer for specific posts by ID, e.g. a quoted post or a reply's
+/// parent by an author we don't follow. Any peer holding them may answer.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PostFetchRequest {
+    pub ids: Vec<String>,
+}
+
+/// The requested posts the peer holds and may share, as originally signed,
+/// plus their edits. Unknown IDs are left out.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PostFetchResponse {
+    pub posts: Vec<Post>,
+    #[serde(default)]
+    pub edits: Vec<PostEdit>,
+    /// Requested posts their author shared with an audience list the
+    /// requester is on.
+    #[serde(default)]
+    pub audience_posts: Vec<AudiencePost>,
+}
+
+pub const FOLLOW_ALPN: &[u8] = b"iroh-social/follow/1";
+
+/// Sent on a follow-protocol stream. The receiver answers with the
+/// resulting `FollowRequestStatus` as JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum FollowMessage {
+    /// Ask to follow the receiver's private profile.
+    Request(FollowRequest),
+    /// The sender approved or denied the receiver's request to follow them.
+    Decision(FollowDecision),
 }
@@ -1,10 +1,12 @@
-use crate::types::{Interaction, Post};
+use crate::types::{FollowDecision, FollowRequest, Interaction, Post, PostEdit, Profile};
 use iroh::{PublicKey, SecretKey, Signature};
 
 /// Produce the canonical bytes for signing a Post.
 /// Fields are serialized in a deterministic order, excluding `signature`.
+/// Optional fields added later are only included when set, so posts signed
+/// before they existed still verify.
 fn post_signing_bytes(post: &Post) -> Vec<u8> {
-    serde_json::to_vec(&serde_json::json!({
+    let mut value = serde_json::json!({
         "id": post.id,
         "author": post.author,
         "content": post.content,
@@ -14,8 +16,23 @@ fn post_signing_bytes(post: &Post) -> Vec<u8> {
         "reply_to_author": post.reply_to_author,
         "quote_of": post.quote_of,
         "quote_of_author": post.quote_of_author,
-    }))
-    .expect("json serialization should not fail")
+    });
+    if let Some(poll) = &post.poll {
+        value["poll"] = serde_json::json!(poll);
+    }
+    if let Some(warning) = &post.content_warning {
+        value["content_warning"] = serde_json::json!(warning);
+    }
+    if post.sensitive_media {
+        value["sensitive_media"] = serde_json::json!(true);
+    }
+    if let Some(expires_at) = post.expires_at {
+        value["expires_at"] = serde_json::json!(expires_at);
+    }
+    if let Some(preview) = &post.link_preview {
+        value["link_preview"] = serde_json::json!(preview);
+    }
+    serde_json::to_vec(&value).expect("json serialization should not fail")
 }
 
 /// Produce the canonical bytes for signing an Interaction.
@@ -32,6 +49,63 @@ fn interaction_signing_bytes(interaction: &Interaction) -> Vec<u8> {
     .expect("json serialization should not fail")
 }
 
+/// Produce the canonical bytes for signing a PostEdit.
+/// Fields are serialized in a deterministic order, excluding `signature`.
+fn post_edit_signing_bytes(edit: &PostEdit) -> Vec<u8> {
+    serde_json::to_vec(&serde_json::json!({
+        "id": edit.id,
+        "post_id": edit.post_id,
+        "author": edit.author,
+        "content": edit.content,
+        "media": edit.media,
+        "timestamp": edit.timestamp,
+    }))
+    .expect("json serialization should not fail")
+}
+
+/// Produce the canonical bytes for signing the Profile of `pubkey`.
+/// Fields are serialized in a deterministic order, excluding `signature`.
+fn profile_signing_bytes(profile: &Profile, pubkey: &str) -> Vec<u8> {
+    let mut value = serde_json::json!({
+        "pubkey": pubkey,
+        "display_name": profile.display_name,
+        "bio": profile.bio,
+        "avatar_hash": profile.avatar_hash,
+        "avatar_ticket": profile.avatar_ticket,
+        "is_private": profile.is_private,
+        "pinned_posts": profile.pinned_posts,
+    });
+    if let Some(updated_at) = profile.updated_at {
+        value["updated_at"] = serde_json::json!(updated_at);
+    }
+    serde_json::to_vec(&value).expect("json serialization should not fail")
+}
+
+/// Produce the canonical bytes for signing a FollowRequest.
+/// Fields are serialized in a deterministic order, excluding `signature`.
+fn follow_request_signing_bytes(req: &FollowRequest) -> Vec<u8> {
+    serde_json::to_vec(&serde_json::json!({
+        "type": "follow_request",
+        "requester": req.requester,
+        "target": req.target,
+        "timestamp": req.timestamp,
+    }))
+    .expect("json serialization should not fail")
+}
+
+/// Produce the canonical bytes for signing a FollowDecision.
+/// Fields are serialized in a deterministic order, excluding `signature`.
+fn follow_decision_signing_bytes(decision: &FollowDecision) -> Vec<u8> {
+    serde_json::to_vec(&serde_json::json!({
+        "type": "follow_decision",
+        "target": decision.target,
+        "requester": decision.requester,
+        "approved": decision.approved,
+        "timestamp": decision.timestamp,
+    }))
+    .expect("json serialization should not fail")
+}
+
 fn signature_to_hex(sig: &Signature) -> String {
     let bytes = sig.to_bytes();
     let mut hex = String::with_capacity(128);
@@ -77,6 +151,34 @@ pub fn sign_interaction(interaction: &mut Interaction, secret_key: &SecretKey) {
     interaction.signature = signature_to_hex(&sig);
 }
 
+/// Sign a PostEdit in place using the given secret key.
+pub fn sign_post_edit(edit: &mut PostEdit, secret_key: &SecretKey) {
+    let bytes = post_edit_signing_bytes(edit);
+    let sig = secret_key.sign(&bytes);
+    edit.signature = signature_to_hex(&sig);
+}
+
+/// Sign a Profile in place using the given secret key.
+pub fn sign_profile(profile: &mut Profile, secret_key: &SecretKey) {
+    let bytes = profile_signing_bytes(profile, &secret_key.public().to_string());
+    let sig = secret_key.sign(&bytes);
+    profile.signature = Some(signature_to_hex(&sig));
+}
+
+/// Sign a FollowRequest in place using the requester's secret key.
+pub fn sign_follow_request(req: &mut FollowRequest, secret_key: &SecretKey) {
+    let bytes = follow_request_signing_bytes(req);
+    let sig = secret_key.sign(&bytes);
+    req.signature = signature_to_hex(&sig);
+}
+
+/// Sign a FollowDecision in place using the target's secret key.
+pub fn sign_follow_decision(decision: &mut FollowDecision, secret_key: &SecretKey) {
+    let bytes = follow_decision_signing_bytes(decision);
+    let sig = secret_key.sign(&bytes);
+    decision.signature = signature_to_hex(&sig);
+}
+
 /// Verify a Post's signature against its author public key.
 pub fn verify_post_signature(post: &Post) -> Result<(), String> {
     let sig = hex_to_signature(&post.signature)?;
@@ -102,3 +204,213 @@ pub fn verify_interaction_signature(interaction: &Interaction) -> Result<(), Str
         .verify(&bytes, &sig)
         .map_err(|_| "signature verification failed".to_string())
 }
+
+/// Verify a PostEdit's signature against its author public key.
+pub fn verify_post_edit_signature(edit: &PostEdit) -> Result<(), String> {
+    let sig = hex_to_signature(&edit.signature)?;
+    let pubkey: PublicKey = edit
+        .author
+        .parse()
+        .map_err(|e| format!("invalid author pubkey: {e}"))?;
+    let bytes = post_edit_signing_bytes(edit);
+    pubkey
+        .verify(&bytes, &sig)
+        .map_err(|_| "signature verification failed".to_string())
+}
+
+/// Verify a Profile's signature against the public key it was received for.
+pub fn verify_profile_signature(profile: &Profile, pubkey: &str) -> Result<(), String> {
+    let sig = hex_to_signature(
+        profile
+            .signature
+            .as_deref()
+            .ok_or_else(|| "profile is not signed".to_string())?,
+    )?;
+    let key: PublicKey = pubkey
+        .parse()
+        .map_err(|e| format!("invalid profile pubkey: {e}"))?;
+    let bytes = profile_signing_bytes(profile, pubkey);
+    key.verify(&bytes, &sig)
+        .map_err(|_| "signature verification failed".to_string())
+}
+
+/// Verify a FollowRequest's signature against its requester public key.
+pub fn verify_follow_request_signature(req: &FollowRequest) -> Result<(), String> {
+    let sig = hex_to_signature(&req.signature)?;
+    let pubkey: PublicKey = req
+        .requester
+        .parse()
+        .map_err(|e| format!("invalid requester pubkey: {e}"))?;
+    let bytes = follow_request_signing_bytes(req);
+    pubkey
+        .verify(&bytes, &sig)
+        .map_err(|_| "signature verification failed".to_string())
+}
+
+/// Verify a FollowDecision's signature against its target public key.
+pub fn verify_follow_decision_signature(decision: &FollowDecision) -> Result<(), String> {
+    let sig = hex_to_signature(&decision.signature)?;
+    let pubkey: PublicKey = decision
+        .target
+        .parse()
+        .map_err(|e| format!("invalid target pubkey: {e}"))?;
+    let bytes = follow_decision_signing_bytes(decision);
+    pubkey
+        .verify(&bytes, &sig)
+        .map_err(|_| "signature verification failed".to_string())
+}
+
+/// Check a profile received for `pubkey`. A signed profile must verify. An
+/// unsigned one, saved before profiles were signed, is accepted without its
+/// pinned posts and timestamp, which only a signature can vouch for.
+pub fn check_profile(profile: &mut Profile, pubkey: &str) -> Result<(), String> {
+    if profile.signature.is_none() {
+        profile.pinned_posts.clear();
+        profile.updated_at = None;
+        return Ok(());
+    }
+    verify_profile_signature(profile, pubkey)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_post(secret_key: &SecretKey) -> Post {
+        Post {
+            id: "p1".to_string(),
+            author: secret_key.public().to_string(),
+            content: "hello".to_string(),
+            timestamp: 1_700_000_000_000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_post_signing_bytes_unchanged_without_optional_fields() {
+        let sk = SecretKey::from_bytes(&[7u8; 32]);
+        let post = test_post(&sk);
+        let legacy = serde_json::to_vec(&serde_json::json!({
+            "id": post.id,
+            "author": post.author,
+            "content": post.content,
+            "timestamp": post.timestamp,
+            "media": post.media,
+            "reply_to": post.reply_to,
+            "reply_to_author": post.reply_to_author,
+            "quote_of": post.quote_of,
+            "quote_of_author": post.quote_of_author,
+        }))
+        .unwrap();
+        assert_eq!(post_signing_bytes(&post), legacy);
+    }
+
+    #[test]
+    fn test_content_warning_is_signed() {
+        let sk = SecretKey::from_bytes(&[7u8; 32]);
+        let mut post = test_post(&sk);
+        post.content_warning = Some("spoilers".to_string());
+        post.sensitive_media = true;
+        sign_post(&mut post, &sk);
+        assert!(verify_post_signature(&post).is_ok());
+
+        let mut stripped = post.clone();
+        stripped.content_warning = None;
+        assert!(verify_post_signature(&stripped).is_err());
+        let mut unflagged = post.clone();
+        unflagged.sensitive_media = false;
+        assert!(verify_post_signature(&unflagged).is_err());
+    }
+
+    #[test]
+    fn test_post_edit_signed_by_author() {
+        let sk = SecretKey::from_bytes(&[7u8; 32]);
+        let mut edit = PostEdit {
+            id: "e1".to_string(),
+            post_id: "p1".to_string(),
+            author: sk.public().to_string(),
+            content: "fixed typo".to_string(),
+            media: vec![],
+            timestamp: 1_700_000_000_000,
+            signature: String::new(),
+        };
+        sign_post_edit(&mut edit, &sk);
+        assert!(verify_post_edit_signature(&edit).is_ok());
+
+        let mut retargeted = edit.clone();
+        retargeted.post_id = "p2".to_string();
+        assert!(verify_post_edit_signature(&retargeted).is_err());
+        let mut rewritten = edit.clone();
+        rewritten.content = "something else".to_string();
+        assert!(verify_post_edit_signature(&rewritten).is_err());
+    }
+
+    #[test]
+    fn test_profile_signature_covers_pinned_posts() {
+        let sk = SecretKey::from_bytes(&[7u8; 32]);
+        let pubkey = sk.public().to_string();
+        let mut profile = Profile {
+            display_name: "alice".to_string(),
+            bio: String::new(),
+            avatar_hash: None,
+            avatar_ticket: None,
+            is_private: false,
+            pinned_posts: vec!["p1".to_string()],
+            updated_at: None,
+            signature: None,
+        };
+        assert!(verify_profile_signature(&profile, &pubkey).is_err());
+        sign_profile(&mut profile, &sk);
+        assert!(verify_profile_signature(&profile, &pubkey).is_ok());
+
+        let other = SecretKey::from_bytes(&[8u8; 32]).public().to_string();
+        assert!(verify_profile_signature(&profile, &other).is_err());
+        profile.pinned_posts.push("p2".to_string());
+        assert!(verify_profile_signature(&profile, &pubkey).is_err());
+    }
+
+    #[test]
+    fn test_profile_signature_covers_updated_at() {
+        let sk = SecretKey::from_bytes(&[7u8; 32]);
+        let pubkey = sk.public().to_string();
+        let mut profile = Profile {
+            display_name: "alice".to_string(),
+            bio: String::new(),
+            avatar_hash: None,
+            avatar_ticket: None,
+            is_private: false,
+            pinned_posts: vec![],
+            updated_at: Some(1_700_000_000_000),
+            signature: None,
+        };
+        sign_profile(&mut profile, &sk);
+        assert!(verify_profile_signature(&profile, &pubkey).is_ok());
+
+        let mut bumped = profile.clone();
+        bumped.updated_at = Some(1_800_000_000_000);
+        assert!(verify_profile_signature(&bumped, &pubkey).is_err());
+        let mut stripped = profile.clone();
+        stripped.updated_at = None;
+        assert!(verify_profile_signature(&stripped, &pubkey).is_err());
+    }
+
+    #[test]
+    fn test_follow_decision_signed_by_target() {
+        let target = SecretKey::from_bytes(&[7u8; 32]);
+        let requester = SecretKey::from_bytes(&[8u8; 32]);
+        let mut decision = FollowDecision {
+            target: target.public().to_string(),
+            requester: requester.public().to_string(),
+            approved: true,
+            timestamp: 1_700_000_000_000,
+            signature: String::new(),
+        };
+        sign_follow_decision(&mut decision, &requester);
+        assert!(verify_follow_decision_signature(&decision).is_err());
+        sign_follow_decision(&mut decision, &target);
+        assert!(verify_follow_decision_signature(&decision).is_ok());
+
+        decision.approved = false;
+        assert!(verify_follow_decision_signature(&decision).is_err());
+    }
+}
@@ -7,18 +7,34 @@ pub struct Profile {
     pub avatar_hash: Option<String>,
     pub avatar_ticket: Option<String>,
     pub is_private: bool,
+    /// IDs of the owner's posts shown first on their profile, newest pin first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pinned_posts: Vec<String>,
+    /// When the owner last changed the profile, in ms. Signed, so a replayed
+    /// older copy can't replace a newer one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<u64>,
+    /// Owner's signature over the profile. Profiles saved before signing
+    /// existed have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MediaAttachment {
     pub hash: String,
     pub ticket: String,
     pub mime_type: String,
     pub filename: String,
     pub size: u64,
+    /// Hex-encoded per-file key for encrypted DM attachments.
+    /// Never set on post media, and omitted from JSON when absent so post
+    /// signatures are unaffected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Post {
     pub id: String,
     pub author: String,
@@ -33,6 +49,129 @@ pub struct Post {
     pub quote_of: Option<String>,
     pub quote_of_author: Option<String>,
     pub signature: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll: Option<Poll>,
+    /// Short warning shown in place of the content until the reader expands it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_warning: Option<String>,
+    /// The attached media should not be shown without a click-through.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sensitive_media: bool,
+    /// When set (ms), every node drops the post once this time has passed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Preview of the first link in `content`, made by the author's node so
+    /// readers never contact the linked site.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_preview: Option<Box<LinkPreview>>,
+}
+
+impl Post {
+    pub fn kind(&self) -> PostKind {
+        if self.quote_of.is_some() {
+            if self.content.is_empty() && self.media.is_empty() && self.poll.is_none() {
+                PostKind::Repost
+            } else {
+                PostKind::Quote
+            }
+        } else if self.reply_to.is_some() {
+            PostKind::Reply
+        } else {
+            PostKind::Post
+        }
+    }
+}
+
+/// What a post is, read from its fields: a repost is a `quote_of` with no
+/// content, media or poll of its own, a quote adds commentary. Posts signed
+/// before kinds were named classify the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PostKind {
+    Post,
+    Reply,
+    Repost,
+    Quote,
+}
+
+impl PostKind {
+    /// Name stored in the `kind` column. Also the notification kind raised
+    /// for the quoted author by reposts and quotes.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PostKind::Post => "post",
+            PostKind::Reply => "reply",
+            PostKind::Repost => "repost",
+            PostKind::Quote => "quote",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Thumbnail re-hosted as a blob by the author.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<MediaAttachment>,
+}
+
+/// A signed revision of a post's content and media, by the post's author.
+/// The original post is never modified; readers show the newest edit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostEdit {
+    pub id: String,
+    pub post_id: String,
+    pub author: String,
+    pub content: String,
+    #[serde(default)]
+    pub media: Vec<MediaAttachment>,
+    pub timestamp: u64,
+    pub signature: String,
+}
+
+/// How posts with a content warning or sensitive media are shown, globally
+/// or for one author.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SensitiveMode {
+    Show,
+    #[default]
+    Collapse,
+    /// Left out of the home feed entirely; collapsed elsewhere.
+    Hide,
+}
+
+impl SensitiveMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SensitiveMode::Show => "show",
+            SensitiveMode::Collapse => "collapse",
+            SensitiveMode::Hide => "hide",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "show" => Some(SensitiveMode::Show),
+            "collapse" => Some(SensitiveMode::Collapse),
+            "hide" => Some(SensitiveMode::Hide),
+            _ => None,
+        }
+    }
+}
+
+/// A poll attached to a post, covered by the post signature.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Poll {
+    pub options: Vec<String>,
+    /// Votes cast after this time (ms) are not counted.
+    pub closes_at: u64,
+    /// Voters may pick several options instead of exactly one.
+    #[serde(default)]
+    pub multiple: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,9 +185,35 @@ pub struct Interaction {
     pub signature: String,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+/// `Like` serializes as the bare string `"Like"`, so likes signed and
+/// stored before reactions existed keep the same wire form.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum InteractionKind {
     Like,
+    /// A single emoji, checked by `validate_reaction`.
+    Reaction(String),
+    /// Indices of the chosen options of the target post's poll.
+    Vote(Vec<u32>),
+}
+
+impl InteractionKind {
+    /// Name stored in the `kind` column, without the reaction payload.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InteractionKind::Like => "Like",
+            InteractionKind::Reaction(_) => "Reaction",
+            InteractionKind::Vote(_) => "Vote",
+        }
+    }
+
+    /// Notification kind raised when someone interacts with our post.
+    pub fn notification_kind(&self) -> &'static str {
+        match self {
+            InteractionKind::Like => "like",
+            InteractionKind::Reaction(_) => "reaction",
+            InteractionKind::Vote(_) => "vote",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,3 +230,314 @@ pub struct FollowerEntry {
     pub last_seen: u64,
     pub is_online: bool,
 }
+
+/// Signed request to follow a private profile, sent to its owner.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FollowRequest {
+    pub requester: String,
+    pub target: String,
+    pub timestamp: u64,
+    pub signature: String,
+}
+
+/// Signed answer from the owner of a private profile to a follow request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FollowDecision {
+    pub target: String,
+    pub requester: String,
+    pub approved: bool,
+    pub timestamp: u64,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FollowRequestStatus {
+    Pending,
+    Approved,
+    Denied,
+}
+
+impl FollowRequestStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FollowRequestStatus::Pending => "pending",
+            FollowRequestStatus::Approved => "approved",
+            FollowRequestStatus::Denied => "denied",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(FollowRequestStatus::Pending),
+            "approved" => Some(FollowRequestStatus::Approved),
+            "denied" => Some(FollowRequestStatus::Denied),
+            _ => None,
+        }
+    }
+}
+
+/// A follow request received by this node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FollowRequestEntry {
+    pub pubkey: String,
+    pub timestamp: u64,
+    pub status: FollowRequestStatus,
+}
+
+/// A follower allowed to read this node's posts while its profile is private.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovedFollower {
+    pub pubkey: String,
+    pub approved_at: u64,
+}
+
+/// A named set of pubkeys, followed or not, that custom feeds draw from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserList {
+    pub id: String,
+    pub name: String,
+    pub members: Vec<String>,
+    pub created_at: u64,
+}
+
+/// A saved feed: posts by the members of its lists (everyone when it has
+/// none), narrowed by hashtags and the media and reply filters.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomFeed {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub list_ids: Vec<String>,
+    /// Normalized hashtags; a post matches if it carries any of them.
+    #[serde(default)]
+    pub hashtags: Vec<String>,
+    #[serde(default)]
+    pub media_only: bool,
+    #[serde(default)]
+    pub exclude_replies: bool,
+    pub created_at: u64,
+}
+
+/// How a muted word's pattern is matched. Words and phrases ignore case;
+/// a word only matches whole words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MutedWordKind {
+    Word,
+    Phrase,
+    Regex,
+}
+
+impl MutedWordKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MutedWordKind::Word => "word",
+            MutedWordKind::Phrase => "phrase",
+            MutedWordKind::Regex => "regex",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "word" => Some(MutedWordKind::Word),
+            "phrase" => Some(MutedWordKind::Phrase),
+            "regex" => Some(MutedWordKind::Regex),
+            _ => None,
+        }
+    }
+}
+
+/// Where a muted word applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterScope {
+    Feed,
+    Notifications,
+    Both,
+}
+
+impl FilterScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FilterScope::Feed => "feed",
+            FilterScope::Notifications => "notifications",
+            FilterScope::Both => "both",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "feed" => Some(FilterScope::Feed),
+            "notifications" => Some(FilterScope::Notifications),
+            "both" => Some(FilterScope::Both),
+            _ => None,
+        }
+    }
+}
+
+/// What muting someone hides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MuteScope {
+    /// Their posts, reposts and notifications, and others' reposts and
+    /// quotes of them.
+    #[default]
+    Posts,
+    /// Only their reposts.
+    Reposts,
+    /// Only the notifications they raise.
+    Notifications,
+    /// Their direct messages still arrive, but silently.
+    Dms,
+}
+
+impl MuteScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MuteScope::Posts => "posts",
+            MuteScope::Reposts => "reposts",
+            MuteScope::Notifications => "notifications",
+            MuteScope::Dms => "dms",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "posts" => Some(MuteScope::Posts),
+            "reposts" => Some(MuteScope::Reposts),
+            "notifications" => Some(MuteScope::Notifications),
+            "dms" => Some(MuteScope::Dms),
+            _ => None,
+        }
+    }
+}
+
+/// A muted user, kept locally.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mute {
+    pub pubkey: String,
+    pub scope: MuteScope,
+    /// When set (ms), the mute lifts once this time has passed.
+    pub expires_at: Option<u64>,
+    pub created_at: u64,
+}
+
+/// A word, phrase or regex whose matching posts are collapsed in the feed
+/// or raise no notifications, kept locally.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MutedWord {
+    pub id: String,
+    pub pattern: String,
+    pub kind: MutedWordKind,
+    pub scope: FilterScope,
+    /// When set (ms), the filter stops applying once this time has passed.
+    pub expires_at: Option<u64>,
+    pub created_at: u64,
+}
+
+/// A named list of pubkeys, kept locally, that posts can be addressed to
+/// instead of everyone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Audience {
+    pub id: String,
+    pub name: String,
+    pub members: Vec<String>,
+    pub created_at: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn media() -> MediaAttachment {
+        MediaAttachment {
+            hash: "h".to_string(),
+            ticket: String::new(),
+            mime_type: "image/png".to_string(),
+            filename: "a.png".to_string(),
+            size: 1,
+            key: None,
+        }
+    }
+
+    #[test]
+    fn test_post_kind() {
+        let quote_of = Some("p0".to_string());
+        let reply_to = Some("p1".to_string());
+        let cases = [
+            (
+                Post {
+                    content: "hi".to_string(),
+                    ..Default::default()
+                },
+                PostKind::Post,
+            ),
+            (
+                Post {
+                    content: "hi".to_string(),
+                    reply_to: reply_to.clone(),
+                    ..Default::default()
+                },
+                PostKind::Reply,
+            ),
+            (
+                Post {
+                    quote_of: quote_of.clone(),
+                    ..Default::default()
+                },
+                PostKind::Repost,
+            ),
+            (
+                Post {
+                    content: "look".to_string(),
+                    quote_of: quote_of.clone(),
+                    ..Default::default()
+                },
+                PostKind::Quote,
+            ),
+            // Media or a poll alone is commentary enough
+            (
+                Post {
+                    media: vec![media()],
+                    quote_of: quote_of.clone(),
+                    ..Default::default()
+                },
+                PostKind::Quote,
+            ),
+            (
+                Post {
+                    poll: Some(Poll {
+                        options: vec!["a".to_string(), "b".to_string()],
+                        closes_at: 1,
+                        multiple: false,
+                    }),
+                    quote_of: quote_of.clone(),
+                    ..Default::default()
+                },
+                PostKind::Quote,
+            ),
+            // A quote that also replies is still a quote
+            (
+                Post {
+                    content: "look".to_string(),
+                    reply_to: reply_to.clone(),
+                    quote_of: quote_of.clone(),
+                    ..Default::default()
+                },
+                PostKind::Quote,
+            ),
+            (
+                Post {
+                    reply_to,
+                    quote_of,
+                    ..Default::default()
+                },
+                PostKind::Repost,
+            ),
+        ];
+        for (post, kind) in cases {
+            assert_eq!(post.kind(), kind, "{post:?}");
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use crate::types::{Interaction, Post, Profile};
+use crate::dm::AudiencePost;
+use crate::types::{Interaction, InteractionKind, LinkPreview, Poll, Post, PostEdit, Profile};
 use iroh::PublicKey;
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -8,6 +9,19 @@ pub const MAX_TIMESTAMP_DRIFT_MS: u64 = 5 * 60 * 1000;
 pub const MAX_BLOB_SIZE: usize = 50 * 1024 * 1024;
 pub const MAX_DISPLAY_NAME_LEN: usize = 200;
 pub const MAX_BIO_LEN: usize = 2_000;
+pub const MAX_POLL_OPTIONS: usize = 10;
+pub const MAX_POLL_OPTION_LEN: usize = 200;
+pub const MAX_CONTENT_WARNING_LEN: usize = 200;
+pub const MAX_PINNED_POSTS: usize = 3;
+pub const MAX_AUDIENCE_NAME_LEN: usize = 100;
+pub const MAX_LIST_NAME_LEN: usize = 100;
+pub const MAX_HASHTAG_LEN: usize = 64;
+pub const MAX_MUTED_WORD_LEN: usize = 200;
+pub const MAX_LINK_URL_LEN: usize = 2_000;
+pub const MAX_LINK_TITLE_LEN: usize = 300;
+pub const MAX_LINK_DESCRIPTION_LEN: usize = 1_000;
+/// Longest emoji ZWJ/tag sequences (families, subdivision flags) fit well within this.
+pub const MAX_REACTION_LEN: usize = 32;
 
 /// Return the first 8 characters of an ID string, or the whole string if shorter.
 pub fn short_id(id: &str) -> &str {
@@ -36,10 +50,76 @@ pub fn validate_profile(profile: &Profile) -> Result<(), String> {
             MAX_BIO_LEN
         ));
     }
+    if profile.pinned_posts.len() > MAX_PINNED_POSTS {
+        return Err(format!(
+            "too many pinned posts: {} (max {MAX_PINNED_POSTS})",
+            profile.pinned_posts.len()
+        ));
+    }
+    for (i, id) in profile.pinned_posts.iter().enumerate() {
+        if profile.pinned_posts[..i].contains(id) {
+            return Err(format!("post {id} is pinned twice"));
+        }
+    }
+    Ok(())
+}
+
+/// Whether `c` can appear in an emoji sequence.
+fn is_emoji_char(c: char) -> bool {
+    matches!(c as u32,
+        0x1F000..=0x1FAFF // pictographs, emoticons, symbols, regional indicators, skin tones
+        | 0x2600..=0x27BF // misc symbols, dingbats
+        | 0x2300..=0x23FF // misc technical
+        | 0x2B00..=0x2BFF // arrows, stars
+        | 0x2190..=0x21FF // arrows
+        | 0x00A9 | 0x00AE | 0x203C | 0x2049 | 0x2122 | 0x2139
+        | 0x3030 | 0x303D | 0x3297 | 0x3299
+        | 0x200D // zero width joiner
+        | 0xFE0E | 0xFE0F // variation selectors
+        | 0x20E3 // combining keycap
+        | 0xE0020..=0xE007F // tag sequences
+    )
+}
+
+/// A reaction must be a single short emoji sequence. Keycap digits and
+/// `#`/`*` are only allowed together with the combining keycap.
+pub fn validate_reaction(emoji: &str) -> Result<(), String> {
+    if emoji.is_empty() {
+        return Err("reaction is empty".to_string());
+    }
+    if emoji.len() > MAX_REACTION_LEN {
+        return Err(format!(
+            "reaction too long: {} bytes (max {})",
+            emoji.len(),
+            MAX_REACTION_LEN
+        ));
+    }
+    let keycap = emoji.contains('\u{20E3}');
+    let valid = emoji
+        .chars()
+        .all(|c| is_emoji_char(c) || (keycap && (c.is_ascii_digit() || c == '#' || c == '*')));
+    if !valid {
+        return Err(format!("reaction is not an emoji: {emoji:?}"));
+    }
     Ok(())
 }
 
 pub fn validate_interaction(interaction: &Interaction) -> Result<(), String> {
+    match &interaction.kind {
+        InteractionKind::Like => {}
+        InteractionKind::Reaction(emoji) => validate_reaction(emoji)?,
+        InteractionKind::Vote(choices) => {
+            if choices.is_empty() || choices.len() > MAX_POLL_OPTIONS {
+                return Err(format!("vote has {} choices", choices.len()));
+            }
+            let mut sorted = choices.clone();
+            sorted.sort_unstable();
+            sorted.dedup();
+            if sorted.len() != choices.len() {
+                return Err("vote has duplicate choices".to_string());
+            }
+        }
+    }
     let now = now_millis();
     if interaction.timestamp > now + MAX_TIMESTAMP_DRIFT_MS {
         return Err(format!(
@@ -65,6 +145,23 @@ pub fn validate_post(post: &Post) -> Result<(), String> {
             MAX_MEDIA_COUNT
         ));
     }
+    if let Some(poll) = &post.poll {
+        validate_poll(poll, post.timestamp)?;
+    }
+    if let Some(warning) = &post.content_warning {
+        if warning.trim().is_empty() {
+            return Err("content warning is empty".to_string());
+        }
+        if warning.len() > MAX_CONTENT_WARNING_LEN {
+            return Err(format!(
+                "content warning too long: {} bytes (max {MAX_CONTENT_WARNING_LEN})",
+                warning.len()
+            ));
+        }
+    }
+    if let Some(preview) = &post.link_preview {
+        validate_link_preview(preview)?;
+    }
     let now = now_millis();
     if post.timestamp > now + MAX_TIMESTAMP_DRIFT_MS {
         return Err(format!(
@@ -72,9 +169,124 @@ pub fn validate_post(post: &Post) -> Result<(), String> {
             post.timestamp, now
         ));
     }
+    if let Some(expires_at) = post.expires_at {
+        if expires_at <= post.timestamp {
+            return Err("post expires before it was posted".to_string());
+        }
+        if expires_at <= now {
+            return Err(format!("post expired at {expires_at} (now: {now})"));
+        }
+    }
+    Ok(())
+}
+
+/// A post shared with an audience list, including the post itself.
+pub fn validate_audience_post(shared: &AudiencePost) -> Result<(), String> {
+    if shared.audience.trim().is_empty() || shared.audience.len() > MAX_AUDIENCE_NAME_LEN {
+        return Err("invalid audience name".to_string());
+    }
+    validate_post(&shared.post)
+}
+
+pub fn validate_post_edit(edit: &PostEdit) -> Result<(), String> {
+    if edit.content.len() > MAX_POST_CONTENT_LEN {
+        return Err(format!(
+            "edit content too long: {} bytes (max {})",
+            edit.content.len(),
+            MAX_POST_CONTENT_LEN
+        ));
+    }
+    if edit.media.len() > MAX_MEDIA_COUNT {
+        return Err(format!(
+            "too many media attachments: {} (max {})",
+            edit.media.len(),
+            MAX_MEDIA_COUNT
+        ));
+    }
+    let now = now_millis();
+    if edit.timestamp > now + MAX_TIMESTAMP_DRIFT_MS {
+        return Err(format!(
+            "edit timestamp {} is too far in the future (now: {})",
+            edit.timestamp, now
+        ));
+    }
     Ok(())
 }
 
+fn validate_link_preview(preview: &LinkPreview) -> Result<(), String> {
+    if !(preview.url.starts_with("https://") || preview.url.starts_with("http://")) {
+        return Err("link preview url is not http(s)".to_string());
+    }
+    if preview.url.len() > MAX_LINK_URL_LEN {
+        return Err(format!(
+            "link preview url too long: {} bytes (max {MAX_LINK_URL_LEN})",
+            preview.url.len()
+        ));
+    }
+    if preview.title.trim().is_empty() {
+        return Err("link preview title is empty".to_string());
+    }
+    if preview.title.len() > MAX_LINK_TITLE_LEN {
+        return Err(format!(
+            "link preview title too long: {} bytes (max {MAX_LINK_TITLE_LEN})",
+            preview.title.len()
+        ));
+    }
+    if let Some(description) = &preview.description
+        && description.len() > MAX_LINK_DESCRIPTION_LEN
+    {
+        return Err(format!(
+            "link preview description too long: {} bytes (max {MAX_LINK_DESCRIPTION_LEN})",
+            description.len()
+        ));
+    }
+    if let Some(image) = &preview.image
+        && !image.mime_type.starts_with("image/")
+    {
+        return Err(format!(
+            "link preview image is not an image: {}",
+            image.mime_type
+        ));
+    }
+    Ok(())
+}
+
+fn validate_poll(poll: &Poll, post_timestamp: u64) -> Result<(), String> {
+    if poll.options.len() < 2 || poll.options.len() > MAX_POLL_OPTIONS {
+        return Err(format!(
+            "poll must have 2 to {MAX_POLL_OPTIONS} options, got {}",
+            poll.options.len()
+        ));
+    }
+    for option in &poll.options {
+        if option.trim().is_empty() {
+            return Err("poll option is empty".to_string());
+        }
+        if option.len() > MAX_POLL_OPTION_LEN {
+            return Err(format!(
+                "poll option too long: {} bytes (max {MAX_POLL_OPTION_LEN})",
+                option.len()
+            ));
+        }
+    }
+    if poll.closes_at <= post_timestamp {
+        return Err("poll closes before it was posted".to_string());
+    }
+    Ok(())
+}
+
+/// Whether `choices` is a countable vote on `poll`: every index in range,
+/// none repeated, exactly one choice unless the poll allows several.
+pub fn vote_matches_poll(poll: &Poll, choices: &[u32]) -> bool {
+    !choices.is_empty()
+        && (poll.multiple || choices.len() == 1)
+        && choices.iter().all(|&i| (i as usize) < poll.options.len())
+        && choices
+            .iter()
+            .enumerate()
+            .all(|(n, i)| !choices[..n].contains(i))
+}
+
 /// Extract all valid pubkey mentions from post content.
 /// Mentions use the format @{pubkey} where pubkey is a valid iroh public key hex string.
 pub fn parse_mentions(content: &str) -> Vec<String> {
@@ -102,3 +314,63 @@ pub fn parse_mentions(content: &str) -> Vec<String> {
     }
     mentions
 }
+
+/// Canonical form of a hashtag for matching: without the leading `#`,
+/// lowercased, and only ASCII letters, digits and underscores.
+pub fn normalize_hashtag(tag: &str) -> Result<String, String> {
+    let tag = tag.trim();
+    let tag = tag.strip_prefix('#').unwrap_or(tag).to_ascii_lowercase();
+    if tag.is_empty() {
+        return Err("hashtag is empty".to_string());
+    }
+    if tag.len() > MAX_HASHTAG_LEN {
+        return Err(format!("hashtag exceeds {MAX_HASHTAG_LEN} characters"));
+    }
+    if !tag.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_') {
+        return Err(format!("invalid hashtag: #{tag}"));
+    }
+    Ok(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_hashtag() {
+        assert_eq!(normalize_hashtag("#Rust").unwrap(), "rust");
+        assert_eq!(normalize_hashtag(" p2p_apps ").unwrap(), "p2p_apps");
+        for bad in ["", "#", "two words", "#tag!", "c++", "*", &"a".repeat(65)] {
+            assert!(normalize_hashtag(bad).is_err(), "{bad:?} should be invalid");
+        }
+    }
+
+    #[test]
+    fn test_validate_reaction() {
+        for ok in ["👍", "❤️", "🎉", "👍🏽", "👨‍👩‍👧", "🇺🇸", "1️⃣", "#️⃣"]
+        {
+            assert!(validate_reaction(ok).is_ok(), "{ok:?} should be valid");
+        }
+        for bad in ["", "like", "1", "👍 nice", "<script>", &"👍".repeat(9)] {
+            assert!(validate_reaction(bad).is_err(), "{bad:?} should be invalid");
+        }
+    }
+
+    #[test]
+    fn test_validate_post_rejects_expired() {
+        let now = now_millis();
+        let mut post = Post {
+            id: "p1".to_string(),
+            author: "a".to_string(),
+            content: "gone soon".to_string(),
+            timestamp: now - 2_000,
+            expires_at: Some(now + 60_000),
+            ..Default::default()
+        };
+        assert!(validate_post(&post).is_ok());
+        post.expires_at = Some(now - 1_000);
+        assert!(validate_post(&post).is_err());
+        post.expires_at = Some(post.timestamp);
+        assert!(validate_post(&post).is_err());
+    }
+}
@@ -0,0 +1,5 @@
+ALTER TABLE dm_outbox ADD COLUMN next_retry_at INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE dm_outbox ADD COLUMN failed INTEGER NOT NULL DEFAULT 0;
+CREATE INDEX IF NOT EXISTS idx_dm_outbox_due ON dm_outbox(failed, next_retry_at);
+
+ALTER TABLE dm_messages ADD COLUMN failed INTEGER NOT NULL DEFAULT 0;
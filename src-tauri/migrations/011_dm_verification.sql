@@ -0,0 +1,10 @@
+ALTER TABLE dm_conversations ADD COLUMN verified INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE dm_conversations ADD COLUMN verified_at INTEGER;
+
+-- Alias a verified peer was known by, to warn if the alias later points at another key
+CREATE TABLE IF NOT EXISTS verified_aliases (
+    alias TEXT PRIMARY KEY,
+    pubkey TEXT NOT NULL,
+    verified_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_verified_aliases_pubkey ON verified_aliases(pubkey);
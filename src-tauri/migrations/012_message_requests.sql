@@ -0,0 +1,7 @@
+-- Conversations opened by peers outside the DM accept policy wait here until accepted
+ALTER TABLE dm_conversations ADD COLUMN is_request INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
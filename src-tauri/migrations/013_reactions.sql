@@ -0,0 +1,5 @@
+-- Emoji payload for Reaction interactions, '' for likes.
+-- Each author may leave one of each emoji on a post.
+ALTER TABLE interactions ADD COLUMN emoji TEXT NOT NULL DEFAULT '';
+DROP INDEX IF EXISTS idx_interactions_unique;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_interactions_unique ON interactions(author, kind, emoji, target_post_id);
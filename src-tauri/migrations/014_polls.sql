@@ -0,0 +1,6 @@
+ALTER TABLE posts ADD COLUMN poll_json TEXT;
+
+-- JSON array of chosen option indices for Vote interactions
+ALTER TABLE interactions ADD COLUMN vote_json TEXT;
+-- One vote per author per poll, whatever the choices
+CREATE UNIQUE INDEX IF NOT EXISTS idx_interactions_one_vote ON interactions(author, target_post_id) WHERE kind='Vote';
@@ -0,0 +1,12 @@
+-- Signed edits of a post; the original row in posts is never changed
+CREATE TABLE IF NOT EXISTS post_revisions (
+    id TEXT PRIMARY KEY,
+    post_id TEXT NOT NULL,
+    author TEXT NOT NULL,
+    content TEXT NOT NULL,
+    media_json TEXT NOT NULL DEFAULT '[]',
+    timestamp INTEGER NOT NULL,
+    signature TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_post_revisions_post ON post_revisions(post_id, timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_post_revisions_author ON post_revisions(author, timestamp);
@@ -0,0 +1,8 @@
+ALTER TABLE posts ADD COLUMN content_warning TEXT;
+ALTER TABLE posts ADD COLUMN sensitive_media INTEGER NOT NULL DEFAULT 0;
+
+-- Per-author override of the global sensitive content setting
+CREATE TABLE IF NOT EXISTS sensitive_overrides (
+    pubkey TEXT PRIMARY KEY,
+    mode TEXT NOT NULL
+);
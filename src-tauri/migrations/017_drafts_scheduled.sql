@@ -0,0 +1,18 @@
+-- Unsigned post bodies; signed only when published
+CREATE TABLE IF NOT EXISTS drafts (
+    id TEXT PRIMARY KEY,
+    body_json TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS scheduled_posts (
+    id TEXT PRIMARY KEY,
+    body_json TEXT NOT NULL,
+    publish_at INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    -- Set when publishing failed; the entry is then held until rescheduled
+    last_error TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_scheduled_posts_publish_at ON scheduled_posts(publish_at);
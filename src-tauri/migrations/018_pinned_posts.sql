@@ -0,0 +1,3 @@
+-- Pinned post IDs as a JSON array, and the owner's signature over the profile
+ALTER TABLE profiles ADD COLUMN pinned_posts TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE profiles ADD COLUMN signature TEXT;
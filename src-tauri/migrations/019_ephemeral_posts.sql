@@ -0,0 +1,2 @@
+ALTER TABLE posts ADD COLUMN expires_at INTEGER;
+CREATE INDEX IF NOT EXISTS idx_posts_expires_at ON posts(expires_at) WHERE expires_at IS NOT NULL;
@@ -0,0 +1 @@
+ALTER TABLE posts ADD COLUMN link_preview_json TEXT;
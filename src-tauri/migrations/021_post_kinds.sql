@@ -0,0 +1,15 @@
+-- Kind of each post as classified by Post::kind(), so reposts and quotes
+-- can be counted apart
+ALTER TABLE posts ADD COLUMN kind TEXT NOT NULL DEFAULT 'post';
+UPDATE posts SET kind = CASE
+    WHEN quote_of IS NOT NULL AND content = '' AND media_json = '[]' AND poll_json IS NULL
+        THEN 'repost'
+    WHEN quote_of IS NOT NULL THEN 'quote'
+    WHEN reply_to IS NOT NULL THEN 'reply'
+    ELSE 'post'
+END;
+CREATE INDEX IF NOT EXISTS idx_posts_quote_kind ON posts(quote_of, kind);
+
+-- Reposts used to raise "quote" notifications
+UPDATE notifications SET kind = 'repost'
+WHERE kind = 'quote' AND post_id IN (SELECT id FROM posts WHERE kind = 'repost');
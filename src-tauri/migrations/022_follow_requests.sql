@@ -0,0 +1,21 @@
+-- Follow requests received while our profile is private
+CREATE TABLE IF NOT EXISTS follow_requests (
+    requester TEXT PRIMARY KEY,
+    timestamp INTEGER NOT NULL,
+    signature TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending'
+);
+CREATE INDEX IF NOT EXISTS idx_follow_requests_status ON follow_requests(status, timestamp DESC);
+
+-- Followers allowed to sync and read our posts while the profile is private
+CREATE TABLE IF NOT EXISTS approved_followers (
+    pubkey TEXT PRIMARY KEY,
+    approved_at INTEGER NOT NULL
+);
+
+-- Our own requests to follow private profiles
+CREATE TABLE IF NOT EXISTS sent_follow_requests (
+    target TEXT PRIMARY KEY,
+    requested_at INTEGER NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending'
+);
@@ -0,0 +1,23 @@
+-- Keys our private feed is sealed under; the newest one is current
+CREATE TABLE IF NOT EXISTS feed_keys (
+    key_id TEXT PRIMARY KEY,
+    key BLOB NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+-- Approved followers each feed key has been delivered to
+CREATE TABLE IF NOT EXISTS feed_key_deliveries (
+    key_id TEXT NOT NULL,
+    pubkey TEXT NOT NULL,
+    delivered_at INTEGER NOT NULL,
+    PRIMARY KEY (key_id, pubkey)
+);
+
+-- Feed keys received from private profiles we follow
+CREATE TABLE IF NOT EXISTS received_feed_keys (
+    author TEXT NOT NULL,
+    key_id TEXT NOT NULL,
+    key BLOB NOT NULL,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (author, key_id)
+);
@@ -0,0 +1,23 @@
+-- Named lists of pubkeys that posts can be addressed to
+CREATE TABLE IF NOT EXISTS audiences (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS audience_members (
+    audience_id TEXT NOT NULL REFERENCES audiences(id) ON DELETE CASCADE,
+    pubkey TEXT NOT NULL,
+    added_at INTEGER NOT NULL,
+    PRIMARY KEY (audience_id, pubkey)
+);
+CREATE INDEX IF NOT EXISTS idx_audience_members_pubkey ON audience_members(pubkey);
+
+-- Our own posts addressed to an audience, for serving them only to members
+CREATE TABLE IF NOT EXISTS audience_posts (
+    post_id TEXT PRIMARY KEY,
+    audience_id TEXT NOT NULL
+);
+
+-- Name of the audience a post was shared with; NULL for public posts
+ALTER TABLE posts ADD COLUMN audience TEXT;
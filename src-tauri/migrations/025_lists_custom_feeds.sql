@@ -0,0 +1,24 @@
+-- Named sets of pubkeys for custom feeds, followed or not
+CREATE TABLE IF NOT EXISTS lists (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS list_members (
+    list_id TEXT NOT NULL REFERENCES lists(id) ON DELETE CASCADE,
+    pubkey TEXT NOT NULL,
+    added_at INTEGER NOT NULL,
+    PRIMARY KEY (list_id, pubkey)
+);
+
+-- Saved feeds combining lists, hashtags and post filters
+CREATE TABLE IF NOT EXISTS custom_feeds (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    list_ids_json TEXT NOT NULL DEFAULT '[]',
+    hashtags_json TEXT NOT NULL DEFAULT '[]',
+    media_only INTEGER NOT NULL DEFAULT 0,
+    exclude_replies INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL
+);
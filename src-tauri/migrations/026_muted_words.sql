@@ -0,0 +1,10 @@
+-- Muted words, phrases and regexes, collapsing matching posts in the feed
+-- and/or keeping them out of notifications
+CREATE TABLE IF NOT EXISTS muted_words (
+    id TEXT PRIMARY KEY,
+    pattern TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    expires_at INTEGER,
+    created_at INTEGER NOT NULL
+);
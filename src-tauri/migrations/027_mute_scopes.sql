@@ -0,0 +1,4 @@
+-- Mutes gain a scope (what they hide) and an optional expiry. Existing
+-- mutes keep hiding everything, permanently.
+ALTER TABLE mutes ADD COLUMN scope TEXT NOT NULL DEFAULT 'posts';
+ALTER TABLE mutes ADD COLUMN expires_at INTEGER;
@@ -0,0 +1,10 @@
+-- Ratchet headers of envelopes already decrypted, so a resent copy of one
+-- is acknowledged again instead of failing to decrypt
+CREATE TABLE IF NOT EXISTS dm_received_envelopes (
+    peer_pubkey TEXT NOT NULL,
+    dh_public TEXT NOT NULL,
+    message_number INTEGER NOT NULL,
+    message_id TEXT,
+    received_at INTEGER NOT NULL,
+    PRIMARY KEY (peer_pubkey, dh_public, message_number)
+);
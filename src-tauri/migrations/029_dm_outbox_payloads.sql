@@ -0,0 +1,4 @@
+-- Payloads queued before we had a ratchet session with the peer. The flush
+-- seals one into envelope_json, left empty until then, once a handshake
+-- succeeds.
+ALTER TABLE dm_outbox ADD COLUMN payload_json TEXT;
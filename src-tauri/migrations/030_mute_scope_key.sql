@@ -0,0 +1,13 @@
+-- A pubkey can be muted for several scopes at once, each with its own
+-- expiry, so mutes are keyed by (pubkey, scope)
+CREATE TABLE mutes_by_scope (
+    pubkey TEXT NOT NULL,
+    scope TEXT NOT NULL DEFAULT 'posts',
+    expires_at INTEGER,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (pubkey, scope)
+);
+INSERT INTO mutes_by_scope (pubkey, scope, expires_at, created_at)
+    SELECT pubkey, scope, expires_at, created_at FROM mutes;
+DROP TABLE mutes;
+ALTER TABLE mutes_by_scope RENAME TO mutes;
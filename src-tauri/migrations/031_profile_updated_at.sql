@@ -0,0 +1,3 @@
+-- When the owner last changed the profile, as signed by them. NULL for
+-- profiles from before it was sent.
+ALTER TABLE profiles ADD COLUMN updated_at INTEGER;
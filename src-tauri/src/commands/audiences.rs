@@ -0,0 +1,87 @@
+use crate::state::{AppState, generate_id};
+use iroh::EndpointId;
+use iroh_social_types::{Audience, MAX_AUDIENCE_NAME_LEN, now_millis};
+use std::sync::Arc;
+use tauri::State;
+
+/// Trimmed, non-empty name and deduplicated, well-formed member pubkeys.
+fn check_audience(name: &str, members: Vec<String>) -> Result<(String, Vec<String>), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("audience name is empty".to_string());
+    }
+    if name.len() > MAX_AUDIENCE_NAME_LEN {
+        return Err(format!(
+            "audience name exceeds {MAX_AUDIENCE_NAME_LEN} characters"
+        ));
+    }
+    let mut checked = Vec::with_capacity(members.len());
+    for member in members {
+        if member.parse::<EndpointId>().is_err() {
+            return Err(format!("invalid member pubkey: {member}"));
+        }
+        if !checked.contains(&member) {
+            checked.push(member);
+        }
+    }
+    Ok((name.to_string(), checked))
+}
+
+#[tauri::command]
+pub async fn get_audiences(state: State<'_, Arc<AppState>>) -> Result<Vec<Audience>, String> {
+    state.storage.get_audiences().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn create_audience(
+    state: State<'_, Arc<AppState>>,
+    name: String,
+    members: Vec<String>,
+) -> Result<Audience, String> {
+    let (name, members) = check_audience(&name, members)?;
+    let audience = Audience {
+        id: generate_id(),
+        name,
+        members,
+        created_at: now_millis(),
+    };
+    state
+        .storage
+        .create_audience(&audience)
+        .map_err(|e| e.to_string())?;
+    log::info!(
+        "[audience] created {:?} with {} members",
+        audience.name,
+        audience.members.len()
+    );
+    Ok(audience)
+}
+
+/// Rename an audience and replace its members. Members removed here stop
+/// receiving its posts from sync; posts they already have stay with them.
+#[tauri::command]
+pub async fn update_audience(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    name: String,
+    members: Vec<String>,
+) -> Result<(), String> {
+    let (name, members) = check_audience(&name, members)?;
+    if !state
+        .storage
+        .update_audience(&id, &name, &members, now_millis())
+        .map_err(|e| e.to_string())?
+    {
+        return Err(format!("audience {id} not found"));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_audience(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    state
+        .storage
+        .delete_audience(&id)
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -1,4 +1,6 @@
+use crate::crypto::{decrypt_attachment, encrypt_attachment, hex};
 use crate::state::AppState;
+use bytes::Bytes;
 use iroh_blobs::{HashAndFormat, ticket::BlobTicket};
 use iroh_social_types::MAX_BLOB_SIZE;
 use std::sync::Arc;
@@ -96,34 +98,31 @@ pub async fn add_blob_bytes(
     }))
 }
 
-#[tauri::command]
-pub async fn fetch_blob_bytes(
-    state: State<'_, Arc<AppState>>,
-    ticket: String,
-) -> Result<Vec<u8>, String> {
+/// Return a blob's bytes from the local store, fetching it from the ticket's provider if needed.
+async fn fetch_ticket_bytes(state: &AppState, ticket: &str) -> Result<Bytes, String> {
     let ticket: BlobTicket = ticket.parse().map_err(|e| format!("{e}"))?;
-    let store = state.store.clone();
-    let endpoint = state.endpoint.clone();
-    let blobs = state.blobs.clone();
 
-    if let Ok(bytes) = store.get_bytes(ticket.hash()).await {
-        return Ok(bytes.to_vec());
+    if let Ok(bytes) = state.store.get_bytes(ticket.hash()).await {
+        return Ok(bytes);
     }
 
     log::info!("[blob] fetching {} from remote...", ticket.hash());
-    let conn = endpoint
+    let conn = state
+        .endpoint
         .connect(ticket.addr().clone(), iroh_blobs::ALPN)
         .await
         .map_err(|e| e.to_string())?;
 
     let hash_and_format: HashAndFormat = ticket.hash_and_format();
-    blobs
+    state
+        .blobs
         .remote()
         .fetch(conn, hash_and_format)
         .await
         .map_err(|e| e.to_string())?;
 
-    let bytes = store
+    let bytes = state
+        .store
         .get_bytes(ticket.hash())
         .await
         .map_err(|e| e.to_string())?;
@@ -133,5 +132,63 @@ pub async fn fetch_blob_bytes(
         ticket.hash(),
         bytes.len()
     );
+    Ok(bytes)
+}
+
+#[tauri::command]
+pub async fn fetch_blob_bytes(
+    state: State<'_, Arc<AppState>>,
+    ticket: String,
+) -> Result<Vec<u8>, String> {
+    let bytes = fetch_ticket_bytes(&state, &ticket).await?;
     Ok(bytes.to_vec())
 }
+
+/// Encrypt a DM attachment under a fresh per-file key and add the ciphertext to the store.
+/// The key is returned to be sent inside the ratchet-encrypted `DirectMessage`, so only
+/// the conversation peer can read the blob even though its ticket is fetchable by anyone.
+#[tauri::command]
+pub async fn add_dm_blob_bytes(
+    state: State<'_, Arc<AppState>>,
+    data: Vec<u8>,
+) -> Result<serde_json::Value, String> {
+    if data.len() > MAX_BLOB_SIZE {
+        return Err(format!(
+            "blob too large: {} bytes (max {} bytes)",
+            data.len(),
+            MAX_BLOB_SIZE
+        ));
+    }
+
+    let size = data.len();
+    let (key, ciphertext) = encrypt_attachment(&data);
+    let tag = state
+        .store
+        .add_slice(&ciphertext)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let addr = state.endpoint.addr();
+    let ticket = BlobTicket::new(addr, tag.hash, tag.format);
+    log::info!("[blob] added encrypted DM blob {} ({size} bytes)", tag.hash);
+
+    Ok(serde_json::json!({
+        "hash": tag.hash.to_string(),
+        "ticket": ticket.to_string(),
+        "key": hex::encode(key),
+    }))
+}
+
+/// Fetch an encrypted DM attachment and decrypt it with its per-file key.
+#[tauri::command]
+pub async fn fetch_dm_blob_bytes(
+    state: State<'_, Arc<AppState>>,
+    ticket: String,
+    key: String,
+) -> Result<Vec<u8>, String> {
+    let key: [u8; 32] = hex::decode(&key)?
+        .try_into()
+        .map_err(|_| "invalid attachment key length".to_string())?;
+    let ciphertext = fetch_ticket_bytes(&state, &ticket).await?;
+    decrypt_attachment(&key, &ciphertext).map_err(|e| e.to_string())
+}
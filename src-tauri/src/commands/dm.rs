@@ -1,8 +1,10 @@
+use crate::crypto::safety_number;
 use crate::state::AppState;
 use crate::storage::Storage;
+use iroh::EndpointId;
 use iroh_social_types::{
-    ConversationMeta, DirectMessage, DmPayload, MediaAttachment, StoredMessage, now_millis,
-    short_id,
+    ConversationMeta, DirectMessage, DmPayload, DmPolicy, MediaAttachment, SafetyNumber,
+    StoredMessage, now_millis, short_id,
 };
 use std::sync::Arc;
 use tauri::State;
@@ -25,6 +27,11 @@ pub async fn send_dm(
         reply_to
     );
 
+    // DM media must go through add_dm_blob_bytes so only the peer can decrypt it
+    if media.iter().flatten().any(|m| m.key.is_none()) {
+        return Err("DM attachments must be encrypted".to_string());
+    }
+
     let my_id = state.endpoint.id().to_string();
     let msg_id = uuid::Uuid::new_v4().to_string();
     let timestamp = now_millis();
@@ -55,11 +62,12 @@ pub async fn send_dm(
         read: false,
         delivered: false,
         reply_to,
+        failed: false,
     };
 
     state
         .storage
-        .upsert_conversation(&to, &my_id, timestamp, &preview)
+        .upsert_conversation(&to, &my_id, timestamp, &preview, false)
         .map_err(|e| {
             log::error!("[dm-cmd] upsert_conversation error: {e}");
             e.to_string()
@@ -91,12 +99,108 @@ pub async fn get_conversations(
 ) -> Result<Vec<ConversationMeta>, String> {
     let convos = state
         .storage
-        .get_conversations()
+        .get_conversations(false)
         .map_err(|e| e.to_string())?;
     log::info!("[dm-cmd] get_conversations: {} conversations", convos.len());
     Ok(convos)
 }
 
+#[tauri::command]
+pub async fn get_message_requests(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<ConversationMeta>, String> {
+    state
+        .storage
+        .get_conversations(true)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn accept_message_request(
+    state: State<'_, Arc<AppState>>,
+    peer_pubkey: String,
+) -> Result<(), String> {
+    let my_id = state.endpoint.id().to_string();
+    let accepted = state
+        .storage
+        .accept_message_request(&peer_pubkey, &my_id)
+        .map_err(|e| e.to_string())?;
+    if !accepted {
+        return Err(format!(
+            "no message request from {}",
+            short_id(&peer_pubkey)
+        ));
+    }
+    log::info!("[dm-cmd] accepted request from {}", short_id(&peer_pubkey));
+    Ok(())
+}
+
+/// Delete a message request without notifying the sender.
+/// They may send another request later.
+#[tauri::command]
+pub async fn decline_message_request(
+    state: State<'_, Arc<AppState>>,
+    peer_pubkey: String,
+) -> Result<(), String> {
+    let my_id = state.endpoint.id().to_string();
+    if !state
+        .storage
+        .is_message_request(&peer_pubkey, &my_id)
+        .map_err(|e| e.to_string())?
+    {
+        return Err(format!(
+            "no message request from {}",
+            short_id(&peer_pubkey)
+        ));
+    }
+    state
+        .storage
+        .delete_conversation(&peer_pubkey, &my_id)
+        .map_err(|e| e.to_string())?;
+    log::info!("[dm-cmd] declined request from {}", short_id(&peer_pubkey));
+    Ok(())
+}
+
+/// Decline a message request and block the sender.
+#[tauri::command]
+pub async fn block_message_request(
+    state: State<'_, Arc<AppState>>,
+    peer_pubkey: String,
+) -> Result<(), String> {
+    let my_id = state.endpoint.id().to_string();
+    state
+        .storage
+        .delete_conversation(&peer_pubkey, &my_id)
+        .map_err(|e| e.to_string())?;
+    state
+        .storage
+        .block_user(&peer_pubkey)
+        .map_err(|e| e.to_string())?;
+    state.dm.disconnect_peer(&peer_pubkey);
+    log::info!(
+        "[dm-cmd] declined request from {} and blocked them",
+        short_id(&peer_pubkey)
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_dm_policy(state: State<'_, Arc<AppState>>) -> Result<DmPolicy, String> {
+    state.storage.get_dm_policy().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_dm_policy(
+    state: State<'_, Arc<AppState>>,
+    policy: DmPolicy,
+) -> Result<(), String> {
+    log::info!("[dm-cmd] dm policy set to {}", policy.as_str());
+    state
+        .storage
+        .set_dm_policy(policy)
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn get_dm_messages(
     state: State<'_, Arc<AppState>>,
@@ -173,6 +277,22 @@ pub async fn flush_dm_outbox(state: State<'_, Arc<AppState>>) -> Result<serde_js
     }))
 }
 
+#[tauri::command]
+pub async fn retry_dm(state: State<'_, Arc<AppState>>, message_id: String) -> Result<(), String> {
+    let peer = state
+        .storage
+        .requeue_outbox_message(&message_id, now_millis())
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("message {message_id} is not queued"))?;
+    log::info!(
+        "[dm-cmd] retrying message {} to {}",
+        short_id(&message_id),
+        short_id(&peer)
+    );
+    state.dm.request_outbox_flush(&peer);
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn get_unread_dm_count(state: State<'_, Arc<AppState>>) -> Result<u32, String> {
     state
@@ -197,6 +317,16 @@ pub async fn send_dm_signal(
         other => return Err(format!("unknown signal type: {other}")),
     };
 
+    // Don't reveal read or typing state until the request is accepted
+    let my_id = state.endpoint.id().to_string();
+    if state
+        .storage
+        .is_message_request(&to, &my_id)
+        .map_err(|e| e.to_string())?
+    {
+        return Ok(());
+    }
+
     let dm_handler = state.dm.clone();
     let endpoint = state.endpoint.clone();
 
@@ -211,3 +341,81 @@ pub async fn send_dm_signal(
 
     Ok(())
 }
+
+fn compute_safety_number(state: &AppState, peer_pubkey: &str) -> Result<SafetyNumber, String> {
+    let peer_id: EndpointId = peer_pubkey
+        .parse()
+        .map_err(|e| format!("invalid peer pubkey: {e}"))?;
+    let my_id = state.endpoint.id();
+    let (digits, scannable) =
+        safety_number(my_id.as_bytes(), peer_id.as_bytes()).ok_or("invalid identity key")?;
+    let verified = state
+        .storage
+        .is_conversation_verified(peer_pubkey, &my_id.to_string())
+        .map_err(|e| e.to_string())?;
+    Ok(SafetyNumber {
+        peer_pubkey: peer_pubkey.to_string(),
+        digits,
+        scannable,
+        verified,
+    })
+}
+
+#[tauri::command]
+pub async fn get_safety_number(
+    state: State<'_, Arc<AppState>>,
+    peer_pubkey: String,
+) -> Result<SafetyNumber, String> {
+    compute_safety_number(&state, &peer_pubkey)
+}
+
+/// Compare a safety number scanned from the peer's device and mark the
+/// conversation verified if it matches. Returns whether it matched.
+#[tauri::command]
+pub async fn verify_safety_number(
+    state: State<'_, Arc<AppState>>,
+    peer_pubkey: String,
+    scanned: String,
+) -> Result<bool, String> {
+    let expected = compute_safety_number(&state, &peer_pubkey)?;
+    if scanned.trim() != expected.scannable {
+        log::warn!(
+            "[dm-cmd] scanned safety number for {} does not match",
+            short_id(&peer_pubkey)
+        );
+        return Ok(false);
+    }
+    let my_id = state.endpoint.id().to_string();
+    let found = state
+        .storage
+        .set_conversation_verified(&peer_pubkey, &my_id, true, now_millis())
+        .map_err(|e| e.to_string())?;
+    if !found {
+        return Err("no conversation with this peer".to_string());
+    }
+    log::info!("[dm-cmd] verified {}", short_id(&peer_pubkey));
+    Ok(true)
+}
+
+/// Set the verified state after the user compared the digits by hand.
+#[tauri::command]
+pub async fn set_dm_verified(
+    state: State<'_, Arc<AppState>>,
+    peer_pubkey: String,
+    verified: bool,
+) -> Result<(), String> {
+    let my_id = state.endpoint.id().to_string();
+    let found = state
+        .storage
+        .set_conversation_verified(&peer_pubkey, &my_id, verified, now_millis())
+        .map_err(|e| e.to_string())?;
+    if !found {
+        return Err("no conversation with this peer".to_string());
+    }
+    log::info!(
+        "[dm-cmd] {} {}",
+        if verified { "verified" } else { "unverified" },
+        short_id(&peer_pubkey)
+    );
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use crate::commands::posts::post_from_body;
+use crate::state::{AppState, generate_id};
+use crate::storage::{Draft, PostBody, ScheduledPost};
+use iroh_social_types::{now_millis, validate_post};
+use std::sync::Arc;
+use tauri::State;
+
+/// Check a body against the post rules before it is stored for later, so a
+/// scheduled post does not fail at publish time for something we could catch now.
+fn validate_body(state: &AppState, body: &PostBody, publish_at: u64) -> Result<(), String> {
+    let post = post_from_body(state, String::new(), body.clone(), now_millis());
+    validate_post(&post)?;
+    if let Some(poll) = &body.poll
+        && poll.closes_at <= publish_at
+    {
+        return Err("poll closes before the post is published".to_string());
+    }
+    Ok(())
+}
+
+/// Save a new draft, or overwrite the draft `id`.
+#[tauri::command]
+pub async fn save_draft(
+    state: State<'_, Arc<AppState>>,
+    id: Option<String>,
+    body: PostBody,
+) -> Result<Draft, String> {
+    let id = id.unwrap_or_else(generate_id);
+    state
+        .storage
+        .save_draft(&id, &body, now_millis())
+        .map_err(|e| e.to_string())?;
+    log::info!("[drafts] saved draft {id}");
+    state
+        .storage
+        .get_draft(&id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("draft {id} not found"))
+}
+
+#[tauri::command]
+pub async fn get_draft(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+) -> Result<Option<Draft>, String> {
+    state.storage.get_draft(&id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_drafts(state: State<'_, Arc<AppState>>) -> Result<Vec<Draft>, String> {
+    state.storage.get_drafts().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_draft(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    state.storage.delete_draft(&id).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Queue a post for publishing at `publish_at` (ms). If it came from a draft,
+/// the draft is removed.
+#[tauri::command]
+pub async fn schedule_post(
+    state: State<'_, Arc<AppState>>,
+    body: PostBody,
+    publish_at: u64,
+    draft_id: Option<String>,
+) -> Result<ScheduledPost, String> {
+    validate_body(&state, &body, publish_at)?;
+    let id = generate_id();
+    let now = now_millis();
+    state
+        .storage
+        .insert_scheduled_post(&id, &body, publish_at, now)
+        .map_err(|e| e.to_string())?;
+    if let Some(draft_id) = draft_id {
+        state
+            .storage
+            .delete_draft(&draft_id)
+            .map_err(|e| e.to_string())?;
+    }
+    log::info!("[scheduler] scheduled post {id} for {publish_at}");
+    Ok(ScheduledPost {
+        id,
+        body,
+        publish_at,
+        created_at: now,
+        last_error: None,
+    })
+}
+
+#[tauri::command]
+pub async fn get_scheduled_posts(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<ScheduledPost>, String> {
+    state
+        .storage
+        .get_scheduled_posts()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn cancel_scheduled_post(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+) -> Result<(), String> {
+    state
+        .storage
+        .delete_scheduled_post(&id)
+        .map_err(|e| e.to_string())?;
+    log::info!("[scheduler] cancelled scheduled post {id}");
+    Ok(())
+}
+
+/// Move a scheduled post to a new time. Also retries one that failed.
+#[tauri::command]
+pub async fn reschedule_post(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    publish_at: u64,
+) -> Result<(), String> {
+    let found = state
+        .storage
+        .reschedule_post(&id, publish_at)
+        .map_err(|e| e.to_string())?;
+    if !found {
+        return Err(format!("scheduled post {id} not found"));
+    }
+    log::info!("[scheduler] rescheduled post {id} for {publish_at}");
+    Ok(())
+}
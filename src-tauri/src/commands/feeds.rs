@@ -0,0 +1,252 @@
+use crate::constants::{DEFAULT_FEED_LIMIT, RANKED_FEED_CANDIDATES, RANKED_FEED_WINDOW};
+use crate::ranking::{RankedPost, rank};
+use crate::state::{AppState, generate_id};
+use crate::storage::{FeedQuery, PostView, ReplyFilter};
+use iroh::EndpointId;
+use iroh_social_types::{CustomFeed, MAX_LIST_NAME_LEN, UserList, normalize_hashtag, now_millis};
+use std::sync::Arc;
+use tauri::State;
+
+/// Trimmed, non-empty name for a list or custom feed.
+fn check_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("name is empty".to_string());
+    }
+    if name.len() > MAX_LIST_NAME_LEN {
+        return Err(format!("name exceeds {MAX_LIST_NAME_LEN} characters"));
+    }
+    Ok(name.to_string())
+}
+
+/// Deduplicated, well-formed member pubkeys.
+fn check_members(members: Vec<String>) -> Result<Vec<String>, String> {
+    let mut checked = Vec::with_capacity(members.len());
+    for member in members {
+        if member.parse::<EndpointId>().is_err() {
+            return Err(format!("invalid member pubkey: {member}"));
+        }
+        if !checked.contains(&member) {
+            checked.push(member);
+        }
+    }
+    Ok(checked)
+}
+
+/// Normalize the feed's name and hashtags, and make sure its lists exist.
+fn check_custom_feed(state: &AppState, feed: &mut CustomFeed) -> Result<(), String> {
+    feed.name = check_name(&feed.name)?;
+    let mut hashtags = Vec::with_capacity(feed.hashtags.len());
+    for tag in &feed.hashtags {
+        let tag = normalize_hashtag(tag)?;
+        if !hashtags.contains(&tag) {
+            hashtags.push(tag);
+        }
+    }
+    feed.hashtags = hashtags;
+    let mut list_ids: Vec<String> = Vec::with_capacity(feed.list_ids.len());
+    for id in feed.list_ids.drain(..) {
+        if !list_ids.contains(&id) {
+            list_ids.push(id);
+        }
+    }
+    feed.list_ids = list_ids;
+    for id in &feed.list_ids {
+        if state
+            .storage
+            .get_list(id)
+            .map_err(|e| e.to_string())?
+            .is_none()
+        {
+            return Err(format!("list {id} not found"));
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_lists(state: State<'_, Arc<AppState>>) -> Result<Vec<UserList>, String> {
+    state.storage.get_lists().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn create_list(
+    state: State<'_, Arc<AppState>>,
+    name: String,
+    members: Vec<String>,
+) -> Result<UserList, String> {
+    let list = UserList {
+        id: generate_id(),
+        name: check_name(&name)?,
+        members: check_members(members)?,
+        created_at: now_millis(),
+    };
+    state
+        .storage
+        .create_list(&list)
+        .map_err(|e| e.to_string())?;
+    log::info!(
+        "[feeds] created list {:?} with {} members",
+        list.name,
+        list.members.len()
+    );
+    Ok(list)
+}
+
+#[tauri::command]
+pub async fn update_list(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    name: String,
+    members: Vec<String>,
+) -> Result<(), String> {
+    let name = check_name(&name)?;
+    let members = check_members(members)?;
+    if !state
+        .storage
+        .update_list(&id, &name, &members, now_millis())
+        .map_err(|e| e.to_string())?
+    {
+        return Err(format!("list {id} not found"));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_list(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    state.storage.delete_list(&id).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_custom_feeds(state: State<'_, Arc<AppState>>) -> Result<Vec<CustomFeed>, String> {
+    state.storage.get_custom_feeds().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn create_custom_feed(
+    state: State<'_, Arc<AppState>>,
+    name: String,
+    list_ids: Vec<String>,
+    hashtags: Vec<String>,
+    media_only: bool,
+    exclude_replies: bool,
+) -> Result<CustomFeed, String> {
+    let mut feed = CustomFeed {
+        id: generate_id(),
+        name,
+        list_ids,
+        hashtags,
+        media_only,
+        exclude_replies,
+        created_at: now_millis(),
+    };
+    check_custom_feed(&state, &mut feed)?;
+    state
+        .storage
+        .create_custom_feed(&feed)
+        .map_err(|e| e.to_string())?;
+    log::info!("[feeds] created custom feed {:?}", feed.name);
+    Ok(feed)
+}
+
+#[tauri::command]
+pub async fn update_custom_feed(
+    state: State<'_, Arc<AppState>>,
+    mut feed: CustomFeed,
+) -> Result<CustomFeed, String> {
+    check_custom_feed(&state, &mut feed)?;
+    if !state
+        .storage
+        .update_custom_feed(&feed)
+        .map_err(|e| e.to_string())?
+    {
+        return Err(format!("custom feed {} not found", feed.id));
+    }
+    Ok(feed)
+}
+
+#[tauri::command]
+pub async fn delete_custom_feed(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    state
+        .storage
+        .delete_custom_feed(&id)
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// A page of a custom feed, paginated like the home feed. A feed whose lists
+/// were all deleted shows nothing rather than everyone.
+#[tauri::command]
+pub async fn get_custom_feed_posts(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    limit: Option<usize>,
+    before: Option<u64>,
+    before_id: Option<String>,
+) -> Result<Vec<PostView>, String> {
+    let storage = &state.storage;
+    let feed = storage
+        .get_custom_feed(&id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("custom feed {id} not found"))?;
+    let lists = storage.get_lists().map_err(|e| e.to_string())?;
+    let list_ids: Vec<String> = feed
+        .list_ids
+        .iter()
+        .filter(|id| lists.iter().any(|l| &l.id == *id))
+        .cloned()
+        .collect();
+    if list_ids.is_empty() && !feed.list_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let q = FeedQuery {
+        limit: limit.unwrap_or(DEFAULT_FEED_LIMIT),
+        before,
+        before_id,
+        list_ids,
+        hashtags: feed.hashtags,
+        media_only: feed.media_only,
+        replies: if feed.exclude_replies {
+            ReplyFilter::None
+        } else {
+            ReplyFilter::All
+        },
+        ..Default::default()
+    };
+    let posts = storage.get_feed(&q).map_err(|e| e.to_string())?;
+    log::info!("[feeds] loaded {} posts for {:?}", posts.len(), feed.name);
+    Ok(posts)
+}
+
+/// A page of the "for you" feed: recent posts scored on-device from likes,
+/// reposts and replies by people we follow, our affinity with the author,
+/// and age. Each post carries the reasons behind its score. The first page
+/// (offset 0) ranks afresh; later pages come from that same ranking.
+#[tauri::command]
+pub async fn get_ranked_feed(
+    state: State<'_, Arc<AppState>>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Vec<RankedPost>, String> {
+    let offset = offset.unwrap_or(0);
+    let mut snapshot = state.ranked_feed.lock().unwrap();
+    if offset == 0 || snapshot.is_empty() {
+        let now = now_millis();
+        let since = now.saturating_sub(RANKED_FEED_WINDOW.as_millis() as u64);
+        let my_id = state.endpoint.id().to_string();
+        let candidates = state
+            .storage
+            .get_rank_candidates(&my_id, since, RANKED_FEED_CANDIDATES)
+            .map_err(|e| e.to_string())?;
+        *snapshot = rank(candidates, now);
+        log::info!("[feeds] ranked {} posts", snapshot.len());
+    }
+    let ranked: Vec<RankedPost> = snapshot
+        .iter()
+        .skip(offset)
+        .take(limit.unwrap_or(DEFAULT_FEED_LIMIT))
+        .cloned()
+        .collect();
+    Ok(ranked)
+}
@@ -1,8 +1,9 @@
 use crate::state::{AppState, generate_id};
-use crate::storage::PostCounts;
+use crate::storage::{PollResults, PostCounts};
 use iroh::SecretKey;
 use iroh_social_types::{
-    Interaction, InteractionKind, Post, now_millis, sign_interaction, sign_post, validate_post,
+    Interaction, InteractionKind, Post, now_millis, sign_interaction, sign_post,
+    validate_interaction, validate_post, validate_reaction, vote_matches_poll,
 };
 use std::sync::Arc;
 use tauri::State;
@@ -44,7 +45,7 @@ pub async fn unlike_post(
     let my_id = state.endpoint.id().to_string();
     let id = state
         .storage
-        .delete_interaction_by_target(&my_id, "Like", &target_post_id)
+        .delete_interaction_by_target(&my_id, &InteractionKind::Like, &target_post_id)
         .map_err(|e| e.to_string())?;
     if let Some(id) = id {
         let feed = state.feed.lock().await;
@@ -55,24 +56,142 @@ pub async fn unlike_post(
     Ok(())
 }
 
+#[tauri::command]
+pub async fn react_to_post(
+    state: State<'_, Arc<AppState>>,
+    target_post_id: String,
+    target_author: String,
+    emoji: String,
+) -> Result<Interaction, String> {
+    validate_reaction(&emoji)?;
+    let my_id = state.endpoint.id().to_string();
+    let mut interaction = Interaction {
+        id: generate_id(),
+        author: my_id,
+        kind: InteractionKind::Reaction(emoji),
+        target_post_id,
+        target_author,
+        timestamp: now_millis(),
+        signature: String::new(),
+    };
+    let sk = SecretKey::from_bytes(&state.secret_key_bytes);
+    sign_interaction(&mut interaction, &sk);
+    state
+        .storage
+        .save_interaction(&interaction)
+        .map_err(|e| e.to_string())?;
+    let feed = state.feed.lock().await;
+    feed.broadcast_interaction(&interaction)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(interaction)
+}
+
+#[tauri::command]
+pub async fn unreact_to_post(
+    state: State<'_, Arc<AppState>>,
+    target_post_id: String,
+    emoji: String,
+) -> Result<(), String> {
+    let my_id = state.endpoint.id().to_string();
+    let id = state
+        .storage
+        .delete_interaction_by_target(&my_id, &InteractionKind::Reaction(emoji), &target_post_id)
+        .map_err(|e| e.to_string())?;
+    if let Some(id) = id {
+        let feed = state.feed.lock().await;
+        feed.broadcast_delete_interaction(&id, &my_id)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Vote on a post's poll. Votes are final: one per author per poll.
+#[tauri::command]
+pub async fn vote_on_poll(
+    state: State<'_, Arc<AppState>>,
+    target_post_id: String,
+    choices: Vec<u32>,
+) -> Result<Interaction, String> {
+    let post = state
+        .storage
+        .get_post_by_id(&target_post_id)
+        .map_err(|e| e.to_string())?
+        .ok_or("post not found")?;
+    let poll = post.poll.as_ref().ok_or("post has no poll")?;
+    if now_millis() >= poll.closes_at {
+        return Err("poll is closed".to_string());
+    }
+    if !vote_matches_poll(poll, &choices) {
+        return Err("invalid choices for this poll".to_string());
+    }
+    let my_id = state.endpoint.id().to_string();
+    let results = state
+        .storage
+        .get_poll_results(&my_id, &target_post_id)
+        .map_err(|e| e.to_string())?;
+    if results.is_some_and(|r| r.my_vote.is_some()) {
+        return Err("already voted".to_string());
+    }
+
+    let mut interaction = Interaction {
+        id: generate_id(),
+        author: my_id,
+        kind: InteractionKind::Vote(choices),
+        target_post_id,
+        target_author: post.post.author,
+        timestamp: now_millis(),
+        signature: String::new(),
+    };
+    validate_interaction(&interaction)?;
+    let sk = SecretKey::from_bytes(&state.secret_key_bytes);
+    sign_interaction(&mut interaction, &sk);
+    state
+        .storage
+        .save_interaction(&interaction)
+        .map_err(|e| e.to_string())?;
+    let feed = state.feed.lock().await;
+    feed.broadcast_interaction(&interaction)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(interaction)
+}
+
+#[tauri::command]
+pub async fn get_poll_results(
+    state: State<'_, Arc<AppState>>,
+    post_id: String,
+) -> Result<Option<PollResults>, String> {
+    let my_id = state.endpoint.id().to_string();
+    state
+        .storage
+        .get_poll_results(&my_id, &post_id)
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn repost(
     state: State<'_, Arc<AppState>>,
     target_post_id: String,
     target_author: String,
 ) -> Result<Post, String> {
+    if let Some(target) = state
+        .storage
+        .get_post_by_id(&target_post_id)
+        .map_err(|e| e.to_string())?
+        && target.audience.is_some()
+    {
+        return Err("cannot repost a post shared with an audience".to_string());
+    }
     let author = state.endpoint.id().to_string();
     let mut post = Post {
         id: generate_id(),
         author,
-        content: String::new(),
         timestamp: now_millis(),
-        media: vec![],
-        reply_to: None,
-        reply_to_author: None,
         quote_of: Some(target_post_id),
         quote_of_author: Some(target_author),
-        signature: String::new(),
+        ..Default::default()
     };
 
     validate_post(&post)?;
@@ -1,15 +1,21 @@
+mod audiences;
 mod blobs;
 mod dm;
+mod drafts;
+mod feeds;
 mod interactions;
 mod moderation;
 mod notifications;
-mod posts;
-mod profile;
+pub(crate) mod posts;
+pub(crate) mod profile;
 mod social;
 pub(crate) mod sync;
 
+pub use audiences::*;
 pub use blobs::*;
 pub use dm::*;
+pub use drafts::*;
+pub use feeds::*;
 pub use interactions::*;
 pub use moderation::*;
 pub use notifications::*;
@@ -1,7 +1,13 @@
-use crate::state::AppState;
+use crate::state::{AppState, generate_id};
+use crate::word_filter;
+use iroh_social_types::{
+    FilterScope, Mute, MuteScope, MutedWord, MutedWordKind, SensitiveMode, now_millis, short_id,
+};
 use std::sync::Arc;
 use tauri::State;
 
+use super::social::revoke_approved_follower;
+
 #[tauri::command]
 pub async fn toggle_bookmark(
     state: State<'_, Arc<AppState>>,
@@ -24,17 +30,44 @@ pub async fn is_bookmarked(
         .map_err(|e| e.to_string())
 }
 
+/// Mute `pubkey` for `scope` (everything by default), until `expires_at`
+/// if given. Replaces any earlier mute of them for that scope.
 #[tauri::command]
-pub async fn mute_user(state: State<'_, Arc<AppState>>, pubkey: String) -> Result<(), String> {
-    state.storage.mute_user(&pubkey).map_err(|e| e.to_string())
+pub async fn mute_user(
+    state: State<'_, Arc<AppState>>,
+    pubkey: String,
+    scope: Option<MuteScope>,
+    expires_at: Option<u64>,
+) -> Result<(), String> {
+    if expires_at.is_some_and(|t| t <= now_millis()) {
+        return Err("expiry must be in the future".to_string());
+    }
+    let scope = scope.unwrap_or_default();
+    state
+        .storage
+        .mute_user(&pubkey, scope, expires_at)
+        .map_err(|e| e.to_string())?;
+    log::info!(
+        "[moderation] muted {} ({})",
+        short_id(&pubkey),
+        scope.as_str()
+    );
+    Ok(())
 }
 
+/// Lift the mute of `pubkey` for `scope`, or every mute of them if none
+/// is given.
 #[tauri::command]
-pub async fn unmute_user(state: State<'_, Arc<AppState>>, pubkey: String) -> Result<(), String> {
-    state
-        .storage
-        .unmute_user(&pubkey)
-        .map_err(|e| e.to_string())
+pub async fn unmute_user(
+    state: State<'_, Arc<AppState>>,
+    pubkey: String,
+    scope: Option<MuteScope>,
+) -> Result<(), String> {
+    match scope {
+        Some(scope) => state.storage.unmute_user_for(&pubkey, scope),
+        None => state.storage.unmute_user(&pubkey),
+    }
+    .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -42,11 +75,27 @@ pub async fn is_muted(state: State<'_, Arc<AppState>>, pubkey: String) -> Result
     state.storage.is_muted(&pubkey).map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn get_user_mutes(
+    state: State<'_, Arc<AppState>>,
+    pubkey: String,
+) -> Result<Vec<Mute>, String> {
+    state
+        .storage
+        .get_user_mutes(&pubkey)
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn get_muted_pubkeys(state: State<'_, Arc<AppState>>) -> Result<Vec<String>, String> {
     state.storage.get_muted_pubkeys().map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn get_mutes(state: State<'_, Arc<AppState>>) -> Result<Vec<Mute>, String> {
+    state.storage.get_mutes().map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn block_user(state: State<'_, Arc<AppState>>, pubkey: String) -> Result<(), String> {
     let is_following = state
@@ -61,6 +110,7 @@ pub async fn block_user(state: State<'_, Arc<AppState>>, pubkey: String) -> Resu
         let mut feed = state.feed.lock().await;
         feed.unfollow_user(&pubkey);
     }
+    revoke_approved_follower(&state, &pubkey)?;
 
     state.storage.block_user(&pubkey).map_err(|e| e.to_string())
 }
@@ -85,3 +135,99 @@ pub async fn get_blocked_pubkeys(state: State<'_, Arc<AppState>>) -> Result<Vec<
         .get_blocked_pubkeys()
         .map_err(|e| e.to_string())
 }
+
+#[tauri::command]
+pub async fn get_sensitive_mode(state: State<'_, Arc<AppState>>) -> Result<SensitiveMode, String> {
+    state
+        .storage
+        .get_sensitive_mode()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_sensitive_mode(
+    state: State<'_, Arc<AppState>>,
+    mode: SensitiveMode,
+) -> Result<(), String> {
+    log::info!(
+        "[moderation] sensitive content mode set to {}",
+        mode.as_str()
+    );
+    state
+        .storage
+        .set_sensitive_mode(mode)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_sensitive_override(
+    state: State<'_, Arc<AppState>>,
+    pubkey: String,
+) -> Result<Option<SensitiveMode>, String> {
+    state
+        .storage
+        .get_sensitive_override(&pubkey)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_sensitive_override(
+    state: State<'_, Arc<AppState>>,
+    pubkey: String,
+    mode: Option<SensitiveMode>,
+) -> Result<(), String> {
+    state
+        .storage
+        .set_sensitive_override(&pubkey, mode)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_muted_words(state: State<'_, Arc<AppState>>) -> Result<Vec<MutedWord>, String> {
+    state.storage.get_muted_words().map_err(|e| e.to_string())
+}
+
+/// Mute a word, phrase or regex in the feed, notifications or both, until
+/// `expires_at` if given.
+#[tauri::command]
+pub async fn add_muted_word(
+    state: State<'_, Arc<AppState>>,
+    pattern: String,
+    kind: MutedWordKind,
+    scope: FilterScope,
+    expires_at: Option<u64>,
+) -> Result<MutedWord, String> {
+    word_filter::compile(&pattern, kind)?;
+    let now = now_millis();
+    if expires_at.is_some_and(|t| t <= now) {
+        return Err("expiry must be in the future".to_string());
+    }
+    let word = MutedWord {
+        id: generate_id(),
+        pattern: pattern.trim().to_string(),
+        kind,
+        scope,
+        expires_at,
+        created_at: now,
+    };
+    state
+        .storage
+        .add_muted_word(&word)
+        .map_err(|e| e.to_string())?;
+    log::info!(
+        "[moderation] muted {} {:?} in {}",
+        kind.as_str(),
+        word.pattern,
+        scope.as_str()
+    );
+    Ok(word)
+}
+
+#[tauri::command]
+pub async fn remove_muted_word(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    state
+        .storage
+        .remove_muted_word(&id)
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
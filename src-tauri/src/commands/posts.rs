@@ -1,13 +1,26 @@
-use crate::state::{AppState, generate_id};
-use crate::storage::FeedQuery;
+use crate::commands::profile::publish_profile;
+use crate::link_preview::generate_link_preview;
+use crate::state::{
+    AppState, Conversation, ConversationNode, MissingPost, PostHistory, generate_id,
+};
+use crate::storage::{FeedFilters, FeedQuery, PostBody, PostView, Storage};
+use futures::{StreamExt, future::join_all};
 use iroh::SecretKey;
-use iroh_social_types::{MediaAttachment, Post, now_millis, sign_post, validate_post};
+use iroh_social_types::{
+    Audience, MediaAttachment, Poll, Post, PostEdit, PostKind, now_millis, short_id, sign_post,
+    sign_post_edit, validate_post, validate_post_edit,
+};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tauri::State;
+use tauri::{AppHandle, Emitter, State};
 
-use crate::constants::{DEFAULT_FEED_LIMIT, DEFAULT_REPLY_LIMIT};
+use crate::constants::{
+    DEFAULT_CONVERSATION_DEPTH, DEFAULT_FEED_LIMIT, DEFAULT_REPLY_LIMIT, MAX_ANCESTOR_DEPTH,
+    MAX_ANCESTOR_FETCHES, MAX_CONVERSATION_DEPTH, MAX_CONVERSATION_NODES, MAX_THREAD_LEN,
+};
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn create_post(
     state: State<'_, Arc<AppState>>,
     content: String,
@@ -16,43 +29,274 @@ pub async fn create_post(
     reply_to_author: Option<String>,
     quote_of: Option<String>,
     quote_of_author: Option<String>,
-) -> Result<Post, String> {
-    let author = state.endpoint.id().to_string();
-    let media_count = media.as_ref().map_or(0, |m| m.len());
-    let mut post = Post {
-        id: generate_id(),
-        author,
+    poll: Option<Poll>,
+    content_warning: Option<String>,
+    sensitive_media: Option<bool>,
+    expires_at: Option<u64>,
+    audience: Option<String>,
+) -> Result<PostView, String> {
+    let audience = audience
+        .map(|id| {
+            state
+                .storage
+                .get_audience(&id)
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("audience {id} not found"))
+        })
+        .transpose()?;
+    let body = PostBody {
         content,
-        timestamp: now_millis(),
         media: media.unwrap_or_default(),
         reply_to,
         reply_to_author,
         quote_of,
         quote_of_author,
+        poll,
+        content_warning,
+        sensitive_media: sensitive_media.unwrap_or(false),
+        expires_at,
+    };
+    publish_post(&state, generate_id(), body, audience.as_ref()).await
+}
+
+/// Build an unsigned post from `body`, authored by us at `timestamp`.
+pub(crate) fn post_from_body(state: &AppState, id: String, body: PostBody, timestamp: u64) -> Post {
+    Post {
+        id,
+        author: state.endpoint.id().to_string(),
+        content: body.content,
+        timestamp,
+        media: body.media,
+        reply_to: body.reply_to,
+        reply_to_author: body.reply_to_author,
+        quote_of: body.quote_of,
+        quote_of_author: body.quote_of_author,
         signature: String::new(),
+        poll: body.poll,
+        content_warning: body
+            .content_warning
+            .map(|w| w.trim().to_string())
+            .filter(|w| !w.is_empty()),
+        sensitive_media: body.sensitive_media,
+        expires_at: body.expires_at,
+        link_preview: None,
+    }
+}
+
+/// Add a preview of the first link in a validated, unsigned post, unless
+/// link previews are turned off. Publishing goes ahead without one if the
+/// page can't be read in time. A thumbnail stored for a preview that is then
+/// dropped is deleted again.
+async fn attach_link_preview(state: &AppState, post: &mut Post) {
+    if !state.storage.get_link_previews_enabled().unwrap_or(false) {
+        return;
+    }
+    let preview = generate_link_preview(
+        state.link_fetcher.as_ref(),
+        &state.store,
+        &state.endpoint,
+        &post.content,
+    )
+    .await;
+    match preview {
+        Ok(preview) => post.link_preview = preview.map(Box::new),
+        Err(e) => log::warn!("[post] link preview failed: {e}"),
+    }
+    if let Err(e) = validate_post(post) {
+        log::warn!("[post] dropping invalid link preview: {e}");
+        if let Some(image) = post.link_preview.take().and_then(|l| l.image) {
+            delete_unreferenced_blob(state, &image.hash).await;
+        }
+    }
+}
+
+/// Delete a blob from the store unless something stored still refers to
+/// it. Drops the tags that kept blobs we added; the next blob GC run then
+/// removes the data.
+async fn delete_unreferenced_blob(state: &AppState, hash: &str) {
+    match state.storage.is_blob_referenced(hash) {
+        Ok(false) => {}
+        Ok(true) => return,
+        Err(e) => {
+            log::error!("[post] failed to check blob {hash}: {e}");
+            return;
+        }
+    }
+    let Ok(hash) = hash.parse::<iroh_blobs::Hash>() else {
+        return;
+    };
+    let tags = match state.store.tags().list().await {
+        Ok(tags) => tags,
+        Err(e) => {
+            log::error!("[post] failed to list blob tags: {e}");
+            return;
+        }
     };
+    let names: Vec<_> = tags
+        .filter_map(|tag| async move { tag.ok().filter(|t| t.hash == hash).map(|t| t.name) })
+        .collect()
+        .await;
+    for name in names {
+        if let Err(e) = state.store.tags().delete(name).await {
+            log::error!("[post] failed to delete tag of blob {hash}: {e}");
+        }
+    }
+}
+
+/// A quote needs commentary of its own; a bare share goes through `repost`.
+fn check_not_bare_quote(post: &Post) -> Result<(), String> {
+    if post.kind() == PostKind::Repost {
+        return Err("quote has no commentary; use repost to share a post as is".to_string());
+    }
+    Ok(())
+}
+
+/// Sign, store and broadcast a post timestamped now. Shared by `create_post`
+/// and the scheduled post publisher. A post for an `audience` skips gossip
+/// and goes to the members directly.
+pub(crate) async fn publish_post(
+    state: &AppState,
+    id: String,
+    body: PostBody,
+    audience: Option<&Audience>,
+) -> Result<PostView, String> {
+    let mut post = post_from_body(state, id, body, now_millis());
+    let media_count = post.media.len();
 
     validate_post(&post)?;
+    check_not_bare_quote(&post)?;
+    attach_link_preview(state, &mut post).await;
 
     let sk = SecretKey::from_bytes(&state.secret_key_bytes);
     sign_post(&mut post, &sk);
 
-    state
-        .storage
-        .insert_post(&post)
-        .map_err(|e| e.to_string())?;
+    match audience {
+        Some(audience) => state.storage.insert_audience_post(&post, &audience.name),
+        None => state.storage.insert_post(&post),
+    }
+    .map_err(|e| e.to_string())?;
     log::info!(
         "[post] created post {} ({} media attachments)",
         &post.id,
         media_count
     );
+    if let Some(audience) = audience {
+        state
+            .storage
+            .set_post_audience(&post.id, &audience.id)
+            .map_err(|e| e.to_string())?;
+        send_to_audience(state, audience, &post);
+        return Ok(PostView {
+            audience: Some(audience.name.clone()),
+            ..PostView::new(post)
+        });
+    }
     let feed = state.feed.lock().await;
     feed.broadcast_post(&post)
         .await
         .map_err(|e| e.to_string())?;
     log::info!("[post] broadcast post {}", &post.id);
 
-    Ok(post)
+    Ok(PostView::new(post))
+}
+
+/// Queue an audience post for each member. The outbox delivers it, and
+/// members who are offline get it once they are back.
+fn send_to_audience(state: &AppState, audience: &Audience, post: &Post) {
+    log::info!(
+        "[post] sending post {} to {} members of {:?}",
+        &post.id,
+        audience.members.len(),
+        audience.name
+    );
+    for member in &audience.members {
+        if let Err(e) = state.dm.queue_audience_post(member, &audience.name, post) {
+            log::error!(
+                "[post] failed to queue audience post {} for {}: {e}",
+                &post.id,
+                short_id(member)
+            );
+        }
+    }
+}
+
+/// Drop expired posts and any media blobs nothing else refers to. Our own
+/// expired posts get a delete broadcast so peers drop them too, even if they
+/// don't sweep. Returns how many posts were removed.
+pub(crate) async fn sweep_expired_posts(state: &AppState) -> Result<usize, String> {
+    let expired = state
+        .storage
+        .delete_expired_posts(now_millis())
+        .map_err(|e| e.to_string())?;
+    if expired.is_empty() {
+        return Ok(0);
+    }
+    let my_id = state.endpoint.id().to_string();
+
+    let blobs: Vec<&str> = expired
+        .iter()
+        .flat_map(|p| {
+            p.media
+                .iter()
+                .chain(p.link_preview.as_ref().and_then(|l| l.image.as_ref()))
+        })
+        .map(|m| m.hash.as_str())
+        .collect();
+    for hash in blobs {
+        delete_unreferenced_blob(state, hash).await;
+    }
+
+    let own: Vec<&str> = expired
+        .iter()
+        .filter(|p| p.author == my_id)
+        .map(|p| p.id.as_str())
+        .collect();
+    {
+        let feed = state.feed.lock().await;
+        for id in &own {
+            if let Err(e) = feed.broadcast_delete(id, &my_id).await {
+                log::error!("[expiry] failed to broadcast delete {id}: {e}");
+            }
+        }
+    }
+    if let Some(mut profile) = state
+        .storage
+        .get_profile(&my_id)
+        .map_err(|e| e.to_string())?
+        .filter(|p| p.pinned_posts.iter().any(|id| own.contains(&id.as_str())))
+    {
+        profile
+            .pinned_posts
+            .retain(|id| !own.contains(&id.as_str()));
+        publish_profile(state, profile).await?;
+    }
+
+    log::info!(
+        "[expiry] removed {} expired posts ({} own)",
+        expired.len(),
+        own.len()
+    );
+    Ok(expired.len())
+}
+
+#[tauri::command]
+pub async fn get_link_previews(state: State<'_, Arc<AppState>>) -> Result<bool, String> {
+    state
+        .storage
+        .get_link_previews_enabled()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_link_previews(
+    state: State<'_, Arc<AppState>>,
+    enabled: bool,
+) -> Result<(), String> {
+    log::info!("[post] link previews enabled={enabled}");
+    state
+        .storage
+        .set_link_previews_enabled(enabled)
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -75,52 +319,299 @@ pub async fn delete_post(state: State<'_, Arc<AppState>>, id: String) -> Result<
 
     let removed = state.storage.delete_post(&id).map_err(|e| e.to_string())?;
     log::info!("[post] delete post {id}: removed={removed}");
+    {
+        let feed = state.feed.lock().await;
+        feed.broadcast_delete(&id, &my_id)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    log::info!("[post] broadcast delete {id}");
+
+    if let Some(mut profile) = state
+        .storage
+        .get_profile(&my_id)
+        .map_err(|e| e.to_string())?
+        .filter(|p| p.pinned_posts.contains(&id))
+    {
+        profile.pinned_posts.retain(|p| *p != id);
+        publish_profile(&state, profile).await?;
+    }
+
+    Ok(())
+}
+
+/// Publish a chain of posts, each replying to the one before. Every part is
+/// validated before any is signed or stored, and all are stored together,
+/// so a thread is never left half-published locally.
+#[tauri::command]
+pub async fn create_thread(
+    state: State<'_, Arc<AppState>>,
+    parts: Vec<PostBody>,
+) -> Result<Vec<PostView>, String> {
+    if parts.len() < 2 {
+        return Err("a thread needs at least 2 posts".to_string());
+    }
+    if parts.len() > MAX_THREAD_LEN {
+        return Err(format!(
+            "thread too long: {} posts (max {MAX_THREAD_LEN})",
+            parts.len()
+        ));
+    }
+    let my_id = state.endpoint.id().to_string();
+    let ids: Vec<String> = parts.iter().map(|_| generate_id()).collect();
+    let start = now_millis();
+
+    let mut posts = Vec::with_capacity(parts.len());
+    for (i, body) in parts.into_iter().enumerate() {
+        // Distinct timestamps keep the parts in order wherever they are sorted
+        let mut post = post_from_body(&state, ids[i].clone(), body, start + i as u64);
+        if i > 0 {
+            post.reply_to = Some(ids[i - 1].clone());
+            post.reply_to_author = Some(my_id.clone());
+        }
+        validate_post(&post)
+            .and_then(|_| check_not_bare_quote(&post))
+            .map_err(|e| format!("post {} of the thread: {e}", i + 1))?;
+        posts.push(post);
+    }
+
+    // Each part's page is fetched at once rather than one after another
+    join_all(
+        posts
+            .iter_mut()
+            .map(|post| attach_link_preview(&state, post)),
+    )
+    .await;
+    let sk = SecretKey::from_bytes(&state.secret_key_bytes);
+    for post in &mut posts {
+        sign_post(post, &sk);
+    }
+    state
+        .storage
+        .insert_posts(&posts)
+        .map_err(|e| e.to_string())?;
+    log::info!("[post] created thread {} ({} posts)", &ids[0], posts.len());
+
+    let feed = state.feed.lock().await;
+    for post in &posts {
+        feed.broadcast_post(post).await.map_err(|e| e.to_string())?;
+    }
+    log::info!("[post] broadcast thread {}", &ids[0]);
+
+    Ok(posts.into_iter().map(PostView::new).collect())
+}
+
+#[tauri::command]
+pub async fn get_thread(
+    state: State<'_, Arc<AppState>>,
+    root_id: String,
+) -> Result<Vec<PostView>, String> {
+    state
+        .storage
+        .get_thread(&root_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn edit_post(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    content: String,
+    media: Option<Vec<MediaAttachment>>,
+) -> Result<PostView, String> {
+    let my_id = state.endpoint.id().to_string();
+
+    let post = state
+        .storage
+        .get_post_by_id(&id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("post {id} not found"))?;
+    if post.author != my_id {
+        return Err("cannot edit posts authored by other users".to_string());
+    }
+    if post.quote_of.is_some() && post.content.is_empty() {
+        return Err("cannot edit a repost".to_string());
+    }
+    if post.audience.is_some() {
+        return Err("cannot edit a post shared with an audience".to_string());
+    }
+
+    let mut edit = PostEdit {
+        id: generate_id(),
+        post_id: id.clone(),
+        author: my_id,
+        content,
+        media: media.unwrap_or_default(),
+        timestamp: now_millis(),
+        signature: String::new(),
+    };
+    validate_post_edit(&edit)?;
+
+    let sk = SecretKey::from_bytes(&state.secret_key_bytes);
+    sign_post_edit(&mut edit, &sk);
+
+    state
+        .storage
+        .insert_post_edit(&edit)
+        .map_err(|e| e.to_string())?;
+    log::info!("[post] edited post {id}");
     let feed = state.feed.lock().await;
-    feed.broadcast_delete(&id, &my_id)
+    feed.broadcast_edit(&edit)
         .await
         .map_err(|e| e.to_string())?;
-    log::info!("[post] broadcast delete {id}");
 
-    Ok(())
+    state
+        .storage
+        .get_post_latest(&id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("post {id} not found"))
+}
+
+#[tauri::command]
+pub async fn get_post_history(
+    state: State<'_, Arc<AppState>>,
+    post_id: String,
+) -> Result<PostHistory, String> {
+    let post = state
+        .storage
+        .get_post_by_id(&post_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("post {post_id} not found"))?;
+    let edits = state
+        .storage
+        .get_post_edits(&post_id, &post.author)
+        .map_err(|e| e.to_string())?;
+    Ok(PostHistory {
+        original: post.post,
+        edits,
+    })
 }
 
+/// The home feed, newest first. Pass the last post's `timestamp` and `id` as
+/// `before` and `before_id` for the next page.
 #[tauri::command]
 pub async fn get_feed(
     state: State<'_, Arc<AppState>>,
     limit: Option<usize>,
     before: Option<u64>,
-) -> Result<Vec<Post>, String> {
+    before_id: Option<String>,
+    filters: Option<FeedFilters>,
+) -> Result<Vec<PostView>, String> {
+    let filters = filters.unwrap_or_default();
     let q = FeedQuery {
         limit: limit.unwrap_or(DEFAULT_FEED_LIMIT),
         before,
+        before_id,
+        media_only: filters.media_only,
+        replies: filters.replies,
+        exclude_reposts: filters.exclude_reposts,
+        exclude_author: filters.hide_own.then(|| state.endpoint.id().to_string()),
+        ..Default::default()
     };
     let posts = state.storage.get_feed(&q).map_err(|e| e.to_string())?;
     log::info!("[feed] loaded {} posts", posts.len());
     Ok(posts)
 }
 
+/// An author's posts, newest first. Unfiltered, the first page starts with
+/// their pinned posts, which are left out of the pages themselves. Pinned
+/// posts we don't hold are fetched from the author in the background.
 #[tauri::command]
 pub async fn get_user_posts(
+    app_handle: AppHandle,
     state: State<'_, Arc<AppState>>,
     pubkey: String,
     limit: Option<usize>,
     before: Option<u64>,
     media_filter: Option<String>,
-) -> Result<Vec<Post>, String> {
-    state
-        .storage
+) -> Result<Vec<PostView>, String> {
+    let storage = &state.storage;
+    let mut posts = storage
         .get_posts_by_author(
             &pubkey,
             limit.unwrap_or(DEFAULT_FEED_LIMIT),
             before,
             media_filter.as_deref(),
         )
+        .map_err(|e| e.to_string())?;
+    if media_filter.is_some() {
+        return Ok(posts);
+    }
+    let pinned_ids = storage
+        .get_profile(&pubkey)
+        .map_err(|e| e.to_string())?
+        .map(|p| p.pinned_posts)
+        .unwrap_or_default();
+    if pinned_ids.is_empty() {
+        return Ok(posts);
+    }
+    posts.retain(|p| !pinned_ids.contains(&p.id));
+    if before.is_some() {
+        return Ok(posts);
+    }
+
+    let mut pinned = Vec::with_capacity(pinned_ids.len() + posts.len());
+    let mut missing = Vec::new();
+    for id in &pinned_ids {
+        match storage.get_post_latest(id).map_err(|e| e.to_string())? {
+            Some(mut post) if post.author == pubkey => {
+                post.pinned = true;
+                pinned.push(post);
+            }
+            Some(_) => {}
+            None => missing.push(id.clone()),
+        }
+    }
+    if !missing.is_empty() && pubkey != state.endpoint.id().to_string() {
+        let state = state.inner().clone();
+        tokio::spawn(async move {
+            let stored = crate::post_fetch::fetch_missing_posts(
+                &state.endpoint,
+                &state.storage,
+                &missing,
+                Some(&pubkey),
+            )
+            .await;
+            if stored > 0 {
+                let _ = app_handle.emit("feed-updated", ());
+            }
+        });
+    }
+    pinned.extend(posts);
+    Ok(pinned)
+}
+
+#[tauri::command]
+pub async fn get_post(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+) -> Result<Option<PostView>, String> {
+    state
+        .storage
+        .get_post_latest(&id)
         .map_err(|e| e.to_string())
 }
 
+/// Like `get_post`, but a post we don't hold is requested from its author
+/// (when known) or a few followed peers, verified and cached.
 #[tauri::command]
-pub async fn get_post(state: State<'_, Arc<AppState>>, id: String) -> Result<Option<Post>, String> {
-    state.storage.get_post_by_id(&id).map_err(|e| e.to_string())
+pub async fn fetch_post(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    author: Option<String>,
+) -> Result<Option<PostView>, String> {
+    let storage = &state.storage;
+    if let Some(post) = storage.get_post_latest(&id).map_err(|e| e.to_string())? {
+        return Ok(Some(post));
+    }
+    crate::post_fetch::fetch_missing_posts(
+        &state.endpoint,
+        storage,
+        std::slice::from_ref(&id),
+        author.as_deref(),
+    )
+    .await;
+    storage.get_post_latest(&id).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -129,7 +620,7 @@ pub async fn get_replies(
     target_post_id: String,
     limit: Option<u32>,
     before: Option<u64>,
-) -> Result<Vec<Post>, String> {
+) -> Result<Vec<PostView>, String> {
     state
         .storage
         .get_replies(
@@ -139,3 +630,127 @@ pub async fn get_replies(
         )
         .map_err(|e| e.to_string())
 }
+
+fn conversation_node(
+    storage: &Storage,
+    my_id: &str,
+    post: PostView,
+) -> Result<ConversationNode, String> {
+    let counts = storage
+        .get_post_counts(my_id, &post.id)
+        .map_err(|e| e.to_string())?;
+    Ok(ConversationNode {
+        post,
+        counts,
+        children: Vec::new(),
+    })
+}
+
+/// Attach each post in `by_parent` under its parent, depth first.
+fn build_children(
+    node: &mut ConversationNode,
+    by_parent: &mut HashMap<String, Vec<ConversationNode>>,
+) {
+    if let Some(mut children) = by_parent.remove(&node.post.id) {
+        for child in &mut children {
+            build_children(child, by_parent);
+        }
+        node.children = children;
+    }
+}
+
+/// The first ancestor the chain above `ancestors` points at but we don't hold.
+fn missing_ancestor(storage: &Storage, ancestors: &[PostView]) -> Option<MissingPost> {
+    let top = ancestors.first()?;
+    let parent = top.reply_to.as_ref()?;
+    match storage.get_post_by_id(parent) {
+        Ok(None) => Some(MissingPost {
+            id: parent.clone(),
+            author: top.reply_to_author.clone(),
+        }),
+        _ => None,
+    }
+}
+
+/// The ancestor chain and the reply tree (up to `max_depth` levels, at most
+/// `MAX_CONVERSATION_DEPTH`) around a post. With `fetch_missing`, ancestors we don't hold are fetched by id from
+/// their author or followed peers until the chain reaches its root or nothing
+/// more can be found.
+#[tauri::command]
+pub async fn get_conversation(
+    state: State<'_, Arc<AppState>>,
+    post_id: String,
+    max_depth: Option<u32>,
+    fetch_missing: Option<bool>,
+) -> Result<Conversation, String> {
+    let my_id = state.endpoint.id().to_string();
+    let storage = &state.storage;
+
+    let mut ancestors = storage
+        .get_ancestors(&post_id, MAX_ANCESTOR_DEPTH)
+        .map_err(|e| e.to_string())?;
+    if ancestors.is_empty() {
+        return Err(format!("post {post_id} not found"));
+    }
+
+    if fetch_missing.unwrap_or(false) {
+        for _ in 0..MAX_ANCESTOR_FETCHES {
+            let Some(missing) = missing_ancestor(storage, &ancestors) else {
+                break;
+            };
+            log::info!(
+                "[conversation] fetching missing ancestor {}",
+                short_id(&missing.id)
+            );
+            let stored = crate::post_fetch::fetch_missing_posts(
+                &state.endpoint,
+                storage,
+                std::slice::from_ref(&missing.id),
+                missing.author.as_deref(),
+            )
+            .await;
+            if stored == 0 {
+                break;
+            }
+            ancestors = storage
+                .get_ancestors(&post_id, MAX_ANCESTOR_DEPTH)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    let missing = missing_ancestor(storage, &ancestors);
+    let focus_post = ancestors.pop().expect("chain includes the post itself");
+    let mut focus = conversation_node(storage, &my_id, focus_post)?;
+
+    let descendants = storage
+        .get_descendants(
+            &post_id,
+            max_depth
+                .unwrap_or(DEFAULT_CONVERSATION_DEPTH)
+                .min(MAX_CONVERSATION_DEPTH),
+            MAX_CONVERSATION_NODES,
+        )
+        .map_err(|e| e.to_string())?;
+    let mut by_parent: HashMap<String, Vec<ConversationNode>> = HashMap::new();
+    for post in descendants {
+        let Some(parent) = post.reply_to.clone() else {
+            continue;
+        };
+        by_parent
+            .entry(parent)
+            .or_default()
+            .push(conversation_node(storage, &my_id, post)?);
+    }
+    build_children(&mut focus, &mut by_parent);
+
+    let ancestors = ancestors
+        .into_iter()
+        .map(|post| conversation_node(storage, &my_id, post))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Conversation {
+        ancestors,
+        focus,
+        missing_ancestor: missing,
+    })
+}
@@ -1,5 +1,7 @@
+use crate::follow::ensure_feed_key;
 use crate::state::{AppState, NodeStatus};
-use iroh_social_types::{Profile, validate_profile};
+use iroh::SecretKey;
+use iroh_social_types::{MAX_PINNED_POSTS, Profile, now_millis, sign_profile, validate_profile};
 use std::sync::Arc;
 use tauri::State;
 
@@ -27,19 +29,50 @@ pub async fn save_my_profile(
     is_private: bool,
 ) -> Result<(), String> {
     let node_id = state.endpoint.id().to_string();
+    let pinned_posts = state
+        .storage
+        .get_profile(&node_id)
+        .map_err(|e| e.to_string())?
+        .map(|p| p.pinned_posts)
+        .unwrap_or_default();
     let profile = Profile {
         display_name: display_name.clone(),
         bio: bio.clone(),
         avatar_hash,
         avatar_ticket,
         is_private,
+        pinned_posts,
+        updated_at: None,
+        signature: None,
     };
+    publish_profile(&state, profile).await?;
+    log::info!("[profile] saved profile: {display_name} (private={is_private})");
+
+    // Going private: make sure approved followers hold the feed key
+    if is_private {
+        ensure_feed_key(&state.storage).map_err(|e| e.to_string())?;
+        for follower in state
+            .storage
+            .get_approved_followers()
+            .map_err(|e| e.to_string())?
+        {
+            state.dm.request_outbox_flush(&follower.pubkey);
+        }
+    }
+    Ok(())
+}
+
+/// Sign, store and broadcast our profile.
+pub(crate) async fn publish_profile(state: &AppState, mut profile: Profile) -> Result<(), String> {
     validate_profile(&profile)?;
+    profile.updated_at = Some(now_millis());
+    let sk = SecretKey::from_bytes(&state.secret_key_bytes);
+    sign_profile(&mut profile, &sk);
+    let node_id = state.endpoint.id().to_string();
     state
         .storage
         .save_profile(&node_id, &profile)
         .map_err(|e| e.to_string())?;
-    log::info!("[profile] saved profile: {display_name} (private={is_private})");
     let feed = state.feed.lock().await;
     feed.broadcast_profile(&profile)
         .await
@@ -48,6 +81,54 @@ pub async fn save_my_profile(
     Ok(())
 }
 
+/// Our profile, or an error when it has not been set up yet.
+fn my_profile(state: &AppState) -> Result<Profile, String> {
+    let node_id = state.endpoint.id().to_string();
+    state
+        .storage
+        .get_profile(&node_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "profile not set up".to_string())
+}
+
+/// Pin one of our posts to the top of our profile.
+#[tauri::command]
+pub async fn pin_post(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    let node_id = state.endpoint.id().to_string();
+    match state
+        .storage
+        .get_post_by_id(&id)
+        .map_err(|e| e.to_string())?
+    {
+        Some(post) if post.author == node_id => {}
+        Some(_) => return Err("cannot pin posts authored by other users".to_string()),
+        None => return Err(format!("post {id} not found")),
+    }
+    let mut profile = my_profile(&state)?;
+    if profile.pinned_posts.contains(&id) {
+        return Ok(());
+    }
+    if profile.pinned_posts.len() >= MAX_PINNED_POSTS {
+        return Err(format!("at most {MAX_PINNED_POSTS} posts can be pinned"));
+    }
+    profile.pinned_posts.insert(0, id.clone());
+    publish_profile(&state, profile).await?;
+    log::info!("[profile] pinned post {id}");
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn unpin_post(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    let mut profile = my_profile(&state)?;
+    if !profile.pinned_posts.contains(&id) {
+        return Ok(());
+    }
+    profile.pinned_posts.retain(|p| *p != id);
+    publish_profile(&state, profile).await?;
+    log::info!("[profile] unpinned post {id}");
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn get_remote_profile(
     state: State<'_, Arc<AppState>>,
@@ -1,7 +1,13 @@
+use crate::follow::{ensure_feed_key, revoke_follower, rotate_feed_key, send_follow_message};
 use crate::state::AppState;
-use iroh_social_types::{FollowEntry, FollowerEntry, now_millis, short_id};
+use iroh::SecretKey;
+use iroh_social_types::{
+    ApprovedFollower, FollowDecision, FollowEntry, FollowMessage, FollowRequest,
+    FollowRequestEntry, FollowRequestStatus, FollowerEntry, now_millis, short_id,
+    sign_follow_decision, sign_follow_request,
+};
 use std::sync::Arc;
-use tauri::{AppHandle, State};
+use tauri::{AppHandle, Emitter, State};
 
 use super::sync::process_sync_result;
 
@@ -61,6 +67,211 @@ pub async fn follow_user(
         }
     }
 
+    // A private profile only lets us sync once it approves our request
+    if state.storage.is_private_profile(&pubkey).unwrap_or(false)
+        && let Err(e) = send_follow_request(&state, &pubkey).await
+    {
+        log::error!(
+            "[follow] failed to request to follow {}: {e}",
+            short_id(&pubkey)
+        );
+    }
+
+    Ok(())
+}
+
+/// Sign and send a request to follow `pubkey`, recording the answer.
+async fn send_follow_request(
+    state: &AppState,
+    pubkey: &str,
+) -> Result<FollowRequestStatus, String> {
+    let mut req = FollowRequest {
+        requester: state.endpoint.id().to_string(),
+        target: pubkey.to_string(),
+        timestamp: now_millis(),
+        signature: String::new(),
+    };
+    let sk = SecretKey::from_bytes(&state.secret_key_bytes);
+    sign_follow_request(&mut req, &sk);
+
+    state
+        .storage
+        .set_sent_follow_request(pubkey, FollowRequestStatus::Pending, req.timestamp)
+        .map_err(|e| e.to_string())?;
+    let status = send_follow_message(&state.endpoint, pubkey, &FollowMessage::Request(req))
+        .await
+        .map_err(|e| e.to_string())?;
+    state
+        .storage
+        .set_sent_follow_request(pubkey, status, now_millis())
+        .map_err(|e| e.to_string())?;
+    log::info!(
+        "[follow] request to follow {}: {}",
+        short_id(pubkey),
+        status.as_str()
+    );
+    Ok(status)
+}
+
+/// Sign our answer to a follow request and deliver it in the background.
+fn send_follow_decision(state: &AppState, pubkey: &str, approved: bool) {
+    let mut decision = FollowDecision {
+        target: state.endpoint.id().to_string(),
+        requester: pubkey.to_string(),
+        approved,
+        timestamp: now_millis(),
+        signature: String::new(),
+    };
+    let sk = SecretKey::from_bytes(&state.secret_key_bytes);
+    sign_follow_decision(&mut decision, &sk);
+
+    let endpoint = state.endpoint.clone();
+    let pubkey = pubkey.to_string();
+    tokio::spawn(async move {
+        if let Err(e) =
+            send_follow_message(&endpoint, &pubkey, &FollowMessage::Decision(decision)).await
+        {
+            log::error!(
+                "[follow] failed to deliver decision to {}: {e}",
+                short_id(&pubkey)
+            );
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn request_follow(
+    state: State<'_, Arc<AppState>>,
+    pubkey: String,
+) -> Result<FollowRequestStatus, String> {
+    if pubkey == state.endpoint.id().to_string() {
+        return Err("cannot follow yourself".to_string());
+    }
+    send_follow_request(&state, &pubkey).await
+}
+
+/// Status of our own request to follow `pubkey`, if we sent one.
+#[tauri::command]
+pub async fn get_follow_request_status(
+    state: State<'_, Arc<AppState>>,
+    pubkey: String,
+) -> Result<Option<FollowRequestStatus>, String> {
+    state
+        .storage
+        .get_sent_follow_request(&pubkey)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_follow_requests(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<FollowRequestEntry>, String> {
+    state
+        .storage
+        .get_pending_follow_requests()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn approve_follow_request(
+    app_handle: AppHandle,
+    state: State<'_, Arc<AppState>>,
+    pubkey: String,
+) -> Result<(), String> {
+    if state
+        .storage
+        .get_follow_request_status(&pubkey)
+        .map_err(|e| e.to_string())?
+        .is_none()
+    {
+        return Err("no follow request from this user".to_string());
+    }
+    state
+        .storage
+        .approve_follower(&pubkey, now_millis())
+        .map_err(|e| e.to_string())?;
+    log::info!("[follow] approved follower {}", short_id(&pubkey));
+    send_follow_decision(&state, &pubkey, true);
+    // The feed key follows over the DM channel
+    ensure_feed_key(&state.storage).map_err(|e| e.to_string())?;
+    state.dm.request_outbox_flush(&pubkey);
+    let _ = app_handle.emit("follower-changed", &pubkey);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn deny_follow_request(
+    app_handle: AppHandle,
+    state: State<'_, Arc<AppState>>,
+    pubkey: String,
+) -> Result<(), String> {
+    let was_approved = state
+        .storage
+        .is_approved_follower(&pubkey)
+        .map_err(|e| e.to_string())?;
+    if !state
+        .storage
+        .deny_follow_request(&pubkey)
+        .map_err(|e| e.to_string())?
+    {
+        return Err("no follow request from this user".to_string());
+    }
+    log::info!("[follow] denied follow request from {}", short_id(&pubkey));
+    send_follow_decision(&state, &pubkey, false);
+    if was_approved {
+        // They may hold the current key; move the rest on to a new one
+        rotate_feed_key(&state.storage).map_err(|e| e.to_string())?;
+        redistribute_feed_key(&state)?;
+        let _ = app_handle.emit("follower-changed", &pubkey);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_approved_followers(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<ApprovedFollower>, String> {
+    state
+        .storage
+        .get_approved_followers()
+        .map_err(|e| e.to_string())
+}
+
+/// Revoke a follower's approval and rotate the feed key, so posts from now
+/// on are sealed under a key they never receive. Returns whether they were
+/// approved.
+pub(crate) fn revoke_approved_follower(state: &AppState, pubkey: &str) -> Result<bool, String> {
+    if revoke_follower(&state.storage, pubkey)
+        .map_err(|e| e.to_string())?
+        .is_none()
+    {
+        return Ok(false);
+    }
+    log::info!("[follow] removed approved follower {}", short_id(pubkey));
+    redistribute_feed_key(state)?;
+    Ok(true)
+}
+
+/// Queue the current feed key for everyone still approved, after a rotation.
+fn redistribute_feed_key(state: &AppState) -> Result<(), String> {
+    for follower in state
+        .storage
+        .get_approved_followers()
+        .map_err(|e| e.to_string())?
+    {
+        state.dm.request_outbox_flush(&follower.pubkey);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn remove_approved_follower(
+    app_handle: AppHandle,
+    state: State<'_, Arc<AppState>>,
+    pubkey: String,
+) -> Result<(), String> {
+    revoke_approved_follower(&state, &pubkey)?;
+    let _ = app_handle.emit("follower-changed", &pubkey);
     Ok(())
 }
 
@@ -68,6 +279,10 @@ pub async fn follow_user(
 pub async fn unfollow_user(state: State<'_, Arc<AppState>>, pubkey: String) -> Result<(), String> {
     log::info!("[follow] unfollowing {}...", short_id(&pubkey));
     state.storage.unfollow(&pubkey).map_err(|e| e.to_string())?;
+    state
+        .storage
+        .delete_sent_follow_request(&pubkey)
+        .map_err(|e| e.to_string())?;
     let mut feed = state.feed.lock().await;
     feed.unfollow_user(&pubkey);
     log::info!("[follow] unfollowed {}", short_id(&pubkey));
@@ -76,6 +291,7 @@ pub async fn unfollow_user(state: State<'_, Arc<AppState>>, pubkey: String) -> R
 
 #[tauri::command]
 pub async fn update_follow_alias(
+    app_handle: AppHandle,
     state: State<'_, Arc<AppState>>,
     pubkey: String,
     alias: Option<String>,
@@ -83,7 +299,30 @@ pub async fn update_follow_alias(
     state
         .storage
         .update_follow_alias(&pubkey, alias.as_deref())
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+
+    // Warn if this alias used to name a different, verified identity
+    if let Some(alias) = alias.as_deref().filter(|a| !a.is_empty())
+        && let Some(previous) = state
+            .storage
+            .get_alias_conflict(alias, &pubkey)
+            .map_err(|e| e.to_string())?
+    {
+        log::warn!(
+            "[follow] alias {alias:?} now points at {}, was verified as {}",
+            short_id(&pubkey),
+            short_id(&previous)
+        );
+        let _ = app_handle.emit(
+            "dm-identity-changed",
+            serde_json::json!({
+                "alias": alias,
+                "peer": pubkey,
+                "previous": previous,
+            }),
+        );
+    }
+    Ok(())
 }
 
 #[tauri::command]
@@ -1,13 +1,14 @@
 use crate::state::{AppState, FrontendSyncResult, SyncStatus};
 use crate::storage::Storage;
 use iroh_social_types::{
-    parse_mentions, short_id, validate_interaction, validate_post, verify_interaction_signature,
+    MuteScope, check_profile, parse_mentions, short_id, validate_interaction, validate_post,
+    validate_post_edit, validate_profile, verify_interaction_signature, verify_post_edit_signature,
     verify_post_signature,
 };
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter, State};
 
-/// Validate and store posts/interactions/profile from a sync result.
+/// Validate and store posts/interactions/edits/profile from a sync result.
 /// Returns the number of posts actually stored.
 pub(crate) fn process_sync_result(
     storage: &Storage,
@@ -27,11 +28,29 @@ pub(crate) fn process_sync_result(
             log::error!("[{label}] rejected post {} (bad sig): {reason}", &post.id);
             continue;
         }
-        if let Err(e) = storage.insert_post(post) {
+        let inserted = match &post.audience {
+            Some(audience) => storage.insert_audience_post(post, audience),
+            None => storage.insert_post(post),
+        };
+        if let Err(e) = inserted {
             log::error!("[{label}] failed to store post: {e}");
             continue;
         }
-        if post.author != my_id {
+        let notify = post.author != my_id
+            && !storage
+                .is_muted_for(&post.author, MuteScope::Notifications)
+                .unwrap_or(false)
+            && match storage.notification_muted_by(post) {
+                Ok(Some(pattern)) => {
+                    log::info!(
+                        "[{label}] no notification for {}: muted {pattern:?}",
+                        &post.id
+                    );
+                    false
+                }
+                _ => true,
+            };
+        if notify {
             if parse_mentions(&post.content).contains(&my_id.to_string()) {
                 let _ = storage.insert_notification(
                     "mention",
@@ -55,7 +74,7 @@ pub(crate) fn process_sync_result(
             }
             if post.quote_of_author.as_deref() == Some(my_id) {
                 let _ = storage.insert_notification(
-                    "quote",
+                    post.kind().as_str(),
                     &post.author,
                     post.quote_of.as_deref(),
                     Some(&post.id),
@@ -66,10 +85,16 @@ pub(crate) fn process_sync_result(
         }
         stored += 1;
     }
-    if let Some(profile) = &result.profile
-        && let Err(e) = storage.save_profile(pubkey, profile)
-    {
-        log::error!("[{label}] failed to store profile: {e}");
+    if let Some(profile) = &result.profile {
+        let mut profile = profile.clone();
+        match validate_profile(&profile).and_then(|_| check_profile(&mut profile, pubkey)) {
+            Ok(()) => match storage.save_profile(pubkey, &profile) {
+                Ok(true) => {}
+                Ok(false) => log::info!("[{label}] ignored older profile"),
+                Err(e) => log::error!("[{label}] failed to store profile: {e}"),
+            },
+            Err(reason) => log::error!("[{label}] rejected profile: {reason}"),
+        }
     }
     for interaction in &result.interactions {
         if interaction.author == pubkey
@@ -79,7 +104,7 @@ pub(crate) fn process_sync_result(
             let _ = storage.save_interaction(interaction);
             if interaction.target_author == my_id && interaction.author != my_id {
                 let _ = storage.insert_notification(
-                    "like",
+                    interaction.kind.notification_kind(),
                     &interaction.author,
                     Some(&interaction.target_post_id),
                     None,
@@ -89,6 +114,14 @@ pub(crate) fn process_sync_result(
             }
         }
     }
+    for edit in &result.edits {
+        if edit.author == pubkey
+            && validate_post_edit(edit).is_ok()
+            && verify_post_edit_signature(edit).is_ok()
+        {
+            let _ = storage.insert_post_edit(edit);
+        }
+    }
     stored
 }
 
@@ -128,7 +161,7 @@ pub async fn get_sync_status(
 ) -> Result<SyncStatus, String> {
     let local_count = state
         .storage
-        .count_posts_by_author(&pubkey)
+        .count_posts_by_author(&pubkey, None)
         .map_err(|e| e.to_string())?;
     Ok(SyncStatus { local_count })
 }
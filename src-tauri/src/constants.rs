@@ -14,8 +14,50 @@ pub const DRIP_PEER_PACE: Duration = Duration::from_secs(5);
 pub const DRIP_ACTIVE_INTERVAL: Duration = Duration::from_secs(30);
 pub const DRIP_IDLE_INTERVAL: Duration = Duration::from_secs(120);
 
+// Fetching individual posts
+pub const POST_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+/// Followed peers asked for a post after its author, who may be offline.
+pub const POST_FETCH_FALLBACK_PEERS: usize = 3;
+
+// Follow requests
+pub const FOLLOW_TIMEOUT: Duration = Duration::from_secs(10);
+
+// DM connections
+pub const DM_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DM_ACK_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DM_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+pub const DM_STREAM_TIMEOUT: Duration = Duration::from_secs(30);
+
 // DM outbox
 pub const OUTBOX_FLUSH_INTERVAL: Duration = Duration::from_secs(15);
+pub const OUTBOX_MAX_BACKOFF: Duration = Duration::from_secs(30 * 60);
+pub const OUTBOX_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+// Threads and conversations
+pub const MAX_THREAD_LEN: usize = 25;
+pub const MAX_ANCESTOR_DEPTH: u32 = 100;
+pub const DEFAULT_CONVERSATION_DEPTH: u32 = 6;
+/// Deepest reply tree a caller may ask `get_conversation` for.
+pub const MAX_CONVERSATION_DEPTH: u32 = 20;
+pub const MAX_CONVERSATION_NODES: usize = 500;
+/// Missing ancestors fetched while walking up a conversation, per request.
+pub const MAX_ANCESTOR_FETCHES: usize = 5;
+
+// Scheduled posts
+pub const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+// Link previews
+/// Time allowed to fetch the linked page.
+pub const LINK_PREVIEW_PAGE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time allowed to fetch the page's thumbnail once the page is read.
+pub const LINK_PREVIEW_THUMBNAIL_TIMEOUT: Duration = Duration::from_secs(5);
+pub const LINK_PREVIEW_MAX_PAGE_BYTES: usize = 512 * 1024;
+pub const LINK_PREVIEW_MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+// Ephemeral posts
+pub const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+/// How often blobs that nothing refers to any more are removed from disk.
+pub const BLOB_GC_INTERVAL: Duration = Duration::from_secs(10 * 60);
 
 // Android network monitoring
 #[cfg(target_os = "android")]
@@ -24,6 +66,12 @@ pub const ANDROID_NET_INTERVAL: Duration = Duration::from_secs(30);
 // Relay address logging delay
 pub const RELAY_LOG_DELAY: Duration = Duration::from_secs(3);
 
+// Ranked feed
+/// How far back the ranked feed looks for posts.
+pub const RANKED_FEED_WINDOW: Duration = Duration::from_secs(3 * 24 * 60 * 60);
+/// Most recent posts scored per request.
+pub const RANKED_FEED_CANDIDATES: usize = 500;
+
 // Defaults
 pub const DEFAULT_FEED_LIMIT: usize = 20;
 pub const DEFAULT_NOTIFICATION_LIMIT: usize = 30;
@@ -386,6 +386,179 @@ impl RatchetState {
     }
 }
 
+// -- Attachment Encryption --
+
+/// Plaintext bytes per encrypted attachment chunk.
+const ATTACHMENT_CHUNK_SIZE: usize = 64 * 1024;
+/// Poly1305 tag appended to every encrypted chunk.
+const ATTACHMENT_TAG_SIZE: usize = 16;
+
+/// Nonce for attachment chunk `index`: the index as 8 little-endian bytes,
+/// then a final-chunk flag so truncation at a chunk boundary is detected.
+fn attachment_nonce(index: u64, last: bool) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[..8].copy_from_slice(&index.to_le_bytes());
+    nonce[8] = last as u8;
+    Nonce::clone_from_slice(&nonce)
+}
+
+/// Encrypt a DM attachment under a fresh random per-file key.
+/// The plaintext is sealed in fixed-size ChaCha20Poly1305 chunks so large
+/// files never need a single nonce/key pair to cover more than one chunk.
+/// Returns the key and the ciphertext.
+pub fn encrypt_attachment(plaintext: &[u8]) -> ([u8; 32], Vec<u8>) {
+    let mut key = [0u8; 32];
+    getrandom::fill(&mut key).expect("failed to generate random key");
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+
+    let chunk_count = plaintext.len().div_ceil(ATTACHMENT_CHUNK_SIZE).max(1);
+    let mut ciphertext = Vec::with_capacity(plaintext.len() + chunk_count * ATTACHMENT_TAG_SIZE);
+    for index in 0..chunk_count {
+        let start = index * ATTACHMENT_CHUNK_SIZE;
+        let end = (start + ATTACHMENT_CHUNK_SIZE).min(plaintext.len());
+        let nonce = attachment_nonce(index as u64, index + 1 == chunk_count);
+        let sealed = cipher
+            .encrypt(&nonce, &plaintext[start..end])
+            .expect("encryption should not fail");
+        ciphertext.extend_from_slice(&sealed);
+    }
+    (key, ciphertext)
+}
+
+/// Decrypt a DM attachment produced by `encrypt_attachment`.
+pub fn decrypt_attachment(key: &[u8; 32], ciphertext: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if ciphertext.len() < ATTACHMENT_TAG_SIZE {
+        return Err(CryptoError::DecryptionFailed);
+    }
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+
+    let sealed_size = ATTACHMENT_CHUNK_SIZE + ATTACHMENT_TAG_SIZE;
+    let chunk_count = ciphertext.len().div_ceil(sealed_size);
+    let mut plaintext = Vec::with_capacity(ciphertext.len());
+    for (index, chunk) in ciphertext.chunks(sealed_size).enumerate() {
+        let nonce = attachment_nonce(index as u64, index + 1 == chunk_count);
+        let opened = cipher
+            .decrypt(&nonce, chunk)
+            .map_err(|_| CryptoError::DecryptionFailed)?;
+        plaintext.extend_from_slice(&opened);
+    }
+    Ok(plaintext)
+}
+
+// -- Feed Keys --
+
+/// Generate a fresh key for sealing a private profile's posts.
+pub fn generate_feed_key() -> [u8; 32] {
+    let mut key = [0u8; 32];
+    getrandom::fill(&mut key).expect("failed to generate random key");
+    key
+}
+
+/// Encrypt under a feed key. The key seals many payloads, so each gets a
+/// random nonce. Returns the nonce and the ciphertext.
+pub fn feed_encrypt(key: &[u8; 32], plaintext: &[u8]) -> ([u8; 12], Vec<u8>) {
+    let mut nonce = [0u8; 12];
+    getrandom::fill(&mut nonce).expect("failed to generate random nonce");
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .expect("encryption should not fail");
+    (nonce, ciphertext)
+}
+
+/// Decrypt a payload produced by `feed_encrypt`.
+pub fn feed_decrypt(
+    key: &[u8; 32],
+    nonce: &[u8],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    if nonce.len() != 12 {
+        return Err(CryptoError::DecryptionFailed);
+    }
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| CryptoError::DecryptionFailed)
+}
+
+// -- Safety Numbers --
+
+const FINGERPRINT_VERSION: u16 = 0;
+/// Hash iterations per fingerprint, slowing down searches for colliding keys.
+const FINGERPRINT_ITERATIONS: usize = 5200;
+const SAFETY_NUMBER_PREFIX: &str = "iroh-social-safety:v1:";
+
+/// Fingerprint of one identity: iterated SHA-512 over its Ed25519 node key
+/// and the X25519 key derived from it, truncated to 30 bytes.
+fn identity_fingerprint(ed25519_public: &[u8; 32]) -> Option<[u8; 30]> {
+    let x25519_public = ed25519_public_to_x25519(ed25519_public)?;
+    let mut hasher = Sha512::new();
+    hasher.update(FINGERPRINT_VERSION.to_be_bytes());
+    hasher.update(ed25519_public);
+    hasher.update(x25519_public);
+    let mut digest = hasher.finalize();
+    for _ in 0..FINGERPRINT_ITERATIONS {
+        let mut hasher = Sha512::new();
+        hasher.update(digest);
+        hasher.update(x25519_public);
+        digest = hasher.finalize();
+    }
+    let mut fingerprint = [0u8; 30];
+    fingerprint.copy_from_slice(&digest[..30]);
+    Some(fingerprint)
+}
+
+/// Render a fingerprint as six 5-digit groups, each from 5 bytes mod 100000.
+fn fingerprint_digits(fingerprint: &[u8; 30]) -> Vec<String> {
+    fingerprint
+        .chunks(5)
+        .map(|chunk| {
+            let n = chunk.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+            format!("{:05}", n % 100_000)
+        })
+        .collect()
+}
+
+/// Safety number for the pair of identities, as `(digits, scannable)`.
+/// Fingerprints are ordered by public key so both sides compute identical
+/// values: `digits` is twelve space-separated 5-digit groups for reading
+/// aloud, `scannable` is a string to encode in a QR code and compare.
+/// Returns `None` if either key is not a valid Ed25519 point.
+pub fn safety_number(a: &[u8; 32], b: &[u8; 32]) -> Option<(String, String)> {
+    let (first, second) = if a <= b { (a, b) } else { (b, a) };
+    let first = identity_fingerprint(first)?;
+    let second = identity_fingerprint(second)?;
+    let mut digits = fingerprint_digits(&first);
+    digits.extend(fingerprint_digits(&second));
+    let scannable = format!(
+        "{SAFETY_NUMBER_PREFIX}{}{}",
+        hex::encode(first),
+        hex::encode(second)
+    );
+    Some((digits.join(" "), scannable))
+}
+
+pub(crate) mod hex {
+    pub fn encode(bytes: impl AsRef<[u8]>) -> String {
+        bytes.as_ref().iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    /// Decode a hex string. Input may come from peers, so anything but ASCII
+    /// is rejected before slicing by byte offsets.
+    pub fn decode(s: &str) -> Result<Vec<u8>, String> {
+        if !s.is_ascii() {
+            return Err("invalid hex: non-ASCII input".to_string());
+        }
+        if !s.len().is_multiple_of(2) {
+            return Err("odd length hex string".to_string());
+        }
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|e| format!("invalid hex: {e}")))
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -415,7 +588,7 @@ mod tests {
         // Derive ed25519 public key using ed25519-dalek-compatible method:
         // The public key is the compressed Edwards Y coordinate of the scalar * basepoint.
         // We use curve25519_dalek directly since we have it as a dependency.
-        let hash = Sha512::digest(&ed_secret);
+        let hash = Sha512::digest(ed_secret);
         let mut scalar_bytes = [0u8; 32];
         scalar_bytes.copy_from_slice(&hash[..32]);
         scalar_bytes[0] &= 248;
@@ -639,4 +812,103 @@ mod tests {
         let (h, c) = alice.encrypt(b"This is E2E encrypted!");
         assert_eq!(bob.decrypt(&h, &c).unwrap(), b"This is E2E encrypted!");
     }
+
+    #[test]
+    fn test_attachment_roundtrip() {
+        // Spans several chunks with a partial final chunk
+        let plaintext: Vec<u8> = (0..(ATTACHMENT_CHUNK_SIZE * 3 + 123))
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let (key, ciphertext) = encrypt_attachment(&plaintext);
+        assert_ne!(&ciphertext[..64], &plaintext[..64]);
+        assert_eq!(decrypt_attachment(&key, &ciphertext).unwrap(), plaintext);
+
+        let (key, ciphertext) = encrypt_attachment(&[]);
+        assert!(decrypt_attachment(&key, &ciphertext).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_attachment_wrong_key_fails() {
+        let (_, ciphertext) = encrypt_attachment(b"secret attachment");
+        let wrong_key = [7u8; 32];
+        assert!(decrypt_attachment(&wrong_key, &ciphertext).is_err());
+    }
+
+    #[test]
+    fn test_attachment_truncation_fails() {
+        let plaintext = vec![9u8; ATTACHMENT_CHUNK_SIZE * 2 + 10];
+        let (key, ciphertext) = encrypt_attachment(&plaintext);
+        let first_two = 2 * (ATTACHMENT_CHUNK_SIZE + ATTACHMENT_TAG_SIZE);
+        assert!(decrypt_attachment(&key, &ciphertext[..first_two]).is_err());
+        assert!(decrypt_attachment(&key, &[]).is_err());
+    }
+
+    #[test]
+    fn test_feed_key_roundtrip() {
+        let key = generate_feed_key();
+        let (nonce, ciphertext) = feed_encrypt(&key, b"followers only");
+        assert_eq!(
+            feed_decrypt(&key, &nonce, &ciphertext).unwrap(),
+            b"followers only"
+        );
+
+        // Each payload gets its own nonce
+        let (other_nonce, _) = feed_encrypt(&key, b"followers only");
+        assert_ne!(nonce, other_nonce);
+
+        // A rotated key doesn't open payloads sealed under the old one
+        let rotated = generate_feed_key();
+        assert!(feed_decrypt(&rotated, &nonce, &ciphertext).is_err());
+        assert!(feed_decrypt(&key, &nonce[..8], &ciphertext).is_err());
+    }
+
+    #[test]
+    fn test_hex_roundtrip_and_rejects_bad_input() {
+        let bytes = [0x00, 0x7f, 0xab, 0xff];
+        assert_eq!(hex::decode(&hex::encode(bytes)).unwrap(), bytes);
+        assert!(hex::decode("abc").is_err());
+        assert!(hex::decode("zz").is_err());
+        // Multibyte characters of even byte length must not panic
+        assert!(hex::decode("éé").is_err());
+        assert!(hex::decode("ab€0").is_err());
+        assert!(hex::decode("日本").is_err());
+    }
+
+    fn random_ed25519_public() -> [u8; 32] {
+        use curve25519_dalek::edwards::EdwardsPoint;
+        use curve25519_dalek::scalar::Scalar;
+        let mut scalar_bytes = [0u8; 32];
+        getrandom::fill(&mut scalar_bytes).unwrap();
+        let scalar = Scalar::from_bytes_mod_order(scalar_bytes);
+        EdwardsPoint::mul_base(&scalar).compress().to_bytes()
+    }
+
+    #[test]
+    fn test_safety_number_symmetric() {
+        let alice = random_ed25519_public();
+        let bob = random_ed25519_public();
+        let (digits_ab, scannable_ab) = safety_number(&alice, &bob).unwrap();
+        let (digits_ba, scannable_ba) = safety_number(&bob, &alice).unwrap();
+        assert_eq!(digits_ab, digits_ba);
+        assert_eq!(scannable_ab, scannable_ba);
+
+        let groups: Vec<&str> = digits_ab.split(' ').collect();
+        assert_eq!(groups.len(), 12);
+        assert!(
+            groups
+                .iter()
+                .all(|g| g.len() == 5 && g.chars().all(|c| c.is_ascii_digit()))
+        );
+    }
+
+    #[test]
+    fn test_safety_number_differs_per_identity() {
+        let alice = random_ed25519_public();
+        let bob = random_ed25519_public();
+        let mallory = random_ed25519_public();
+        let (digits_ab, scannable_ab) = safety_number(&alice, &bob).unwrap();
+        let (digits_am, scannable_am) = safety_number(&alice, &mallory).unwrap();
+        assert_ne!(digits_ab, digits_am);
+        assert_ne!(scannable_ab, scannable_am);
+    }
 }
@@ -0,0 +1,302 @@
+use crate::constants::FOLLOW_TIMEOUT;
+use crate::crypto::{feed_decrypt, feed_encrypt, generate_feed_key};
+use crate::storage::Storage;
+use iroh::{
+    Endpoint, EndpointAddr, EndpointId,
+    endpoint::Connection,
+    protocol::{AcceptError, ProtocolHandler},
+};
+use iroh_social_types::{
+    FOLLOW_ALPN, FeedKey, FollowDecision, FollowMessage, FollowRequest, FollowRequestStatus,
+    GossipMessage, SealedMessage, now_millis, short_id, verify_follow_decision_signature,
+    verify_follow_request_signature,
+};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter};
+
+#[derive(Debug, Clone)]
+pub struct FollowHandler {
+    storage: Arc<Storage>,
+    node_id: String,
+    app_handle: AppHandle,
+}
+
+impl FollowHandler {
+    pub fn new(storage: Arc<Storage>, node_id: String, app_handle: AppHandle) -> Self {
+        Self {
+            storage,
+            node_id,
+            app_handle,
+        }
+    }
+
+    /// A request to follow us. Public profiles answer approved without
+    /// recording anything, so going private later doesn't hand the feed key
+    /// to everyone who followed while public; private ones hold the request
+    /// until the user approves or denies it.
+    fn handle_request(
+        &self,
+        remote: &str,
+        req: &FollowRequest,
+    ) -> anyhow::Result<FollowRequestStatus> {
+        if req.requester != remote || req.target != self.node_id {
+            anyhow::bail!("follow request does not match the connection");
+        }
+        verify_follow_request_signature(req).map_err(|e| anyhow::anyhow!(e))?;
+
+        if self.storage.is_approved_follower(remote)? {
+            return Ok(FollowRequestStatus::Approved);
+        }
+        if !self.storage.is_private_profile(&self.node_id)? {
+            return Ok(FollowRequestStatus::Approved);
+        }
+        if self.storage.upsert_follow_request(req)? {
+            log::info!("[follow] follow request from {}", short_id(remote));
+            let _ = self.storage.insert_notification(
+                "follow_request",
+                remote,
+                None,
+                None,
+                req.timestamp,
+            );
+            let _ = self.app_handle.emit("follow-request-received", remote);
+            let _ = self.app_handle.emit("notification-received", ());
+        }
+        Ok(FollowRequestStatus::Pending)
+    }
+
+    /// The owner of a private profile answered our request to follow them.
+    fn handle_decision(
+        &self,
+        remote: &str,
+        decision: &FollowDecision,
+    ) -> anyhow::Result<FollowRequestStatus> {
+        if decision.target != remote || decision.requester != self.node_id {
+            anyhow::bail!("follow decision does not match the connection");
+        }
+        verify_follow_decision_signature(decision).map_err(|e| anyhow::anyhow!(e))?;
+
+        let status = if decision.approved {
+            FollowRequestStatus::Approved
+        } else {
+            FollowRequestStatus::Denied
+        };
+        if !self.storage.update_sent_follow_request(remote, status)? {
+            anyhow::bail!("no follow request was sent to this peer");
+        }
+        log::info!(
+            "[follow] request to follow {} was {}",
+            short_id(remote),
+            status.as_str()
+        );
+        if decision.approved {
+            let _ = self.storage.insert_notification(
+                "follow_approved",
+                remote,
+                None,
+                None,
+                decision.timestamp,
+            );
+            let _ = self.app_handle.emit("notification-received", ());
+        }
+        let _ = self.app_handle.emit("follow-request-decided", remote);
+        Ok(status)
+    }
+}
+
+impl ProtocolHandler for FollowHandler {
+    async fn accept(&self, conn: Connection) -> Result<(), AcceptError> {
+        let remote = conn.remote_id().to_string();
+
+        if self.storage.is_blocked(&remote).unwrap_or(false) {
+            log::warn!("[follow] rejecting blocked peer {}", short_id(&remote));
+            return Err(AcceptError::from_err(std::io::Error::other("blocked")));
+        }
+
+        let (mut send, mut recv) = conn.accept_bi().await?;
+        let msg_bytes = recv
+            .read_to_end(65_536)
+            .await
+            .map_err(AcceptError::from_err)?;
+        let msg: FollowMessage =
+            serde_json::from_slice(&msg_bytes).map_err(AcceptError::from_err)?;
+
+        let status = match &msg {
+            FollowMessage::Request(req) => self.handle_request(&remote, req),
+            FollowMessage::Decision(decision) => self.handle_decision(&remote, decision),
+        }
+        .map_err(|e| {
+            log::warn!("[follow] rejected message from {}: {e}", short_id(&remote));
+            AcceptError::from_err(std::io::Error::other(e.to_string()))
+        })?;
+
+        let resp_bytes = serde_json::to_vec(&status).map_err(AcceptError::from_err)?;
+        send.write_all(&resp_bytes)
+            .await
+            .map_err(AcceptError::from_err)?;
+        send.finish().map_err(AcceptError::from_err)?;
+        conn.closed().await;
+        Ok(())
+    }
+}
+
+/// Client: send a follow request or decision to `target` and return the
+/// status it answers with.
+pub async fn send_follow_message(
+    endpoint: &Endpoint,
+    target: &str,
+    msg: &FollowMessage,
+) -> anyhow::Result<FollowRequestStatus> {
+    let target: EndpointId = target.parse()?;
+    let exchange = async {
+        let conn = endpoint
+            .connect(EndpointAddr::from(target), FOLLOW_ALPN)
+            .await?;
+        let (mut send, mut recv) = conn.open_bi().await?;
+        send.write_all(&serde_json::to_vec(msg)?).await?;
+        send.finish()?;
+        let resp_bytes = recv.read_to_end(1024).await?;
+        conn.close(0u32.into(), b"done");
+        anyhow::Ok(serde_json::from_slice::<FollowRequestStatus>(&resp_bytes)?)
+    };
+    tokio::time::timeout(FOLLOW_TIMEOUT, exchange)
+        .await
+        .map_err(|_| anyhow::anyhow!("follow request timed out"))?
+}
+
+/// The current key of our private feed, created on first use.
+pub fn ensure_feed_key(storage: &Storage) -> anyhow::Result<FeedKey> {
+    match storage.current_feed_key()? {
+        Some(key) => Ok(key),
+        None => rotate_feed_key(storage),
+    }
+}
+
+/// Replace the feed key, so followers removed since the last rotation can't
+/// read what we post next. Remaining approved followers get the new key with
+/// their next outbox flush.
+pub fn rotate_feed_key(storage: &Storage) -> anyhow::Result<FeedKey> {
+    let key = FeedKey {
+        key_id: uuid::Uuid::new_v4().to_string(),
+        key: generate_feed_key().to_vec(),
+        created_at: now_millis(),
+    };
+    storage.insert_feed_key(&key)?;
+    log::info!("[follow] rotated feed key to {}", key.key_id);
+    Ok(key)
+}
+
+/// Revoke `pubkey`'s approval and rotate the feed key, so what we post
+/// from now on is sealed under a key they never receive. Returns the new
+/// key, or `None` if they weren't approved.
+pub fn revoke_follower(storage: &Storage, pubkey: &str) -> anyhow::Result<Option<FeedKey>> {
+    if !storage.remove_approved_follower(pubkey)? {
+        return Ok(None);
+    }
+    rotate_feed_key(storage).map(Some)
+}
+
+/// Seal a message of our private feed under the current feed key.
+pub fn seal_message(
+    storage: &Storage,
+    author: &str,
+    msg: &GossipMessage,
+) -> anyhow::Result<SealedMessage> {
+    let feed_key = ensure_feed_key(storage)?;
+    let key: [u8; 32] = feed_key
+        .key
+        .as_slice()
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("invalid feed key"))?;
+    let (nonce, ciphertext) = feed_encrypt(&key, &serde_json::to_vec(msg)?);
+    Ok(SealedMessage {
+        author: author.to_string(),
+        key_id: feed_key.key_id,
+        nonce: nonce.to_vec(),
+        ciphertext,
+    })
+}
+
+/// Open a sealed message with the feed key its author sent us.
+pub fn open_sealed_message(
+    storage: &Storage,
+    sealed: &SealedMessage,
+) -> Result<GossipMessage, String> {
+    let feed_key = storage
+        .get_received_feed_key(&sealed.author, &sealed.key_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("no feed key {}", sealed.key_id))?;
+    let key: [u8; 32] = feed_key
+        .key
+        .as_slice()
+        .try_into()
+        .map_err(|_| "invalid feed key".to_string())?;
+    let plaintext =
+        feed_decrypt(&key, &sealed.nonce, &sealed.ciphertext).map_err(|e| e.to_string())?;
+    let msg: GossipMessage = serde_json::from_slice(&plaintext).map_err(|e| e.to_string())?;
+    let author = match &msg {
+        GossipMessage::NewPost(post) => &post.author,
+        GossipMessage::EditPost(edit) => &edit.author,
+        GossipMessage::DeletePost { author, .. } => author,
+        GossipMessage::NewInteraction(interaction) => &interaction.author,
+        GossipMessage::DeleteInteraction { author, .. } => author,
+        GossipMessage::ProfileUpdate(_) | GossipMessage::Sealed(_) => {
+            return Err("unexpected sealed message".to_string());
+        }
+    };
+    if *author != sealed.author {
+        return Err("sealed message author mismatch".to_string());
+    }
+    Ok(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_util;
+
+    const OWNER: &str = "owner";
+
+    fn post(id: &str) -> GossipMessage {
+        GossipMessage::NewPost(test_util::post(id, OWNER, 1))
+    }
+
+    #[test]
+    fn test_revoking_a_follower_rotates_the_feed_key() {
+        let owner = Storage::open(":memory:").unwrap();
+        let old_key = ensure_feed_key(&owner).unwrap();
+        owner.approve_follower("bob", 1).unwrap();
+        owner.approve_follower("carol", 1).unwrap();
+
+        // Bob received the key while approved
+        let bob = Storage::open(":memory:").unwrap();
+        bob.save_received_feed_key(OWNER, &old_key).unwrap();
+        let sealed = seal_message(&owner, OWNER, &post("p1")).unwrap();
+        assert_eq!(sealed.key_id, old_key.key_id);
+        assert!(open_sealed_message(&bob, &sealed).is_ok());
+
+        let new_key = revoke_follower(&owner, "bob")
+            .unwrap()
+            .expect("bob was approved");
+        assert_ne!(new_key.key_id, old_key.key_id);
+        assert_eq!(
+            owner.current_feed_key().unwrap().unwrap().key_id,
+            new_key.key_id
+        );
+        assert!(!owner.is_approved_follower("bob").unwrap());
+        assert!(owner.is_approved_follower("carol").unwrap());
+        assert!(revoke_follower(&owner, "bob").unwrap().is_none());
+
+        // The old key doesn't open what is sealed under the new one
+        let sealed = seal_message(&owner, OWNER, &post("p2")).unwrap();
+        assert_eq!(sealed.key_id, new_key.key_id);
+        assert!(open_sealed_message(&bob, &sealed).is_err());
+        let old: [u8; 32] = old_key.key.as_slice().try_into().unwrap();
+        assert!(feed_decrypt(&old, &sealed.nonce, &sealed.ciphertext).is_err());
+
+        // Carol, still approved, opens it once the new key arrives
+        let carol = Storage::open(":memory:").unwrap();
+        carol.save_received_feed_key(OWNER, &new_key).unwrap();
+        assert!(open_sealed_message(&carol, &sealed).is_ok());
+    }
+}
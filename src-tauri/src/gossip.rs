@@ -1,3 +1,4 @@
+use crate::follow::{open_sealed_message, seal_message};
 use crate::storage::Storage;
 use bytes::Bytes;
 use futures_lite::StreamExt;
@@ -7,13 +8,15 @@ use iroh_gossip::{
     api::{Event, GossipSender},
 };
 use iroh_social_types::{
-    GossipMessage, Interaction, Post, Profile, now_millis, parse_mentions, short_id,
-    user_feed_topic, validate_interaction, validate_post, validate_profile,
-    verify_interaction_signature, verify_post_signature,
+    GossipMessage, Interaction, MuteScope, Post, PostEdit, Profile, check_profile, now_millis,
+    parse_mentions, short_id, user_feed_topic, validate_interaction, validate_post,
+    validate_post_edit, validate_profile, verify_interaction_signature, verify_post_edit_signature,
+    verify_post_signature,
 };
 use std::collections::HashMap;
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter};
+use tokio::sync::mpsc::UnboundedSender;
 use tokio::task::JoinHandle;
 
 pub struct FeedManager {
@@ -23,6 +26,8 @@ pub struct FeedManager {
     pub subscriptions: HashMap<String, (GossipSender, JoinHandle<()>)>,
     pub storage: Arc<Storage>,
     pub app_handle: AppHandle,
+    /// Notifies the DM outbox task when a peer shows up as a gossip neighbor.
+    pub outbox_flush_tx: UnboundedSender<String>,
 }
 
 impl FeedManager {
@@ -31,6 +36,7 @@ impl FeedManager {
         endpoint: Endpoint,
         storage: Arc<Storage>,
         app_handle: AppHandle,
+        outbox_flush_tx: UnboundedSender<String>,
     ) -> Self {
         Self {
             gossip,
@@ -39,9 +45,26 @@ impl FeedManager {
             subscriptions: HashMap::new(),
             storage,
             app_handle,
+            outbox_flush_tx,
         }
     }
 
+    /// While our profile is private, everything but profile updates goes out
+    /// sealed under the feed key, since anyone can subscribe to our topic and
+    /// relay it. That includes deletes and interactions, which would
+    /// otherwise reveal post ids and what we like.
+    fn seal_if_private(&self, msg: GossipMessage) -> anyhow::Result<GossipMessage> {
+        let my_id = self.endpoint.id().to_string();
+        if !self.storage.is_private_profile(&my_id)? {
+            return Ok(msg);
+        }
+        Ok(GossipMessage::Sealed(seal_message(
+            &self.storage,
+            &my_id,
+            &msg,
+        )?))
+    }
+
     pub async fn start_own_feed(&mut self) -> anyhow::Result<()> {
         let my_id = self.endpoint.id().to_string();
         let topic = user_feed_topic(&my_id);
@@ -54,6 +77,7 @@ impl FeedManager {
         // Listen for neighbors joining/leaving our own feed topic (followers)
         let storage = self.storage.clone();
         let app_handle = self.app_handle.clone();
+        let outbox_flush_tx = self.outbox_flush_tx.clone();
         tokio::spawn(async move {
             log::info!("[gossip-own] listener started for own feed neighbors");
             let mut receiver = receiver;
@@ -63,6 +87,7 @@ impl FeedManager {
                         Event::NeighborUp(endpoint_id) => {
                             let pubkey = endpoint_id.to_string();
                             log::info!("[gossip-own] new follower: {}", short_id(&pubkey));
+                            let _ = outbox_flush_tx.send(pubkey.clone());
                             let now = now_millis();
                             match storage.upsert_follower(&pubkey, now) {
                                 Ok(is_new) => {
@@ -126,7 +151,7 @@ impl FeedManager {
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("own feed not started"))?;
 
-        let msg = GossipMessage::NewPost(post.clone());
+        let msg = self.seal_if_private(GossipMessage::NewPost(post.clone()))?;
         let payload = serde_json::to_vec(&msg)?;
         sender.broadcast(Bytes::from(payload)).await?;
         log::info!("[gossip] broadcast post {}", &post.id);
@@ -134,16 +159,30 @@ impl FeedManager {
         Ok(())
     }
 
+    pub async fn broadcast_edit(&self, edit: &PostEdit) -> anyhow::Result<()> {
+        let sender = self
+            .my_sender
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("own feed not started"))?;
+
+        let msg = self.seal_if_private(GossipMessage::EditPost(edit.clone()))?;
+        let payload = serde_json::to_vec(&msg)?;
+        sender.broadcast(Bytes::from(payload)).await?;
+        log::info!("[gossip] broadcast edit of post {}", &edit.post_id);
+
+        Ok(())
+    }
+
     pub async fn broadcast_delete(&self, id: &str, author: &str) -> anyhow::Result<()> {
         let sender = self
             .my_sender
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("own feed not started"))?;
 
-        let msg = GossipMessage::DeletePost {
+        let msg = self.seal_if_private(GossipMessage::DeletePost {
             id: id.to_string(),
             author: author.to_string(),
-        };
+        })?;
         let payload = serde_json::to_vec(&msg)?;
         sender.broadcast(Bytes::from(payload)).await?;
         log::info!("[gossip] broadcast delete {id}");
@@ -157,7 +196,7 @@ impl FeedManager {
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("own feed not started"))?;
 
-        let msg = GossipMessage::NewInteraction(interaction.clone());
+        let msg = self.seal_if_private(GossipMessage::NewInteraction(interaction.clone()))?;
         let payload = serde_json::to_vec(&msg)?;
         sender.broadcast(Bytes::from(payload)).await?;
         log::info!(
@@ -175,10 +214,10 @@ impl FeedManager {
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("own feed not started"))?;
 
-        let msg = GossipMessage::DeleteInteraction {
+        let msg = self.seal_if_private(GossipMessage::DeleteInteraction {
             id: id.to_string(),
             author: author.to_string(),
-        };
+        })?;
         let payload = serde_json::to_vec(&msg)?;
         sender.broadcast(Bytes::from(payload)).await?;
         log::info!("[gossip] broadcast delete interaction {id}");
@@ -208,6 +247,7 @@ impl FeedManager {
         let pk = pubkey.clone();
         let my_id = self.endpoint.id().to_string();
         let app_handle = self.app_handle.clone();
+        let outbox_flush_tx = self.outbox_flush_tx.clone();
         let handle = tokio::spawn(async move {
             log::info!("[gossip-rx] listener started for {}", short_id(&pk));
             let mut receiver = receiver;
@@ -220,7 +260,22 @@ impl FeedManager {
                                 msg.content.len(),
                                 short_id(&pk)
                             );
-                            match serde_json::from_slice(&msg.content) {
+                            let parsed = match serde_json::from_slice(&msg.content) {
+                                Ok(GossipMessage::Sealed(sealed)) if sealed.author == pk => {
+                                    match open_sealed_message(&storage, &sealed) {
+                                        Ok(inner) => Ok(inner),
+                                        Err(reason) => {
+                                            log::info!(
+                                                "[gossip-rx] cannot open sealed message from {}: {reason}",
+                                                short_id(&pk)
+                                            );
+                                            continue;
+                                        }
+                                    }
+                                }
+                                other => other,
+                            };
+                            match parsed {
                                 Ok(GossipMessage::NewPost(post)) => {
                                     if post.author == pk {
                                         if let Err(reason) = validate_post(&post) {
@@ -251,7 +306,24 @@ impl FeedManager {
                                                     "[gossip-rx] failed to store post: {e}"
                                                 );
                                             }
-                                            if post.author != my_id {
+                                            let notify = post.author != my_id
+                                                && !storage
+                                                    .is_muted_for(
+                                                        &post.author,
+                                                        MuteScope::Notifications,
+                                                    )
+                                                    .unwrap_or(false)
+                                                && match storage.notification_muted_by(&post) {
+                                                    Ok(Some(pattern)) => {
+                                                        log::info!(
+                                                            "[gossip-rx] no notification for {}: muted {pattern:?}",
+                                                            &post.id
+                                                        );
+                                                        false
+                                                    }
+                                                    _ => true,
+                                                };
+                                            if notify {
                                                 if parse_mentions(&post.content).contains(&my_id) {
                                                     let _ = storage.insert_notification(
                                                         "mention",
@@ -278,7 +350,7 @@ impl FeedManager {
                                                 }
                                                 if post.quote_of_author.as_deref() == Some(&my_id) {
                                                     let _ = storage.insert_notification(
-                                                        "quote",
+                                                        post.kind().as_str(),
                                                         &post.author,
                                                         post.quote_of.as_deref(),
                                                         Some(&post.id),
@@ -330,22 +402,71 @@ impl FeedManager {
                                         }
                                     }
                                 }
-                                Ok(GossipMessage::ProfileUpdate(profile)) => {
-                                    if let Err(reason) = validate_profile(&profile) {
+                                Ok(GossipMessage::EditPost(edit)) => {
+                                    if edit.author == pk {
+                                        if let Err(reason) = validate_post_edit(&edit) {
+                                            log::error!(
+                                                "[gossip-rx] rejected edit {} from {}: {reason}",
+                                                &edit.id,
+                                                short_id(&pk)
+                                            );
+                                        } else if let Err(reason) =
+                                            verify_post_edit_signature(&edit)
+                                        {
+                                            log::error!(
+                                                "[gossip-rx] rejected edit {} from {} (bad sig): {reason}",
+                                                &edit.id,
+                                                short_id(&pk)
+                                            );
+                                        } else if storage.is_hidden(&pk).unwrap_or(false) {
+                                            log::info!(
+                                                "[gossip-rx] skipping edit from muted/blocked {}",
+                                                short_id(&pk)
+                                            );
+                                        } else {
+                                            log::info!(
+                                                "[gossip-rx] edit of post {} from {} (sig verified)",
+                                                short_id(&edit.post_id),
+                                                short_id(&pk)
+                                            );
+                                            match storage.insert_post_edit(&edit) {
+                                                Ok(true) => {
+                                                    let _ = app_handle.emit("feed-updated", ());
+                                                }
+                                                Ok(false) => {}
+                                                Err(e) => log::error!(
+                                                    "[gossip-rx] failed to store edit: {e}"
+                                                ),
+                                            }
+                                        }
+                                    }
+                                }
+                                Ok(GossipMessage::ProfileUpdate(mut profile)) => {
+                                    if let Err(reason) = validate_profile(&profile)
+                                        .and_then(|_| check_profile(&mut profile, &pk))
+                                    {
                                         log::error!(
                                             "[gossip-rx] rejected profile from {}: {reason}",
                                             short_id(&pk)
                                         );
                                     } else {
-                                        log::info!(
-                                            "[gossip-rx] profile update from {}: {}",
-                                            short_id(&pk),
-                                            profile.display_name
-                                        );
-                                        if let Err(e) = storage.save_profile(&pk, &profile) {
-                                            log::error!("[gossip-rx] failed to store profile: {e}");
+                                        match storage.save_profile(&pk, &profile) {
+                                            Ok(true) => {
+                                                log::info!(
+                                                    "[gossip-rx] profile update from {}: {}",
+                                                    short_id(&pk),
+                                                    profile.display_name
+                                                );
+                                                let _ = app_handle.emit("profile-updated", &pk);
+                                            }
+                                            Ok(false) => log::info!(
+                                                "[gossip-rx] ignored older profile from {}",
+                                                short_id(&pk)
+                                            ),
+                                            Err(e) => log::error!(
+                                                "[gossip-rx] failed to store profile: {e}"
+                                            ),
                                         }
-                                        let _ = app_handle.emit("profile-updated", &pk);
                                     }
                                 }
                                 Ok(GossipMessage::NewInteraction(interaction)) => {
@@ -385,7 +506,7 @@ impl FeedManager {
                                                 && interaction.author != my_id
                                             {
                                                 let _ = storage.insert_notification(
-                                                    "like",
+                                                    interaction.kind.notification_kind(),
                                                     &interaction.author,
                                                     Some(&interaction.target_post_id),
                                                     None,
@@ -416,11 +537,26 @@ impl FeedManager {
                                         );
                                     }
                                 }
+                                Ok(GossipMessage::Sealed(sealed)) => {
+                                    log::info!(
+                                        "[gossip-rx] ignored sealed message from {} (expected {})",
+                                        short_id(&sealed.author),
+                                        short_id(&pk)
+                                    );
+                                }
                                 Err(e) => {
                                     log::error!("[gossip-rx] failed to parse message: {e}");
                                 }
                             }
                         }
+                        Event::NeighborUp(endpoint_id) => {
+                            log::info!(
+                                "[gossip-rx] neighbor up on {}: {}",
+                                short_id(&pk),
+                                short_id(&endpoint_id.to_string())
+                            );
+                            let _ = outbox_flush_tx.send(endpoint_id.to_string());
+                        }
                         other => {
                             log::info!("[gossip-rx] event from {}: {other:?}", short_id(&pk));
                         }
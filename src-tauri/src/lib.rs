@@ -2,11 +2,16 @@ mod commands;
 mod constants;
 mod crypto;
 mod dm;
+mod follow;
 mod gossip;
+mod link_preview;
+mod post_fetch;
+mod ranking;
 mod setup;
 mod state;
 mod storage;
 mod sync;
+mod word_filter;
 
 use commands::*;
 
@@ -55,9 +60,27 @@ pub fn run() {
             get_node_id,
             get_my_profile,
             save_my_profile,
+            pin_post,
+            unpin_post,
             get_remote_profile,
             create_post,
             delete_post,
+            create_thread,
+            get_thread,
+            get_conversation,
+            fetch_post,
+            get_link_previews,
+            set_link_previews,
+            edit_post,
+            get_post_history,
+            save_draft,
+            get_draft,
+            get_drafts,
+            delete_draft,
+            schedule_post,
+            get_scheduled_posts,
+            cancel_scheduled_post,
+            reschedule_post,
             get_feed,
             get_notifications,
             get_unread_notification_count,
@@ -68,6 +91,10 @@ pub fn run() {
             fetch_older_posts,
             like_post,
             unlike_post,
+            react_to_post,
+            unreact_to_post,
+            vote_on_poll,
+            get_poll_results,
             repost,
             unrepost,
             get_post_counts,
@@ -78,17 +105,50 @@ pub fn run() {
             update_follow_alias,
             get_follows,
             get_followers,
+            request_follow,
+            get_follow_request_status,
+            get_follow_requests,
+            approve_follow_request,
+            deny_follow_request,
+            get_approved_followers,
+            remove_approved_follower,
+            get_audiences,
+            create_audience,
+            update_audience,
+            delete_audience,
+            get_lists,
+            create_list,
+            update_list,
+            delete_list,
+            get_custom_feeds,
+            create_custom_feed,
+            update_custom_feed,
+            delete_custom_feed,
+            get_custom_feed_posts,
+            get_ranked_feed,
             add_blob,
             fetch_blob,
             add_blob_bytes,
             fetch_blob_bytes,
+            add_dm_blob_bytes,
+            fetch_dm_blob_bytes,
             get_node_status,
             send_dm,
             get_conversations,
+            get_message_requests,
+            accept_message_request,
+            decline_message_request,
+            block_message_request,
+            get_dm_policy,
+            set_dm_policy,
             get_dm_messages,
             mark_dm_read,
             delete_dm_message,
             flush_dm_outbox,
+            retry_dm,
+            get_safety_number,
+            verify_safety_number,
+            set_dm_verified,
             get_unread_dm_count,
             send_dm_signal,
             toggle_bookmark,
@@ -96,11 +156,20 @@ pub fn run() {
             mute_user,
             unmute_user,
             is_muted,
+            get_user_mutes,
             get_muted_pubkeys,
+            get_mutes,
+            get_muted_words,
+            add_muted_word,
+            remove_muted_word,
             block_user,
             unblock_user,
             is_blocked,
             get_blocked_pubkeys,
+            get_sensitive_mode,
+            set_sensitive_mode,
+            get_sensitive_override,
+            set_sensitive_override,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
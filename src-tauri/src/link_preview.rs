@@ -0,0 +1,398 @@
+use anyhow::{anyhow, bail};
+use iroh::Endpoint;
+use iroh_blobs::{store::fs::FsStore, ticket::BlobTicket};
+use iroh_social_types::{
+    LinkPreview, MAX_LINK_DESCRIPTION_LEN, MAX_LINK_TITLE_LEN, MAX_LINK_URL_LEN, MediaAttachment,
+};
+use reqwest::Url;
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::constants::{
+    LINK_PREVIEW_MAX_IMAGE_BYTES, LINK_PREVIEW_MAX_PAGE_BYTES, LINK_PREVIEW_PAGE_TIMEOUT,
+    LINK_PREVIEW_THUMBNAIL_TIMEOUT,
+};
+
+/// A fetched response body, cut off at the requested size.
+pub struct FetchedResource {
+    pub content_type: String,
+    pub body: Vec<u8>,
+    pub truncated: bool,
+}
+
+pub type FetchFuture<'a> =
+    Pin<Box<dyn Future<Output = anyhow::Result<FetchedResource>> + Send + 'a>>;
+
+/// Fetches pages and thumbnails for link previews. Kept behind a trait so
+/// the network side can be swapped out. Callers bound each fetch with their
+/// own timeout.
+pub trait LinkFetcher: Send + Sync {
+    fn fetch<'a>(&'a self, url: &'a str, max_bytes: usize) -> FetchFuture<'a>;
+}
+
+pub struct HttpFetcher {
+    client: reqwest::Client,
+}
+
+impl HttpFetcher {
+    pub fn new() -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::limited(5))
+            .user_agent(concat!("iroh-social/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Self { client })
+    }
+}
+
+impl LinkFetcher for HttpFetcher {
+    fn fetch<'a>(&'a self, url: &'a str, max_bytes: usize) -> FetchFuture<'a> {
+        Box::pin(async move {
+            let mut resp = self.client.get(url).send().await?.error_for_status()?;
+            let content_type = resp
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            let mut body = Vec::new();
+            let mut truncated = false;
+            while let Some(chunk) = resp.chunk().await? {
+                let room = max_bytes - body.len();
+                if chunk.len() > room {
+                    body.extend_from_slice(&chunk[..room]);
+                    truncated = true;
+                    break;
+                }
+                body.extend_from_slice(&chunk);
+            }
+            Ok(FetchedResource {
+                content_type,
+                body,
+                truncated,
+            })
+        })
+    }
+}
+
+/// Title, description and absolute thumbnail URL read from a page's
+/// OpenGraph/Twitter meta tags, falling back to `<title>`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PageMeta {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+}
+
+/// The first http(s) URL in post content, without trailing punctuation.
+pub fn first_url(content: &str) -> Option<&str> {
+    content
+        .split_whitespace()
+        .filter_map(|word| {
+            let start = word.find("https://").or_else(|| word.find("http://"))?;
+            let url = word[start..].trim_end_matches(|c: char| ".,;:!?)]}'\"".contains(c));
+            (url.len() <= MAX_LINK_URL_LEN && Url::parse(url).is_ok()).then_some(url)
+        })
+        .next()
+}
+
+fn decode_entities(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// Value of attribute `name` in the tag `tag` (lowercased copy in `lower`).
+fn attr(tag: &str, lower: &str, name: &str) -> Option<String> {
+    let mut from = 0;
+    while let Some(pos) = lower[from..].find(name) {
+        let start = from + pos;
+        from = start + name.len();
+        let preceded = lower[..start].ends_with(|c: char| c.is_ascii_whitespace());
+        let rest = lower[from..].trim_start();
+        if !preceded || !rest.starts_with('=') {
+            continue;
+        }
+        let value_start = tag.len() - rest.len() + 1;
+        let value = tag[value_start..].trim_start();
+        let (quote, value) = match value.chars().next() {
+            Some(q @ ('"' | '\'')) => (Some(q), &value[1..]),
+            _ => (None, value),
+        };
+        let end = match quote {
+            Some(q) => value.find(q),
+            None => value.find(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/'),
+        }
+        .unwrap_or(value.len());
+        return Some(decode_entities(value[..end].trim()));
+    }
+    None
+}
+
+pub fn parse_page_meta(html: &str) -> PageMeta {
+    let lower = html.to_ascii_lowercase();
+    let mut og = PageMeta::default();
+    let mut fallback = PageMeta::default();
+
+    let mut from = 0;
+    while let Some(pos) = lower[from..].find("<meta") {
+        let start = from + pos;
+        let end = lower[start..].find('>').map_or(lower.len(), |e| start + e);
+        from = end;
+        let (tag, tag_lower) = (&html[start..end], &lower[start..end]);
+        let Some(key) = attr(tag, tag_lower, "property").or_else(|| attr(tag, tag_lower, "name"))
+        else {
+            continue;
+        };
+        let Some(content) = attr(tag, tag_lower, "content").filter(|c| !c.is_empty()) else {
+            continue;
+        };
+        let slot = match key.to_ascii_lowercase().as_str() {
+            "og:title" => &mut og.title,
+            "twitter:title" => &mut fallback.title,
+            "og:description" => &mut og.description,
+            "twitter:description" | "description" => &mut fallback.description,
+            "og:image" | "og:image:url" => &mut og.image,
+            "twitter:image" => &mut fallback.image,
+            _ => continue,
+        };
+        slot.get_or_insert(content);
+    }
+
+    if fallback.title.is_none()
+        && let Some(start) = lower.find("<title")
+        && let Some(open_end) = lower[start..].find('>')
+    {
+        let text_start = start + open_end + 1;
+        let text_end = lower[text_start..]
+            .find("</title")
+            .map_or(lower.len(), |e| text_start + e);
+        let title = decode_entities(html[text_start..text_end].trim());
+        if !title.is_empty() {
+            fallback.title = Some(title);
+        }
+    }
+
+    PageMeta {
+        title: og.title.or(fallback.title),
+        description: og.description.or(fallback.description),
+        image: og.image.or(fallback.image),
+    }
+}
+
+fn truncate(s: String, max: usize) -> String {
+    if s.len() <= max {
+        return s;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    s[..end].to_string()
+}
+
+/// Fetch `url` within `LINK_PREVIEW_PAGE_TIMEOUT` and read its preview
+/// metadata. `None` when the page is not HTML or has no title. The thumbnail
+/// URL is made absolute.
+pub async fn fetch_page_meta(
+    fetcher: &dyn LinkFetcher,
+    url: &str,
+) -> anyhow::Result<Option<PageMeta>> {
+    let page = tokio::time::timeout(
+        LINK_PREVIEW_PAGE_TIMEOUT,
+        fetcher.fetch(url, LINK_PREVIEW_MAX_PAGE_BYTES),
+    )
+    .await
+    .map_err(|_| anyhow!("page timed out"))??;
+    if !page.content_type.starts_with("text/html")
+        && !page.content_type.starts_with("application/xhtml")
+    {
+        return Ok(None);
+    }
+    let mut meta = parse_page_meta(&String::from_utf8_lossy(&page.body));
+    if meta.title.is_none() {
+        return Ok(None);
+    }
+    let base = Url::parse(url)?;
+    meta.image = meta
+        .image
+        .and_then(|src| base.join(&src).ok())
+        .filter(|u| matches!(u.scheme(), "http" | "https"))
+        .map(String::from);
+    Ok(Some(meta))
+}
+
+/// Fetch a thumbnail within `LINK_PREVIEW_THUMBNAIL_TIMEOUT` and add it to
+/// the blob store so readers get it from us. Only a thumbnail that arrived in
+/// time is stored.
+async fn store_thumbnail(
+    fetcher: &dyn LinkFetcher,
+    store: &FsStore,
+    endpoint: &Endpoint,
+    url: &str,
+) -> anyhow::Result<MediaAttachment> {
+    let image = tokio::time::timeout(
+        LINK_PREVIEW_THUMBNAIL_TIMEOUT,
+        fetcher.fetch(url, LINK_PREVIEW_MAX_IMAGE_BYTES),
+    )
+    .await
+    .map_err(|_| anyhow!("thumbnail timed out"))??;
+    if image.truncated {
+        bail!("thumbnail larger than {LINK_PREVIEW_MAX_IMAGE_BYTES} bytes");
+    }
+    let mime_type = image
+        .content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    if !mime_type.starts_with("image/") {
+        bail!("thumbnail is not an image: {mime_type}");
+    }
+    let tag = store.add_slice(&image.body).await?;
+    let ticket = BlobTicket::new(endpoint.addr(), tag.hash, tag.format);
+    let filename = Url::parse(url)
+        .ok()
+        .and_then(|u| u.path_segments()?.next_back().map(str::to_string))
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "thumbnail".to_string());
+    Ok(MediaAttachment {
+        hash: tag.hash.to_string(),
+        ticket: ticket.to_string(),
+        mime_type,
+        filename,
+        size: image.body.len() as u64,
+        key: None,
+    })
+}
+
+/// Build a preview of the first link in `content`, if it has one and the
+/// page can be read. A thumbnail that fails to load is left out.
+pub async fn generate_link_preview(
+    fetcher: &dyn LinkFetcher,
+    store: &FsStore,
+    endpoint: &Endpoint,
+    content: &str,
+) -> anyhow::Result<Option<LinkPreview>> {
+    let Some(url) = first_url(content) else {
+        return Ok(None);
+    };
+    let Some(meta) = fetch_page_meta(fetcher, url).await? else {
+        return Ok(None);
+    };
+    let image = match &meta.image {
+        Some(src) => match store_thumbnail(fetcher, store, endpoint, src).await {
+            Ok(image) => Some(image),
+            Err(e) => {
+                log::warn!("[link-preview] no thumbnail for {url}: {e}");
+                None
+            }
+        },
+        None => None,
+    };
+    Ok(Some(LinkPreview {
+        url: url.to_string(),
+        title: truncate(meta.title.unwrap_or_default(), MAX_LINK_TITLE_LEN),
+        description: meta
+            .description
+            .map(|d| truncate(d, MAX_LINK_DESCRIPTION_LEN)),
+        image,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const PAGE: &str = r#"<!doctype html><html><head>
+        <title>Fallback title</title>
+        <meta property="og:title" content="Tom &amp; Jerry">
+        <meta name="description" content='A cat and a mouse'>
+        <meta property="og:image" content="/thumb.png" />
+        </head><body>hi</body></html>"#;
+
+    /// Serve `PAGE` at any path except `/thumb.png`, which returns PNG bytes.
+    async fn serve_stand_in() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut sock, _)) = listener.accept().await else {
+                    return;
+                };
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 4096];
+                    let n = sock.read(&mut buf).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buf[..n]);
+                    let (content_type, body): (&str, &[u8]) =
+                        if request.starts_with("GET /thumb.png") {
+                            ("image/png", b"\x89PNG\r\n\x1a\nfake")
+                        } else {
+                            ("text/html; charset=utf-8", PAGE.as_bytes())
+                        };
+                    let head = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        body.len()
+                    );
+                    let _ = sock.write_all(head.as_bytes()).await;
+                    let _ = sock.write_all(body).await;
+                });
+            }
+        });
+        format!("http://{addr}")
+    }
+
+    #[test]
+    fn test_first_url() {
+        assert_eq!(
+            first_url("look (https://example.com/a?b=1)."),
+            Some("https://example.com/a?b=1")
+        );
+        assert_eq!(first_url("no links here"), None);
+        assert_eq!(first_url("ftp://example.com"), None);
+    }
+
+    #[test]
+    fn test_parse_page_meta_prefers_opengraph() {
+        let meta = parse_page_meta(PAGE);
+        assert_eq!(meta.title.as_deref(), Some("Tom & Jerry"));
+        assert_eq!(meta.description.as_deref(), Some("A cat and a mouse"));
+        assert_eq!(meta.image.as_deref(), Some("/thumb.png"));
+
+        let plain = parse_page_meta("<html><head><TITLE> Plain </TITLE></head></html>");
+        assert_eq!(plain.title.as_deref(), Some("Plain"));
+        assert_eq!(plain.image, None);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_page_meta_from_local_stand_in() {
+        let base = serve_stand_in().await;
+        let fetcher = HttpFetcher::new().unwrap();
+
+        let meta = fetch_page_meta(&fetcher, &format!("{base}/post"))
+            .await
+            .unwrap()
+            .expect("page has a title");
+        assert_eq!(meta.title.as_deref(), Some("Tom & Jerry"));
+        let image = meta.image.expect("thumbnail url is resolved");
+        assert_eq!(image, format!("{base}/thumb.png"));
+
+        let thumb = fetcher
+            .fetch(&image, LINK_PREVIEW_MAX_IMAGE_BYTES)
+            .await
+            .unwrap();
+        assert_eq!(thumb.content_type, "image/png");
+        assert!(!thumb.truncated);
+
+        let cut = fetcher.fetch(&base, 16).await.unwrap();
+        assert_eq!(cut.body.len(), 16);
+        assert!(cut.truncated);
+    }
+}
@@ -0,0 +1,442 @@
+use crate::storage::{PostView, Storage};
+use iroh::{
+    Endpoint, EndpointAddr, EndpointId,
+    endpoint::Connection,
+    protocol::{AcceptError, ProtocolHandler},
+};
+use iroh_social_types::{
+    AudiencePost, MAX_POST_FETCH_IDS, POST_FETCH_ALPN, PostFetchRequest, PostFetchResponse,
+    now_millis, short_id, validate_audience_post, validate_post, validate_post_edit,
+    verify_post_edit_signature, verify_post_signature,
+};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::constants::{POST_FETCH_FALLBACK_PEERS, POST_FETCH_TIMEOUT};
+
+const MAX_RESPONSE_SIZE: usize = 10_000_000;
+
+#[derive(Debug, Clone)]
+pub struct PostFetchHandler {
+    storage: Arc<Storage>,
+    node_id: String,
+}
+
+impl PostFetchHandler {
+    pub fn new(storage: Arc<Storage>, node_id: String) -> Self {
+        Self { storage, node_id }
+    }
+
+    /// Posts by a private profile are only shared by its owner, with
+    /// approved followers, as with sync. Audience posts are only shared by
+    /// their author, with the audience.
+    fn may_share(&self, post: &PostView, remote: &str) -> bool {
+        if post.audience.is_some()
+            && (post.author != self.node_id
+                || !self
+                    .storage
+                    .is_in_post_audience(&post.id, remote)
+                    .unwrap_or(false))
+        {
+            return false;
+        }
+        if !self
+            .storage
+            .is_private_profile(&post.author)
+            .unwrap_or(false)
+        {
+            return true;
+        }
+        post.author == self.node_id && self.storage.is_approved_follower(remote).unwrap_or(false)
+    }
+
+    /// The posts in `ids` that we hold and may share with `remote`, with
+    /// their edits. Expired posts are left out.
+    fn build_response(&self, ids: &[String], remote: &str) -> PostFetchResponse {
+        let mut resp = PostFetchResponse::default();
+        for id in ids.iter().take(MAX_POST_FETCH_IDS) {
+            let Ok(Some(post)) = self.storage.get_post_by_id(id) else {
+                continue;
+            };
+            if post.expires_at.is_some_and(|t| t <= now_millis()) || !self.may_share(&post, remote)
+            {
+                continue;
+            }
+            if let Ok(edits) = self.storage.get_post_edits(&post.id, &post.author) {
+                resp.edits.extend(edits);
+            }
+            match post.audience {
+                Some(audience) => resp.audience_posts.push(AudiencePost {
+                    audience,
+                    post: post.post,
+                }),
+                None => resp.posts.push(post.post),
+            }
+        }
+        resp
+    }
+}
+
+impl ProtocolHandler for PostFetchHandler {
+    async fn accept(&self, conn: Connection) -> Result<(), AcceptError> {
+        let remote = conn.remote_id().to_string();
+
+        if self.storage.is_blocked(&remote).unwrap_or(false) {
+            log::warn!("[post-fetch] rejecting blocked peer {}", short_id(&remote));
+            return Err(AcceptError::from_err(std::io::Error::other("blocked")));
+        }
+
+        let (mut send, mut recv) = conn.accept_bi().await?;
+        let req_bytes = recv
+            .read_to_end(65_536)
+            .await
+            .map_err(AcceptError::from_err)?;
+        let req: PostFetchRequest =
+            serde_json::from_slice(&req_bytes).map_err(AcceptError::from_err)?;
+
+        let resp = self.build_response(&req.ids, &remote);
+        log::info!(
+            "[post-fetch] serving {}/{} posts to {}",
+            resp.posts.len() + resp.audience_posts.len(),
+            req.ids.len(),
+            short_id(&remote)
+        );
+
+        let resp_bytes = serde_json::to_vec(&resp).map_err(AcceptError::from_err)?;
+        send.write_all(&resp_bytes)
+            .await
+            .map_err(AcceptError::from_err)?;
+        send.finish().map_err(AcceptError::from_err)?;
+        conn.closed().await;
+        Ok(())
+    }
+}
+
+/// Client: ask `target` for the posts in `ids`.
+pub async fn fetch_posts_from_peer(
+    endpoint: &Endpoint,
+    target: EndpointId,
+    ids: &[String],
+) -> anyhow::Result<PostFetchResponse> {
+    let conn = endpoint
+        .connect(EndpointAddr::from(target), POST_FETCH_ALPN)
+        .await?;
+    let (mut send, mut recv) = conn.open_bi().await?;
+    let req = PostFetchRequest { ids: ids.to_vec() };
+    send.write_all(&serde_json::to_vec(&req)?).await?;
+    send.finish()?;
+    let resp_bytes = recv.read_to_end(MAX_RESPONSE_SIZE).await?;
+    conn.close(0u32.into(), b"done");
+    Ok(serde_json::from_slice(&resp_bytes)?)
+}
+
+/// Who to ask for missing posts, in order: `author`, then up to
+/// `POST_FETCH_FALLBACK_PEERS` of the peers we follow, never ourselves.
+fn fetch_candidates(storage: &Storage, my_id: &str, author: Option<&str>) -> Vec<String> {
+    let mut peers: Vec<String> = author
+        .filter(|a| *a != my_id)
+        .map(str::to_string)
+        .into_iter()
+        .collect();
+    let fallbacks: Vec<String> = storage
+        .get_follows()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|f| f.pubkey)
+        .filter(|p| p != my_id && !peers.contains(p))
+        .take(POST_FETCH_FALLBACK_PEERS)
+        .collect();
+    peers.extend(fallbacks);
+    peers
+}
+
+/// Store the posts in `resp` from `peer` that are still `wanted` and carry a
+/// valid signature, taking them out of `wanted`, and any edits of posts we
+/// hold. Returns how many posts were stored.
+fn store_response(
+    storage: &Storage,
+    peer: &str,
+    resp: PostFetchResponse,
+    wanted: &mut HashSet<String>,
+) -> usize {
+    let mut stored = 0;
+    let audience_posts = resp
+        .audience_posts
+        .into_iter()
+        .filter(|a| a.post.author == peer && validate_audience_post(a).is_ok())
+        .map(|a| (a.post, Some(a.audience)));
+    for (post, audience) in resp
+        .posts
+        .into_iter()
+        .map(|p| (p, None))
+        .chain(audience_posts)
+    {
+        if !wanted.contains(&post.id) {
+            continue;
+        }
+        if let Err(reason) = validate_post(&post).and_then(|_| verify_post_signature(&post)) {
+            log::error!(
+                "[post-fetch] rejected post {} from {}: {reason}",
+                short_id(&post.id),
+                short_id(peer)
+            );
+            continue;
+        }
+        let inserted = match &audience {
+            Some(audience) => storage.insert_audience_post(&post, audience),
+            None => storage.insert_post(&post),
+        };
+        if inserted.is_ok() {
+            wanted.remove(&post.id);
+            stored += 1;
+        }
+    }
+    for edit in resp.edits {
+        let held = matches!(
+            storage.get_post_by_id(&edit.post_id),
+            Ok(Some(ref p)) if p.author == edit.author
+        );
+        if held && validate_post_edit(&edit).is_ok() && verify_post_edit_signature(&edit).is_ok() {
+            let _ = storage.insert_post_edit(&edit);
+        }
+    }
+    stored
+}
+
+/// Fetch posts we don't hold, asking `author` first and then the peers we
+/// follow, until every ID is found or the candidates run out. Only posts that
+/// were asked for and carry a valid signature are stored. Returns how many
+/// new posts were stored.
+pub async fn fetch_missing_posts(
+    endpoint: &Endpoint,
+    storage: &Storage,
+    ids: &[String],
+    author: Option<&str>,
+) -> usize {
+    let my_id = endpoint.id().to_string();
+    let mut wanted: HashSet<String> = ids
+        .iter()
+        .filter(|id| !matches!(storage.get_post_by_id(id), Ok(Some(_))))
+        .take(MAX_POST_FETCH_IDS)
+        .cloned()
+        .collect();
+    if wanted.is_empty() {
+        return 0;
+    }
+
+    let mut stored = 0;
+    for peer in fetch_candidates(storage, &my_id, author) {
+        if wanted.is_empty() {
+            break;
+        }
+        let Ok(target) = peer.parse::<EndpointId>() else {
+            continue;
+        };
+        let ask: Vec<String> = wanted.iter().cloned().collect();
+        let resp = match tokio::time::timeout(
+            POST_FETCH_TIMEOUT,
+            fetch_posts_from_peer(endpoint, target, &ask),
+        )
+        .await
+        {
+            Ok(Ok(resp)) => resp,
+            Ok(Err(e)) => {
+                log::error!("[post-fetch] request to {} failed: {e}", short_id(&peer));
+                continue;
+            }
+            Err(_) => {
+                log::error!("[post-fetch] request to {} timed out", short_id(&peer));
+                continue;
+            }
+        };
+        stored += store_response(storage, &peer, resp, &mut wanted);
+        log::info!(
+            "[post-fetch] got {stored}/{} posts after asking {}",
+            ids.len(),
+            short_id(&peer)
+        );
+    }
+    stored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_util::post;
+    use iroh::SecretKey;
+    use iroh_social_types::{Audience, FollowEntry, Post, PostEdit, Profile, sign_post};
+
+    fn follow(storage: &Storage, pubkey: &str, followed_at: u64) {
+        storage
+            .follow(&FollowEntry {
+                pubkey: pubkey.to_string(),
+                alias: None,
+                followed_at,
+            })
+            .unwrap();
+    }
+
+    fn signed_post(secret: &SecretKey, id: &str) -> Post {
+        let mut post = post(id, &secret.public().to_string(), now_millis());
+        sign_post(&mut post, secret);
+        post
+    }
+
+    #[test]
+    fn test_fetch_candidates_author_then_capped_follows() {
+        let storage = Storage::open(":memory:").unwrap();
+        for (i, peer) in ["f1", "f2", "f3", "f4", "f5"].iter().enumerate() {
+            follow(&storage, peer, i as u64);
+        }
+        follow(&storage, "author", 10);
+
+        // Newest follows first, the author not asked twice
+        assert_eq!(
+            fetch_candidates(&storage, "me", Some("author")),
+            ["author", "f5", "f4", "f3"]
+        );
+        assert_eq!(
+            fetch_candidates(&storage, "me", None),
+            ["author", "f5", "f4"]
+        );
+        assert_eq!(
+            fetch_candidates(&storage, "me", Some("stranger")).len(),
+            1 + POST_FETCH_FALLBACK_PEERS
+        );
+        // Never ourselves
+        assert_eq!(
+            fetch_candidates(&storage, "author", Some("author")),
+            ["f5", "f4", "f3"]
+        );
+    }
+
+    #[test]
+    fn test_not_found_falls_back_to_next_peer() {
+        let storage = Storage::open(":memory:").unwrap();
+        let author = SecretKey::from_bytes(&[1u8; 32]);
+        let post = signed_post(&author, "wanted");
+        let mut wanted: HashSet<String> = ["wanted".to_string()].into();
+
+        // The author doesn't hold it any more
+        let none = PostFetchResponse::default();
+        assert_eq!(store_response(&storage, "author", none, &mut wanted), 0);
+        assert!(wanted.contains("wanted"));
+
+        // A forged copy is rejected and the post is still wanted
+        let mut forged = post.clone();
+        forged.content = "forged".to_string();
+        let resp = PostFetchResponse {
+            posts: vec![forged],
+            ..Default::default()
+        };
+        assert_eq!(store_response(&storage, "f1", resp, &mut wanted), 0);
+        assert!(wanted.contains("wanted"));
+
+        // The next peer has it, plus a post nobody asked for
+        let resp = PostFetchResponse {
+            posts: vec![post, signed_post(&author, "extra")],
+            ..Default::default()
+        };
+        assert_eq!(store_response(&storage, "f2", resp, &mut wanted), 1);
+        assert!(wanted.is_empty());
+        let stored = storage.get_post_by_id("wanted").unwrap().unwrap();
+        assert_eq!(stored.content, "post wanted");
+        assert!(storage.get_post_by_id("extra").unwrap().is_none());
+    }
+
+    fn private_profile() -> Profile {
+        Profile {
+            display_name: "private".to_string(),
+            bio: String::new(),
+            avatar_hash: None,
+            avatar_ticket: None,
+            is_private: true,
+            pinned_posts: vec![],
+            updated_at: None,
+            signature: None,
+        }
+    }
+
+    #[test]
+    fn test_may_share() {
+        let storage = Arc::new(Storage::open(":memory:").unwrap());
+        let handler = PostFetchHandler::new(storage.clone(), "me".to_string());
+        let get = |id: &str| storage.get_post_by_id(id).unwrap().unwrap();
+
+        storage.insert_post(&post("public", "alice", 1)).unwrap();
+        assert!(handler.may_share(&get("public"), "bob"));
+
+        // Our audience post goes to its members only
+        storage
+            .create_audience(&Audience {
+                id: "a1".to_string(),
+                name: "close".to_string(),
+                members: vec!["bob".to_string()],
+                created_at: 1,
+            })
+            .unwrap();
+        storage
+            .insert_audience_post(&post("mine", "me", 1), "close")
+            .unwrap();
+        storage.set_post_audience("mine", "a1").unwrap();
+        assert!(handler.may_share(&get("mine"), "bob"));
+        assert!(!handler.may_share(&get("mine"), "carol"));
+
+        // An audience post shared with us is never passed on
+        storage
+            .insert_audience_post(&post("theirs", "alice", 1), "friends")
+            .unwrap();
+        assert!(!handler.may_share(&get("theirs"), "bob"));
+
+        // A private author's posts are only served by the author
+        storage.save_profile("alice", &private_profile()).unwrap();
+        assert!(!handler.may_share(&get("public"), "bob"));
+
+        storage.insert_post(&post("own", "me", 1)).unwrap();
+        storage.save_profile("me", &private_profile()).unwrap();
+        storage.approve_follower("bob", 1).unwrap();
+        assert!(handler.may_share(&get("own"), "bob"));
+        assert!(!handler.may_share(&get("own"), "carol"));
+    }
+
+    #[test]
+    fn test_response_skips_expired_and_carries_edits() {
+        let storage = Arc::new(Storage::open(":memory:").unwrap());
+        let handler = PostFetchHandler::new(storage.clone(), "me".to_string());
+        storage.insert_post(&post("live", "alice", 1)).unwrap();
+        storage
+            .insert_post(&Post {
+                expires_at: Some(1),
+                ..post("expired", "alice", 1)
+            })
+            .unwrap();
+        storage
+            .insert_post_edit(&PostEdit {
+                id: "e1".to_string(),
+                post_id: "live".to_string(),
+                author: "alice".to_string(),
+                content: "edited".to_string(),
+                media: vec![],
+                timestamp: 2,
+                signature: String::new(),
+            })
+            .unwrap();
+
+        let ids = ["live", "expired", "unknown"].map(String::from);
+        let resp = handler.build_response(&ids, "bob");
+        let served: Vec<&str> = resp.posts.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(served, ["live"]);
+        // The original is served as signed, with the edit alongside it
+        assert_eq!(resp.posts[0].content, "post live");
+        assert_eq!(resp.edits.len(), 1);
+        assert!(resp.audience_posts.is_empty());
+
+        let many: Vec<String> = (0..MAX_POST_FETCH_IDS + 1)
+            .map(|_| "live".to_string())
+            .collect();
+        assert_eq!(
+            handler.build_response(&many, "bob").posts.len(),
+            MAX_POST_FETCH_IDS
+        );
+    }
+}
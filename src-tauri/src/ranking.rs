@@ -0,0 +1,218 @@
+use serde::Serialize;
+
+use crate::storage::{PostView, RankSignals};
+
+// Points per signal. Every post starts from one point so that, without any
+// signals, the ranked feed falls back to newest first.
+const BASE_POINTS: f64 = 1.0;
+const FOLLOWED_LIKE_POINTS: f64 = 1.0;
+const FOLLOWED_REPOST_POINTS: f64 = 2.0;
+const REPLY_POINTS: f64 = 0.5;
+/// Author affinity grows with the log of our past likes and replies, so a
+/// single favourite author can't crowd out everyone else.
+const AUTHOR_LIKE_POINTS: f64 = 1.0;
+const AUTHOR_REPLY_POINTS: f64 = 2.0;
+/// A post's score halves every this many hours.
+const HALF_LIFE_HOURS: f64 = 12.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RankReasonKind {
+    FollowedLikes,
+    FollowedReposts,
+    Replies,
+    LikedAuthor,
+    RepliedToAuthor,
+}
+
+/// One signal that raised a post's score.
+#[derive(Debug, Clone, Serialize)]
+pub struct RankReason {
+    pub kind: RankReasonKind,
+    pub count: u32,
+    pub points: f64,
+}
+
+/// A post's score and how it was reached: `value` is the points, base
+/// included, times `decay`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Score {
+    pub value: f64,
+    /// Recency multiplier, 1.0 for a brand new post.
+    pub decay: f64,
+    /// Contributing signals, largest first.
+    pub reasons: Vec<RankReason>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RankedPost {
+    pub post: PostView,
+    pub score: Score,
+}
+
+pub fn score(signals: &RankSignals, now: u64) -> Score {
+    let mut reasons = Vec::new();
+    let mut add = |kind, count: u32, points: f64| {
+        if count > 0 {
+            reasons.push(RankReason {
+                kind,
+                count,
+                points,
+            });
+        }
+    };
+    add(
+        RankReasonKind::FollowedLikes,
+        signals.followed_likes,
+        FOLLOWED_LIKE_POINTS * signals.followed_likes as f64,
+    );
+    add(
+        RankReasonKind::FollowedReposts,
+        signals.followed_reposts,
+        FOLLOWED_REPOST_POINTS * signals.followed_reposts as f64,
+    );
+    add(
+        RankReasonKind::Replies,
+        signals.replies,
+        REPLY_POINTS * signals.replies as f64,
+    );
+    add(
+        RankReasonKind::LikedAuthor,
+        signals.author_likes,
+        AUTHOR_LIKE_POINTS * (signals.author_likes as f64).ln_1p(),
+    );
+    add(
+        RankReasonKind::RepliedToAuthor,
+        signals.author_replies,
+        AUTHOR_REPLY_POINTS * (signals.author_replies as f64).ln_1p(),
+    );
+    reasons.sort_by(|a, b| b.points.total_cmp(&a.points));
+
+    let age_hours = now.saturating_sub(signals.timestamp) as f64 / 3_600_000.0;
+    let decay = 0.5f64.powf(age_hours / HALF_LIFE_HOURS);
+    let points = BASE_POINTS + reasons.iter().map(|r| r.points).sum::<f64>();
+    Score {
+        value: points * decay,
+        decay,
+        reasons,
+    }
+}
+
+/// Score and order candidates, highest first. Ties go to the newer post,
+/// then the larger id, so the same data always ranks the same way.
+pub fn rank(candidates: Vec<(PostView, RankSignals)>, now: u64) -> Vec<RankedPost> {
+    let mut ranked: Vec<RankedPost> = candidates
+        .into_iter()
+        .map(|(post, signals)| RankedPost {
+            score: score(&signals, now),
+            post,
+        })
+        .collect();
+    ranked.sort_by(|a, b| {
+        b.score
+            .value
+            .total_cmp(&a.score.value)
+            .then(b.post.timestamp.cmp(&a.post.timestamp))
+            .then(b.post.id.cmp(&a.post.id))
+    });
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_util::post;
+
+    const HOUR: u64 = 3_600_000;
+    const NOW: u64 = 1_700_000_000_000;
+
+    fn candidate(id: &str, age: u64, signals: RankSignals) -> (PostView, RankSignals) {
+        let post = post(id, &format!("author-{id}"), NOW - age);
+        let signals = RankSignals {
+            timestamp: post.timestamp,
+            ..signals
+        };
+        (PostView::new(post), signals)
+    }
+
+    fn fixture() -> Vec<(PostView, RankSignals)> {
+        vec![
+            candidate("quiet-new", 0, RankSignals::default()),
+            candidate(
+                "liked-old",
+                24 * HOUR,
+                RankSignals {
+                    followed_likes: 6,
+                    followed_reposts: 1,
+                    ..Default::default()
+                },
+            ),
+            candidate(
+                "discussed",
+                2 * HOUR,
+                RankSignals {
+                    followed_likes: 1,
+                    replies: 4,
+                    ..Default::default()
+                },
+            ),
+            candidate(
+                "friend",
+                6 * HOUR,
+                RankSignals {
+                    author_likes: 10,
+                    author_replies: 3,
+                    ..Default::default()
+                },
+            ),
+            candidate("quiet-tie", 0, RankSignals::default()),
+        ]
+    }
+
+    #[test]
+    fn test_rank_fixture_order() {
+        let ids: Vec<String> = rank(fixture(), NOW)
+            .into_iter()
+            .map(|r| r.post.post.id)
+            .collect();
+        assert_eq!(
+            ids,
+            ["friend", "discussed", "liked-old", "quiet-tie", "quiet-new"]
+        );
+        let mut reversed = fixture();
+        reversed.reverse();
+        let again: Vec<String> = rank(reversed, NOW)
+            .into_iter()
+            .map(|r| r.post.post.id)
+            .collect();
+        assert_eq!(ids, again);
+    }
+
+    #[test]
+    fn test_score_explains_itself() {
+        let (_, signals) = candidate(
+            "p",
+            HALF_LIFE_HOURS as u64 * HOUR,
+            RankSignals {
+                followed_likes: 2,
+                followed_reposts: 1,
+                replies: 1,
+                ..Default::default()
+            },
+        );
+        let score = score(&signals, NOW);
+        assert!((score.decay - 0.5).abs() < 1e-9);
+        let kinds: Vec<RankReasonKind> = score.reasons.iter().map(|r| r.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                RankReasonKind::FollowedLikes,
+                RankReasonKind::FollowedReposts,
+                RankReasonKind::Replies
+            ]
+        );
+        let points = BASE_POINTS + score.reasons.iter().map(|r| r.points).sum::<f64>();
+        assert!((score.value - points * score.decay).abs() < 1e-9);
+        assert!((score.value - 2.75).abs() < 1e-9);
+    }
+}
@@ -1,14 +1,27 @@
+use crate::commands::posts::{publish_post, sweep_expired_posts};
 use crate::commands::sync::process_sync_result;
 use crate::constants::*;
 use crate::dm::DmHandler;
+use crate::follow::FollowHandler;
 use crate::gossip::FeedManager;
+use crate::link_preview::HttpFetcher;
+use crate::post_fetch::PostFetchHandler;
 use crate::state::AppState;
 use crate::storage::Storage;
 use crate::sync;
 use iroh::{Endpoint, SecretKey, protocol::Router};
-use iroh_blobs::{BlobsProtocol, store::fs::FsStore};
+use iroh_blobs::{
+    BlobsProtocol, Hash,
+    store::{
+        GcConfig, ProtectCb, ProtectOutcome,
+        fs::{FsStore, options::Options},
+    },
+};
 use iroh_gossip::Gossip;
-use iroh_social_types::{DM_ALPN, short_id};
+use iroh_social_types::{DM_ALPN, FOLLOW_ALPN, POST_FETCH_ALPN, now_millis, short_id};
+use std::collections::HashSet;
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter, Manager};
 use tokio::sync::Mutex;
@@ -141,6 +154,8 @@ pub fn initialize(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>
                 iroh_gossip::ALPN.to_vec(),
                 sync::SYNC_ALPN.to_vec(),
                 DM_ALPN.to_vec(),
+                POST_FETCH_ALPN.to_vec(),
+                FOLLOW_ALPN.to_vec(),
             ])
             .bind()
             .await
@@ -169,9 +184,18 @@ pub fn initialize(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>
         }
 
         let blobs_dir = data_dir.join("blobs");
-        let store = FsStore::load(&blobs_dir)
-            .await
-            .expect("failed to open blob store");
+        let store = FsStore::load_with_opts(
+            blobs_dir.join("blobs.db"),
+            Options {
+                gc: Some(GcConfig {
+                    interval: BLOB_GC_INTERVAL,
+                    add_protected: Some(protect_referenced_blobs(storage_clone.clone())),
+                }),
+                ..Options::new(&blobs_dir)
+            },
+        )
+        .await
+        .expect("failed to open blob store");
         log::info!("[setup] blob store opened at {}", blobs_dir.display());
 
         let blobs = BlobsProtocol::new(&store, None);
@@ -179,12 +203,17 @@ pub fn initialize(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>
         log::info!("[setup] gossip started");
 
         let node_id_str = endpoint.id().to_string();
+        let (outbox_flush_tx, outbox_flush_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
         let sync_handler = sync::SyncHandler::new(storage_clone.clone(), node_id_str.clone());
+        let post_fetch_handler = PostFetchHandler::new(storage_clone.clone(), node_id_str.clone());
+        let follow_handler =
+            FollowHandler::new(storage_clone.clone(), node_id_str.clone(), handle.clone());
         let dm_handler = DmHandler::new(
             storage_clone.clone(),
             handle.clone(),
             secret_key_bytes,
             endpoint.id().to_string(),
+            outbox_flush_tx.clone(),
         );
 
         let router = Router::builder(endpoint.clone())
@@ -192,6 +221,8 @@ pub fn initialize(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>
             .accept(iroh_gossip::ALPN, gossip.clone())
             .accept(sync::SYNC_ALPN, sync_handler)
             .accept(DM_ALPN, dm_handler.clone())
+            .accept(POST_FETCH_ALPN, post_fetch_handler)
+            .accept(FOLLOW_ALPN, follow_handler)
             .spawn();
         log::info!("[setup] router spawned");
 
@@ -200,6 +231,7 @@ pub fn initialize(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>
             endpoint.clone(),
             storage_clone.clone(),
             handle.clone(),
+            outbox_flush_tx,
         );
 
         if let Err(e) = feed.start_own_feed().await {
@@ -231,7 +263,7 @@ pub fn initialize(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>
         // Concurrent startup sync with semaphore for bounded parallelism
         let sync_endpoint = endpoint.clone();
         let sync_storage = storage_clone.clone();
-        let sync_follows = follows.clone();
+        let sync_peers = storage_clone.get_sync_peers().unwrap_or_default();
         let sync_handle = handle.clone();
         let sync_my_id = endpoint.id().to_string();
         tokio::spawn(async move {
@@ -256,7 +288,7 @@ pub fn initialize(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>
             let semaphore = Arc::new(tokio::sync::Semaphore::new(SYNC_CONCURRENCY));
             let mut join_set = tokio::task::JoinSet::new();
 
-            for f in sync_follows {
+            for pubkey in sync_peers {
                 let ep = sync_endpoint.clone();
                 let st = sync_storage.clone();
                 let hdl = sync_handle.clone();
@@ -264,7 +296,7 @@ pub fn initialize(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>
                 let mid = sync_my_id.clone();
                 join_set.spawn(async move {
                     let _permit = sem.acquire().await;
-                    sync_peer_posts(&ep, &st, &f.pubkey, &mid, &hdl).await;
+                    sync_peer_posts(&ep, &st, &pubkey, &mid, &hdl).await;
                 });
             }
 
@@ -285,33 +317,33 @@ pub fn initialize(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>
             tokio::time::sleep(DRIP_INITIAL_DELAY).await;
 
             loop {
-                let follows = drip_storage.get_follows().unwrap_or_default();
+                let peers = drip_storage.get_sync_peers().unwrap_or_default();
                 let mut any_work = false;
 
-                for f in &follows {
-                    let target: iroh::EndpointId = match f.pubkey.parse() {
+                for pubkey in &peers {
+                    let target: iroh::EndpointId = match pubkey.parse() {
                         Ok(t) => t,
                         Err(_) => continue,
                     };
 
-                    log::info!("[drip-sync] syncing {}", short_id(&f.pubkey));
+                    log::info!("[drip-sync] syncing {}", short_id(pubkey));
 
                     let result = tokio::time::timeout(
                         SYNC_TIMEOUT,
-                        sync::sync_from_peer(&drip_endpoint, &drip_storage, target, &f.pubkey),
+                        sync::sync_from_peer(&drip_endpoint, &drip_storage, target, pubkey),
                     )
                     .await;
 
                     match result {
                         Ok(Ok(sync_result)) => {
                             if sync_result.posts.is_empty() && sync_result.interactions.is_empty() {
-                                log::info!("[drip-sync] {} up to date", short_id(&f.pubkey),);
+                                log::info!("[drip-sync] {} up to date", short_id(pubkey),);
                                 continue;
                             }
 
                             let stored = process_sync_result(
                                 &drip_storage,
-                                &f.pubkey,
+                                pubkey,
                                 &sync_result,
                                 "drip-sync",
                                 &drip_my_id,
@@ -326,15 +358,15 @@ pub fn initialize(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>
                             log::info!(
                                 "[drip-sync] stored {stored}/{} posts from {} (mode={:?})",
                                 sync_result.posts.len(),
-                                short_id(&f.pubkey),
+                                short_id(pubkey),
                                 sync_result.mode,
                             );
                         }
                         Ok(Err(e)) => {
-                            log::error!("[drip-sync] failed for {}: {e}", short_id(&f.pubkey));
+                            log::error!("[drip-sync] failed for {}: {e}", short_id(pubkey));
                         }
                         Err(_) => {
-                            log::error!("[drip-sync] timed out for {}", short_id(&f.pubkey));
+                            log::error!("[drip-sync] timed out for {}", short_id(pubkey));
                         }
                     }
 
@@ -350,19 +382,32 @@ pub fn initialize(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>
             }
         });
 
-        // DM outbox flush task
+        // DM outbox flush task: retries peers whose backoff has elapsed, expires
+        // stale entries, and flushes a peer right away when it becomes reachable
         let outbox_dm = dm_handler.clone();
         let outbox_ep = endpoint.clone();
         let outbox_storage = storage_clone.clone();
         tokio::spawn(async move {
+            let mut outbox_flush_rx = outbox_flush_rx;
+            // One interval for the whole loop, so a stream of flush requests
+            // can't keep pushing the periodic retries and expiry back
+            let mut retry_interval = tokio::time::interval(OUTBOX_FLUSH_INTERVAL);
+            retry_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
             loop {
-                tokio::time::sleep(OUTBOX_FLUSH_INTERVAL).await;
-                let peers = match outbox_storage.get_all_outbox_peers() {
-                    Ok(p) => p,
-                    Err(e) => {
-                        log::error!("[dm-outbox] failed to get peers: {e}");
-                        continue;
+                let peers = tokio::select! {
+                    _ = retry_interval.tick() => {
+                        if let Err(e) = outbox_dm.expire_outbox() {
+                            log::error!("[dm-outbox] failed to expire entries: {e}");
+                        }
+                        match outbox_storage.get_due_outbox_peers(now_millis()) {
+                            Ok(p) => p,
+                            Err(e) => {
+                                log::error!("[dm-outbox] failed to get peers: {e}");
+                                continue;
+                            }
+                        }
                     }
+                    Some(peer) = outbox_flush_rx.recv() => vec![peer],
                 };
                 for peer in peers {
                     match outbox_dm.flush_outbox_for_peer(&outbox_ep, &peer).await {
@@ -390,6 +435,88 @@ pub fn initialize(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>
             feed: Arc::new(Mutex::new(feed)),
             dm: dm_handler,
             secret_key_bytes,
+            link_fetcher: Arc::new(
+                HttpFetcher::new().expect("failed to create link preview fetcher"),
+            ),
+            ranked_feed: std::sync::Mutex::new(Vec::new()),
+        });
+
+        // Scheduled post publisher: signs and publishes queued posts once due,
+        // including any that came due while the app was closed
+        let scheduler_state = state.clone();
+        let scheduler_handle = handle.clone();
+        tokio::spawn(async move {
+            loop {
+                let due = scheduler_state
+                    .storage
+                    .get_due_scheduled_posts(now_millis())
+                    .unwrap_or_else(|e| {
+                        log::error!("[scheduler] failed to get due posts: {e}");
+                        Vec::new()
+                    });
+                for item in due {
+                    // A crash after publishing but before dequeueing leaves the
+                    // post stored under the scheduled id; don't publish it twice
+                    let published = matches!(
+                        scheduler_state.storage.get_post_by_id(&item.id),
+                        Ok(Some(_))
+                    );
+                    if !published {
+                        match publish_post(&scheduler_state, item.id.clone(), item.body, None).await
+                        {
+                            Ok(_) => {
+                                log::info!(
+                                    "[scheduler] published post {} ({}s late)",
+                                    item.id,
+                                    now_millis().saturating_sub(item.publish_at) / 1000
+                                );
+                            }
+                            Err(e) => {
+                                log::error!("[scheduler] failed to publish {}: {e}", item.id);
+                                let _ = scheduler_state
+                                    .storage
+                                    .set_scheduled_post_error(&item.id, &e);
+                                let _ = scheduler_handle.emit("scheduled-post-failed", &item.id);
+                                continue;
+                            }
+                        }
+                    }
+                    if let Err(e) = scheduler_state.storage.delete_scheduled_post(&item.id) {
+                        log::error!("[scheduler] failed to dequeue {}: {e}", item.id);
+                    }
+                    let _ = scheduler_handle.emit("scheduled-post-published", &item.id);
+                    let _ = scheduler_handle.emit("feed-updated", ());
+                }
+                tokio::time::sleep(SCHEDULE_CHECK_INTERVAL).await;
+            }
+        });
+
+        // Expiry sweep: drops ephemeral posts, expired muted words and
+        // lapsed mutes once their time is up
+        let sweep_state = state.clone();
+        let sweep_handle = handle.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = sweep_state.storage.delete_expired_muted_words(now_millis()) {
+                    log::error!("[expiry] muted word sweep failed: {e}");
+                }
+                match sweep_state.storage.delete_expired_mutes(now_millis()) {
+                    Ok(0) => {}
+                    Ok(n) => {
+                        log::info!("[expiry] {n} mutes lifted");
+                        let _ = sweep_handle.emit("feed-updated", ());
+                    }
+                    Err(e) => log::error!("[expiry] mute sweep failed: {e}"),
+                }
+                match sweep_expired_posts(&sweep_state).await {
+                    Ok(0) => {}
+                    Ok(_) => {
+                        let _ = sweep_handle.emit("feed-updated", ());
+                    }
+                    Err(e) => log::error!("[expiry] sweep failed: {e}"),
+                }
+                tokio::time::sleep(EXPIRY_SWEEP_INTERVAL).await;
+            }
         });
 
         handle.manage(state);
@@ -398,3 +525,22 @@ pub fn initialize(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>
 
     Ok(())
 }
+
+/// Blob GC hook: keeps every blob the database still refers to. Blobs we
+/// added ourselves are also kept by their tags until those are deleted.
+fn protect_referenced_blobs(storage: Arc<Storage>) -> ProtectCb {
+    Arc::new(move |live: &mut HashSet<Hash>| {
+        let outcome = match storage.referenced_blob_hashes() {
+            Ok(hashes) => {
+                live.extend(hashes.iter().filter_map(|h| h.parse::<Hash>().ok()));
+                ProtectOutcome::Continue
+            }
+            Err(e) => {
+                log::error!("[blobs] skipping gc, referenced blobs unknown: {e}");
+                ProtectOutcome::Abort
+            }
+        };
+        Box::pin(std::future::ready(outcome))
+            as Pin<Box<dyn Future<Output = ProtectOutcome> + Send + Sync>>
+    })
+}
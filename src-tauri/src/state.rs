@@ -1,5 +1,7 @@
 use crate::dm::DmHandler;
 use crate::gossip::FeedManager;
+use crate::link_preview::LinkFetcher;
+use crate::ranking::RankedPost;
 use crate::storage::Storage;
 use iroh::{Endpoint, protocol::Router};
 use iroh_blobs::{BlobsProtocol, store::fs::FsStore};
@@ -17,11 +19,15 @@ pub struct AppState {
     pub feed: Arc<Mutex<FeedManager>>,
     pub dm: DmHandler,
     pub secret_key_bytes: [u8; 32],
+    pub link_fetcher: Arc<dyn LinkFetcher>,
+    /// The "for you" feed as ranked for its first page. Later pages are cut
+    /// from it, so scores moving between calls can't skip or repeat posts.
+    pub ranked_feed: std::sync::Mutex<Vec<RankedPost>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FrontendSyncResult {
-    pub posts: Vec<iroh_social_types::Post>,
+    pub posts: Vec<crate::storage::PostView>,
     pub remote_total: u64,
 }
 
@@ -30,6 +36,39 @@ pub struct SyncStatus {
     pub local_count: u64,
 }
 
+/// A post as originally signed plus its signed edits, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostHistory {
+    pub original: iroh_social_types::Post,
+    pub edits: Vec<iroh_social_types::PostEdit>,
+}
+
+/// A post in a conversation tree with its interaction counts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationNode {
+    pub post: crate::storage::PostView,
+    pub counts: crate::storage::PostCounts,
+    pub children: Vec<ConversationNode>,
+}
+
+/// A parent post referenced by `reply_to` that we don't hold.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MissingPost {
+    pub id: String,
+    pub author: Option<String>,
+}
+
+/// Everything around one post: the chain above it and the replies below it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conversation {
+    /// Root first, excluding the focused post.
+    pub ancestors: Vec<ConversationNode>,
+    /// The focused post, with its replies nested under `children`.
+    pub focus: ConversationNode,
+    /// Set when the chain of ancestors breaks on a post we don't hold.
+    pub missing_ancestor: Option<MissingPost>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeStatus {
     pub node_id: String,
@@ -0,0 +1,190 @@
+use iroh_social_types::Audience;
+use rusqlite::{Connection, params};
+
+use super::Storage;
+
+impl Storage {
+    fn audience_members(db: &Connection, audience_id: &str) -> anyhow::Result<Vec<String>> {
+        let mut stmt = db.prepare_cached(
+            "SELECT pubkey FROM audience_members WHERE audience_id=?1 ORDER BY added_at ASC",
+        )?;
+        let rows = stmt.query_map(params![audience_id], |row| row.get(0))?;
+        Ok(rows.collect::<Result<Vec<String>, _>>()?)
+    }
+
+    fn set_audience_members(
+        db: &Connection,
+        audience_id: &str,
+        members: &[String],
+        now: u64,
+    ) -> anyhow::Result<()> {
+        db.execute(
+            "DELETE FROM audience_members WHERE audience_id=?1",
+            params![audience_id],
+        )?;
+        let mut stmt = db.prepare_cached(
+            "INSERT OR IGNORE INTO audience_members (audience_id, pubkey, added_at)
+             VALUES (?1, ?2, ?3)",
+        )?;
+        for pubkey in members {
+            stmt.execute(params![audience_id, pubkey, now as i64])?;
+        }
+        Ok(())
+    }
+
+    pub fn create_audience(&self, audience: &Audience) -> anyhow::Result<()> {
+        let mut db = self.db.lock().unwrap();
+        let tx = db.transaction()?;
+        tx.execute(
+            "INSERT INTO audiences (id, name, created_at) VALUES (?1, ?2, ?3)",
+            params![audience.id, audience.name, audience.created_at as i64],
+        )?;
+        Self::set_audience_members(&tx, &audience.id, &audience.members, audience.created_at)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Rename an audience and replace its members. Returns false if it
+    /// doesn't exist.
+    pub fn update_audience(
+        &self,
+        id: &str,
+        name: &str,
+        members: &[String],
+        now: u64,
+    ) -> anyhow::Result<bool> {
+        let mut db = self.db.lock().unwrap();
+        let tx = db.transaction()?;
+        if tx.execute(
+            "UPDATE audiences SET name=?2 WHERE id=?1",
+            params![id, name],
+        )? == 0
+        {
+            return Ok(false);
+        }
+        Self::set_audience_members(&tx, id, members, now)?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    pub fn delete_audience(&self, id: &str) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let count = db.execute("DELETE FROM audiences WHERE id=?1", params![id])?;
+        Ok(count > 0)
+    }
+
+    pub fn get_audience(&self, id: &str) -> anyhow::Result<Option<Audience>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare("SELECT id, name, created_at FROM audiences WHERE id=?1")?;
+        let mut rows = stmt.query(params![id])?;
+        let Some(row) = rows.next()? else {
+            return Ok(None);
+        };
+        Ok(Some(Audience {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            members: Self::audience_members(&db, id)?,
+            created_at: row.get::<_, i64>(2)? as u64,
+        }))
+    }
+
+    pub fn get_audiences(&self) -> anyhow::Result<Vec<Audience>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt =
+            db.prepare("SELECT id, name, created_at FROM audiences ORDER BY created_at ASC")?;
+        let mut rows = stmt.query([])?;
+        let mut audiences = Vec::new();
+        while let Some(row) = rows.next()? {
+            let id: String = row.get(0)?;
+            audiences.push(Audience {
+                members: Self::audience_members(&db, &id)?,
+                id,
+                name: row.get(1)?,
+                created_at: row.get::<_, i64>(2)? as u64,
+            });
+        }
+        Ok(audiences)
+    }
+
+    /// Record that one of our posts was addressed to an audience.
+    pub fn set_post_audience(&self, post_id: &str, audience_id: &str) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "INSERT OR REPLACE INTO audience_posts (post_id, audience_id) VALUES (?1, ?2)",
+            params![post_id, audience_id],
+        )?;
+        Ok(())
+    }
+
+    /// Whether `pubkey` is on the audience one of our posts was addressed to.
+    pub fn is_in_post_audience(&self, post_id: &str, pubkey: &str) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let exists: bool = db.query_row(
+            "SELECT COUNT(*) > 0 FROM audience_posts ap
+             JOIN audience_members am ON am.audience_id = ap.audience_id
+             WHERE ap.post_id=?1 AND am.pubkey=?2",
+            params![post_id, pubkey],
+            |row| row.get(0),
+        )?;
+        Ok(exists)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn audience(id: &str, members: &[&str]) -> Audience {
+        Audience {
+            id: id.to_string(),
+            name: format!("{id} name"),
+            members: members.iter().map(|m| m.to_string()).collect(),
+            created_at: 1,
+        }
+    }
+
+    #[test]
+    fn test_audience_crud_dedupes_members() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage
+            .create_audience(&audience("a1", &["carol", "bob", "carol"]))
+            .unwrap();
+        let mut stored = storage.get_audience("a1").unwrap().unwrap();
+        stored.members.sort();
+        assert_eq!(stored.name, "a1 name");
+        assert_eq!(stored.members, ["bob", "carol"]);
+
+        assert!(
+            storage
+                .update_audience("a1", "close", &["dave".to_string()], 2)
+                .unwrap()
+        );
+        assert!(!storage.update_audience("missing", "x", &[], 2).unwrap());
+        let stored = storage.get_audience("a1").unwrap().unwrap();
+        assert_eq!(stored.name, "close");
+        assert_eq!(stored.members, ["dave"]);
+        assert_eq!(storage.get_audiences().unwrap().len(), 1);
+
+        assert!(storage.delete_audience("a1").unwrap());
+        assert!(!storage.delete_audience("a1").unwrap());
+        assert!(storage.get_audience("a1").unwrap().is_none());
+        assert!(storage.get_audiences().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_post_audience_follows_current_members() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage.create_audience(&audience("a1", &["bob"])).unwrap();
+        storage.set_post_audience("p1", "a1").unwrap();
+        assert!(storage.is_in_post_audience("p1", "bob").unwrap());
+        assert!(!storage.is_in_post_audience("p1", "carol").unwrap());
+        assert!(!storage.is_in_post_audience("p2", "bob").unwrap());
+
+        // Membership is checked when a post is served, not when it was made
+        storage
+            .update_audience("a1", "a1 name", &["carol".to_string()], 2)
+            .unwrap();
+        assert!(!storage.is_in_post_audience("p1", "bob").unwrap());
+        assert!(storage.is_in_post_audience("p1", "carol").unwrap());
+    }
+}
@@ -29,4 +29,61 @@ impl Storage {
             None => Ok(None),
         }
     }
+
+    /// Remember that the envelope from `peer_pubkey` with this ratchet header
+    /// was decrypted, and the message ID it carried.
+    pub fn record_received_envelope(
+        &self,
+        peer_pubkey: &str,
+        dh_public: &str,
+        message_number: u32,
+        message_id: Option<&str>,
+        received_at: u64,
+    ) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "INSERT OR IGNORE INTO dm_received_envelopes
+             (peer_pubkey, dh_public, message_number, message_id, received_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                peer_pubkey,
+                dh_public,
+                message_number,
+                message_id,
+                received_at as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The message ID of an envelope already decrypted, if it was:
+    /// `Some(None)` for one that carried no message.
+    pub fn get_received_envelope(
+        &self,
+        peer_pubkey: &str,
+        dh_public: &str,
+        message_number: u32,
+    ) -> anyhow::Result<Option<Option<String>>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(
+            "SELECT message_id FROM dm_received_envelopes
+             WHERE peer_pubkey=?1 AND dh_public=?2 AND message_number=?3",
+        )?;
+        let mut rows = stmt.query(params![peer_pubkey, dh_public, message_number])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(row.get(0)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Forget received envelopes older than `cutoff`; senders have given up
+    /// resending them by then.
+    pub fn delete_received_envelopes_before(&self, cutoff: u64) -> anyhow::Result<usize> {
+        let db = self.db.lock().unwrap();
+        let count = db.execute(
+            "DELETE FROM dm_received_envelopes WHERE received_at < ?1",
+            params![cutoff as i64],
+        )?;
+        Ok(count)
+    }
 }
@@ -0,0 +1,198 @@
+use rusqlite::{OptionalExtension, params};
+
+use super::{Draft, PostBody, ScheduledPost, Storage};
+
+impl Storage {
+    fn row_to_draft(row: &rusqlite::Row) -> anyhow::Result<Draft> {
+        let body_json: String = row.get(1)?;
+        Ok(Draft {
+            id: row.get(0)?,
+            body: serde_json::from_str(&body_json)?,
+            created_at: row.get::<_, i64>(2)? as u64,
+            updated_at: row.get::<_, i64>(3)? as u64,
+        })
+    }
+
+    fn row_to_scheduled_post(row: &rusqlite::Row) -> anyhow::Result<ScheduledPost> {
+        let body_json: String = row.get(1)?;
+        Ok(ScheduledPost {
+            id: row.get(0)?,
+            body: serde_json::from_str(&body_json)?,
+            publish_at: row.get::<_, i64>(2)? as u64,
+            created_at: row.get::<_, i64>(3)? as u64,
+            last_error: row.get(4)?,
+        })
+    }
+
+    /// Create or overwrite a draft.
+    pub fn save_draft(&self, id: &str, body: &PostBody, now: u64) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        let body_json = serde_json::to_string(body)?;
+        db.execute(
+            "INSERT INTO drafts (id, body_json, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)
+             ON CONFLICT(id) DO UPDATE SET body_json=?2, updated_at=?3",
+            params![id, body_json, now as i64],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_draft(&self, id: &str) -> anyhow::Result<Option<Draft>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt =
+            db.prepare("SELECT id, body_json, created_at, updated_at FROM drafts WHERE id=?1")?;
+        let draft = stmt
+            .query_row(params![id], |row| Ok(Self::row_to_draft(row)))
+            .optional()?
+            .transpose()?;
+        Ok(draft)
+    }
+
+    pub fn get_drafts(&self) -> anyhow::Result<Vec<Draft>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(
+            "SELECT id, body_json, created_at, updated_at FROM drafts ORDER BY updated_at DESC",
+        )?;
+        let rows = stmt.query_and_then([], Self::row_to_draft)?;
+        rows.collect()
+    }
+
+    pub fn delete_draft(&self, id: &str) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let count = db.execute("DELETE FROM drafts WHERE id=?1", params![id])?;
+        Ok(count > 0)
+    }
+
+    pub fn insert_scheduled_post(
+        &self,
+        id: &str,
+        body: &PostBody,
+        publish_at: u64,
+        now: u64,
+    ) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        let body_json = serde_json::to_string(body)?;
+        db.execute(
+            "INSERT INTO scheduled_posts (id, body_json, publish_at, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![id, body_json, publish_at as i64, now as i64],
+        )?;
+        Ok(())
+    }
+
+    /// All scheduled posts, soonest first, including failed ones.
+    pub fn get_scheduled_posts(&self) -> anyhow::Result<Vec<ScheduledPost>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(
+            "SELECT id, body_json, publish_at, created_at, last_error
+             FROM scheduled_posts ORDER BY publish_at ASC",
+        )?;
+        let rows = stmt.query_and_then([], Self::row_to_scheduled_post)?;
+        rows.collect()
+    }
+
+    /// Scheduled posts whose time has come, including any missed while the
+    /// app was closed. Failed entries wait until they are rescheduled.
+    pub fn get_due_scheduled_posts(&self, now: u64) -> anyhow::Result<Vec<ScheduledPost>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(
+            "SELECT id, body_json, publish_at, created_at, last_error
+             FROM scheduled_posts WHERE publish_at <= ?1 AND last_error IS NULL
+             ORDER BY publish_at ASC",
+        )?;
+        let rows = stmt.query_and_then(params![now as i64], Self::row_to_scheduled_post)?;
+        rows.collect()
+    }
+
+    pub fn delete_scheduled_post(&self, id: &str) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let count = db.execute("DELETE FROM scheduled_posts WHERE id=?1", params![id])?;
+        Ok(count > 0)
+    }
+
+    pub fn set_scheduled_post_error(&self, id: &str, error: &str) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "UPDATE scheduled_posts SET last_error=?2 WHERE id=?1",
+            params![id, error],
+        )?;
+        Ok(())
+    }
+
+    /// Move a scheduled post to a new time and clear any previous failure.
+    pub fn reschedule_post(&self, id: &str, publish_at: u64) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let count = db.execute(
+            "UPDATE scheduled_posts SET publish_at=?2, last_error=NULL WHERE id=?1",
+            params![id, publish_at as i64],
+        )?;
+        Ok(count > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(content: &str) -> PostBody {
+        serde_json::from_value(serde_json::json!({ "content": content })).unwrap()
+    }
+
+    #[test]
+    fn test_drafts_are_overwritten_and_listed_newest_first() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage.save_draft("d1", &body("first"), 10).unwrap();
+        storage.save_draft("d2", &body("second"), 20).unwrap();
+        storage
+            .save_draft("d1", &body("first, revised"), 30)
+            .unwrap();
+
+        let d1 = storage.get_draft("d1").unwrap().unwrap();
+        assert_eq!(d1.body.content, "first, revised");
+        assert_eq!((d1.created_at, d1.updated_at), (10, 30));
+        let ids: Vec<String> = storage
+            .get_drafts()
+            .unwrap()
+            .into_iter()
+            .map(|d| d.id)
+            .collect();
+        assert_eq!(ids, vec!["d1", "d2"]);
+
+        assert!(storage.delete_draft("d1").unwrap());
+        assert!(!storage.delete_draft("d1").unwrap());
+        assert!(storage.get_draft("d1").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_failed_scheduled_posts_wait_for_a_reschedule() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage
+            .insert_scheduled_post("s2", &body("later"), 200, 1)
+            .unwrap();
+        storage
+            .insert_scheduled_post("s1", &body("sooner"), 100, 1)
+            .unwrap();
+        let due = |now| -> Vec<String> {
+            storage
+                .get_due_scheduled_posts(now)
+                .unwrap()
+                .into_iter()
+                .map(|s| s.id)
+                .collect()
+        };
+        assert!(due(50).is_empty());
+        assert_eq!(due(150), vec!["s1"]);
+        assert_eq!(due(250), vec!["s1", "s2"]);
+
+        storage.set_scheduled_post_error("s1", "offline").unwrap();
+        assert_eq!(due(250), vec!["s2"]);
+        let all = storage.get_scheduled_posts().unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].last_error.as_deref(), Some("offline"));
+
+        assert!(storage.reschedule_post("s1", 300).unwrap());
+        assert!(!storage.reschedule_post("missing", 300).unwrap());
+        assert_eq!(due(350), vec!["s2", "s1"]);
+        assert!(storage.delete_scheduled_post("s2").unwrap());
+        assert_eq!(due(350), vec!["s1"]);
+    }
+}
@@ -0,0 +1,151 @@
+use iroh_social_types::FeedKey;
+use rusqlite::params;
+
+use super::Storage;
+
+fn row_to_feed_key(row: &rusqlite::Row) -> rusqlite::Result<FeedKey> {
+    Ok(FeedKey {
+        key_id: row.get(0)?,
+        key: row.get(1)?,
+        created_at: row.get::<_, i64>(2)? as u64,
+    })
+}
+
+impl Storage {
+    /// The newest key our private feed is sealed under.
+    pub fn current_feed_key(&self) -> anyhow::Result<Option<FeedKey>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(
+            "SELECT key_id, key, created_at FROM feed_keys
+             ORDER BY created_at DESC, rowid DESC LIMIT 1",
+        )?;
+        let mut rows = stmt.query_map([], row_to_feed_key)?;
+        Ok(rows.next().transpose()?)
+    }
+
+    pub fn insert_feed_key(&self, key: &FeedKey) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "INSERT INTO feed_keys (key_id, key, created_at) VALUES (?1, ?2, ?3)",
+            params![key.key_id, key.key, key.created_at as i64],
+        )?;
+        Ok(())
+    }
+
+    pub fn mark_feed_key_delivered(
+        &self,
+        key_id: &str,
+        pubkey: &str,
+        delivered_at: u64,
+    ) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "INSERT OR IGNORE INTO feed_key_deliveries (key_id, pubkey, delivered_at)
+             VALUES (?1, ?2, ?3)",
+            params![key_id, pubkey, delivered_at as i64],
+        )?;
+        Ok(())
+    }
+
+    pub fn is_feed_key_delivered(&self, key_id: &str, pubkey: &str) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let count: i64 = db.query_row(
+            "SELECT COUNT(*) FROM feed_key_deliveries WHERE key_id=?1 AND pubkey=?2",
+            params![key_id, pubkey],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Whether feed key `key_id` is waiting in the outbox for `pubkey`.
+    pub fn is_feed_key_queued(&self, key_id: &str, pubkey: &str) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let count: i64 = db.query_row(
+            "SELECT COUNT(*) FROM dm_outbox
+             WHERE peer_pubkey=?2 AND failed=0
+               AND json_extract(payload_json, '$.FeedKey.key_id')=?1",
+            params![key_id, pubkey],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    pub fn save_received_feed_key(&self, author: &str, key: &FeedKey) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "INSERT OR IGNORE INTO received_feed_keys (author, key_id, key, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![author, key.key_id, key.key, key.created_at as i64],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_received_feed_key(
+        &self,
+        author: &str,
+        key_id: &str,
+    ) -> anyhow::Result<Option<FeedKey>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(
+            "SELECT key_id, key, created_at FROM received_feed_keys
+             WHERE author=?1 AND key_id=?2",
+        )?;
+        let mut rows = stmt.query_map(params![author, key_id], row_to_feed_key)?;
+        Ok(rows.next().transpose()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh_social_types::DmPayload;
+
+    fn key(key_id: &str) -> FeedKey {
+        FeedKey {
+            key_id: key_id.to_string(),
+            key: vec![7; 32],
+            created_at: 1,
+        }
+    }
+
+    fn queue(storage: &Storage, id: &str, peer: &str, key: FeedKey) {
+        let payload_json = serde_json::to_string(&DmPayload::FeedKey(key)).unwrap();
+        storage
+            .insert_outbox_payload(id, peer, &payload_json, 1, "")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_feed_key_queued_until_sent_or_expired() {
+        let storage = Storage::open(":memory:").unwrap();
+        assert!(!storage.is_feed_key_queued("k1", "bob").unwrap());
+
+        queue(&storage, "o1", "bob", key("k1"));
+        queue(&storage, "o2", "carol", key("k2"));
+        assert!(storage.is_feed_key_queued("k1", "bob").unwrap());
+        assert!(!storage.is_feed_key_queued("k2", "bob").unwrap());
+        assert!(!storage.is_feed_key_queued("k1", "carol").unwrap());
+
+        // Sealing keeps the payload, so the key still counts as queued
+        storage.seal_outbox_message("o1", "{}").unwrap();
+        assert!(storage.is_feed_key_queued("k1", "bob").unwrap());
+
+        storage.remove_outbox_message("o1").unwrap();
+        assert!(!storage.is_feed_key_queued("k1", "bob").unwrap());
+
+        // An expired entry no longer holds the key back from being requeued
+        storage.expire_outbox_before(2).unwrap();
+        assert!(!storage.is_feed_key_queued("k2", "carol").unwrap());
+    }
+
+    #[test]
+    fn test_feed_key_delivery_is_per_key_and_follower() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage.insert_feed_key(&key("k1")).unwrap();
+        storage.mark_feed_key_delivered("k1", "bob", 2).unwrap();
+        storage.mark_feed_key_delivered("k1", "bob", 3).unwrap();
+        assert!(storage.is_feed_key_delivered("k1", "bob").unwrap());
+        assert!(!storage.is_feed_key_delivered("k1", "carol").unwrap());
+        assert!(!storage.is_feed_key_delivered("k2", "bob").unwrap());
+    }
+}
@@ -1,13 +1,18 @@
-use iroh_social_types::{Interaction, InteractionKind};
-use rusqlite::params;
+use iroh_social_types::{Interaction, InteractionKind, Poll, now_millis, vote_matches_poll};
+use rusqlite::{OptionalExtension, params};
 
-use super::{PostCounts, Storage};
+use super::{PollResults, PostCounts, ReactionCount, Storage};
 
 impl Storage {
     pub(crate) fn row_to_interaction(row: &rusqlite::Row) -> anyhow::Result<Interaction> {
         let kind_str: String = row.get(2)?;
         let kind = match kind_str.to_lowercase().as_str() {
             "like" => InteractionKind::Like,
+            "reaction" => InteractionKind::Reaction(row.get(7)?),
+            "vote" => {
+                let vote_json: String = row.get(8)?;
+                InteractionKind::Vote(serde_json::from_str(&vote_json)?)
+            }
             other => anyhow::bail!("unknown interaction kind: {other}"),
         };
         Ok(Interaction {
@@ -22,21 +27,24 @@ impl Storage {
     }
 
     pub fn save_interaction(&self, interaction: &Interaction) -> anyhow::Result<()> {
-        let db = self.db.lock().unwrap();
-        let kind_str = match interaction.kind {
-            InteractionKind::Like => "Like",
+        let vote_json = match &interaction.kind {
+            InteractionKind::Vote(choices) => Some(serde_json::to_string(choices)?),
+            _ => None,
         };
+        let db = self.db.lock().unwrap();
         db.execute(
-            "INSERT OR IGNORE INTO interactions (id, author, kind, target_post_id, target_author, timestamp, signature)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            "INSERT OR IGNORE INTO interactions (id, author, kind, target_post_id, target_author, timestamp, signature, emoji, vote_json)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
             params![
                 interaction.id,
                 interaction.author,
-                kind_str,
+                interaction.kind.as_str(),
                 interaction.target_post_id,
                 interaction.target_author,
                 interaction.timestamp as i64,
                 interaction.signature,
+                Self::interaction_emoji(&interaction.kind),
+                vote_json,
             ],
         )?;
         Ok(())
@@ -51,17 +59,30 @@ impl Storage {
         Ok(count > 0)
     }
 
+    /// Value of the `emoji` column for an interaction kind.
+    fn interaction_emoji(kind: &InteractionKind) -> &str {
+        match kind {
+            InteractionKind::Like | InteractionKind::Vote(_) => "",
+            InteractionKind::Reaction(emoji) => emoji,
+        }
+    }
+
     pub fn delete_interaction_by_target(
         &self,
         author: &str,
-        kind: &str,
+        kind: &InteractionKind,
         target_post_id: &str,
     ) -> anyhow::Result<Option<String>> {
         let db = self.db.lock().unwrap();
         let id: Option<String> = db
             .query_row(
-                "SELECT id FROM interactions WHERE author=?1 AND kind=?2 AND target_post_id=?3",
-                params![author, kind, target_post_id],
+                "SELECT id FROM interactions WHERE author=?1 AND kind=?2 AND emoji=?3 AND target_post_id=?4",
+                params![
+                    author,
+                    kind.as_str(),
+                    Self::interaction_emoji(kind),
+                    target_post_id
+                ],
                 |row| row.get(0),
             )
             .ok();
@@ -86,7 +107,12 @@ impl Storage {
             |row| row.get(0),
         )?;
         let reposts: i64 = db.query_row(
-            "SELECT COUNT(*) FROM posts WHERE quote_of=?1",
+            "SELECT COUNT(*) FROM posts WHERE quote_of=?1 AND kind='repost'",
+            params![target_post_id],
+            |row| row.get(0),
+        )?;
+        let quotes: i64 = db.query_row(
+            "SELECT COUNT(*) FROM posts WHERE quote_of=?1 AND kind='quote'",
             params![target_post_id],
             |row| row.get(0),
         )?;
@@ -101,24 +127,100 @@ impl Storage {
             |row| row.get(0),
         )?;
         let reposted_by_me: bool = db.query_row(
-            "SELECT COUNT(*) > 0 FROM posts WHERE author=?1 AND quote_of=?2",
+            "SELECT COUNT(*) > 0 FROM posts WHERE author=?1 AND quote_of=?2 AND kind='repost'",
             params![my_pubkey, target_post_id],
             |row| row.get(0),
         )?;
+        let mut stmt = db.prepare(
+            "SELECT emoji, COUNT(*), MAX(author=?2) FROM interactions
+             WHERE target_post_id=?1 AND kind='Reaction'
+             GROUP BY emoji ORDER BY COUNT(*) DESC, MIN(timestamp) ASC",
+        )?;
+        let mut rows = stmt.query(params![target_post_id, my_pubkey])?;
+        let mut reactions = Vec::new();
+        while let Some(row) = rows.next()? {
+            reactions.push(ReactionCount {
+                emoji: row.get(0)?,
+                count: row.get::<_, i64>(1)? as u32,
+                reacted_by_me: row.get::<_, i32>(2)? != 0,
+            });
+        }
         Ok(PostCounts {
             likes: likes as u32,
             replies: replies as u32,
             reposts: reposts as u32,
+            quotes: quotes as u32,
             liked_by_me,
             reposted_by_me,
+            reactions,
         })
     }
 
-    pub fn count_interactions_by_author(&self, author: &str) -> anyhow::Result<u64> {
+    /// Tally the poll on `post_id` from the votes held locally. Votes by the
+    /// wrong target author, cast after closing, or not matching the poll's
+    /// options are ignored. Returns `None` if the post has no poll.
+    pub fn get_poll_results(
+        &self,
+        my_pubkey: &str,
+        post_id: &str,
+    ) -> anyhow::Result<Option<PollResults>> {
+        let db = self.db.lock().unwrap();
+        let post: Option<(String, Option<String>)> = db
+            .query_row(
+                "SELECT author, poll_json FROM posts WHERE id=?1",
+                params![post_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((post_author, Some(poll_json))) = post else {
+            return Ok(None);
+        };
+        let poll: Poll = serde_json::from_str(&poll_json)?;
+
+        let mut stmt = db.prepare(
+            "SELECT author, vote_json FROM interactions
+             WHERE target_post_id=?1 AND target_author=?2 AND kind='Vote' AND timestamp <= ?3",
+        )?;
+        let mut rows = stmt.query(params![post_id, post_author, poll.closes_at as i64])?;
+        let mut counts = vec![0u32; poll.options.len()];
+        let mut voters = 0;
+        let mut my_vote = None;
+        while let Some(row) = rows.next()? {
+            let author: String = row.get(0)?;
+            let vote_json: String = row.get(1)?;
+            let Ok(choices) = serde_json::from_str::<Vec<u32>>(&vote_json) else {
+                continue;
+            };
+            if !vote_matches_poll(&poll, &choices) {
+                continue;
+            }
+            for &choice in &choices {
+                counts[choice as usize] += 1;
+            }
+            voters += 1;
+            if author == my_pubkey {
+                my_vote = Some(choices);
+            }
+        }
+        Ok(Some(PollResults {
+            counts,
+            voters,
+            my_vote,
+            closed: now_millis() >= poll.closes_at,
+        }))
+    }
+
+    /// `likes_only` restricts this and the other sync queries below to
+    /// `Like` interactions, for peers that cannot parse reactions.
+    pub fn count_interactions_by_author(
+        &self,
+        author: &str,
+        likes_only: bool,
+    ) -> anyhow::Result<u64> {
         let db = self.db.lock().unwrap();
         let count: i64 = db.query_row(
-            "SELECT COUNT(*) FROM interactions WHERE author=?1",
-            params![author],
+            "SELECT COUNT(*) FROM interactions WHERE author=?1 AND (?2 = 0 OR kind='Like')",
+            params![author, likes_only as i32],
             |row| row.get(0),
         )?;
         Ok(count as u64)
@@ -134,11 +236,16 @@ impl Storage {
         Ok(ts.unwrap_or(0) as u64)
     }
 
-    pub fn count_interactions_after(&self, author: &str, after_ts: u64) -> anyhow::Result<u64> {
+    pub fn count_interactions_after(
+        &self,
+        author: &str,
+        after_ts: u64,
+        likes_only: bool,
+    ) -> anyhow::Result<u64> {
         let db = self.db.lock().unwrap();
         let count: i64 = db.query_row(
-            "SELECT COUNT(*) FROM interactions WHERE author=?1 AND timestamp > ?2",
-            params![author, after_ts as i64],
+            "SELECT COUNT(*) FROM interactions WHERE author=?1 AND timestamp > ?2 AND (?3 = 0 OR kind='Like')",
+            params![author, after_ts as i64, likes_only as i32],
             |row| row.get(0),
         )?;
         Ok(count as u64)
@@ -148,18 +255,20 @@ impl Storage {
         &self,
         author: &str,
         after_ts: u64,
+        likes_only: bool,
         limit: usize,
         offset: usize,
     ) -> anyhow::Result<Vec<Interaction>> {
         let db = self.db.lock().unwrap();
         let mut stmt = db.prepare(
-            "SELECT id, author, kind, target_post_id, target_author, timestamp, signature
-             FROM interactions WHERE author=?1 AND timestamp > ?2
-             ORDER BY timestamp ASC LIMIT ?3 OFFSET ?4",
+            "SELECT id, author, kind, target_post_id, target_author, timestamp, signature, emoji, vote_json
+             FROM interactions WHERE author=?1 AND timestamp > ?2 AND (?3 = 0 OR kind='Like')
+             ORDER BY timestamp ASC LIMIT ?4 OFFSET ?5",
         )?;
         let mut rows = stmt.query(params![
             author,
             after_ts as i64,
+            likes_only as i32,
             limit as i64,
             offset as i64
         ])?;
@@ -173,16 +282,22 @@ impl Storage {
     pub fn get_interactions_paged(
         &self,
         author: &str,
+        likes_only: bool,
         limit: usize,
         offset: usize,
     ) -> anyhow::Result<Vec<Interaction>> {
         let db = self.db.lock().unwrap();
         let mut stmt = db.prepare(
-            "SELECT id, author, kind, target_post_id, target_author, timestamp, signature
-             FROM interactions WHERE author=?1
-             ORDER BY timestamp ASC LIMIT ?2 OFFSET ?3",
+            "SELECT id, author, kind, target_post_id, target_author, timestamp, signature, emoji, vote_json
+             FROM interactions WHERE author=?1 AND (?2 = 0 OR kind='Like')
+             ORDER BY timestamp ASC LIMIT ?3 OFFSET ?4",
         )?;
-        let mut rows = stmt.query(params![author, limit as i64, offset as i64])?;
+        let mut rows = stmt.query(params![
+            author,
+            likes_only as i32,
+            limit as i64,
+            offset as i64
+        ])?;
         let mut interactions = Vec::new();
         while let Some(row) = rows.next()? {
             interactions.push(Self::row_to_interaction(row)?);
@@ -190,3 +305,76 @@ impl Storage {
         Ok(interactions)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh_social_types::Post;
+
+    fn poll_post(storage: &Storage, multiple: bool) {
+        let post = Post {
+            id: "poll".to_string(),
+            author: "alice".to_string(),
+            content: "pick one".to_string(),
+            timestamp: 1_000,
+            poll: Some(Poll {
+                options: vec!["a".to_string(), "b".to_string()],
+                closes_at: 10_000,
+                multiple,
+            }),
+            ..Default::default()
+        };
+        storage.insert_post(&post).unwrap();
+    }
+
+    fn vote(storage: &Storage, id: &str, author: &str, choices: Vec<u32>, timestamp: u64) {
+        storage
+            .save_interaction(&Interaction {
+                id: id.to_string(),
+                author: author.to_string(),
+                kind: InteractionKind::Vote(choices),
+                target_post_id: "poll".to_string(),
+                target_author: "alice".to_string(),
+                timestamp,
+                signature: String::new(),
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_poll_counts_one_vote_per_voter() {
+        let storage = Storage::open(":memory:").unwrap();
+        poll_post(&storage, false);
+        vote(&storage, "v1", "bob", vec![0], 2_000);
+        vote(&storage, "v2", "bob", vec![1], 3_000);
+        vote(&storage, "v3", "carol", vec![1], 4_000);
+
+        let results = storage.get_poll_results("bob", "poll").unwrap().unwrap();
+        assert_eq!(results.counts, [1, 1]);
+        assert_eq!(results.voters, 2);
+        assert_eq!(results.my_vote, Some(vec![0]));
+        assert!(results.closed);
+    }
+
+    #[test]
+    fn test_poll_ignores_invalid_and_late_votes() {
+        let storage = Storage::open(":memory:").unwrap();
+        poll_post(&storage, true);
+        vote(&storage, "v1", "bob", vec![2], 2_000);
+        vote(&storage, "v2", "carol", vec![1, 1], 2_000);
+        vote(&storage, "v3", "dave", vec![], 2_000);
+        vote(&storage, "v4", "erin", vec![0], 20_000);
+        vote(&storage, "v5", "frank", vec![0, 1], 3_000);
+
+        let results = storage.get_poll_results("bob", "poll").unwrap().unwrap();
+        assert_eq!(results.counts, [1, 1]);
+        assert_eq!(results.voters, 1);
+        assert_eq!(results.my_vote, None);
+        assert!(
+            storage
+                .get_poll_results("bob", "missing")
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use iroh_social_types::{CustomFeed, UserList};
+use rusqlite::{Connection, Row, params};
+
+use super::Storage;
+
+const CUSTOM_FEED_COLUMNS: &str =
+    "id, name, list_ids_json, hashtags_json, media_only, exclude_replies, created_at";
+
+impl Storage {
+    fn list_members(db: &Connection, list_id: &str) -> anyhow::Result<Vec<String>> {
+        let mut stmt = db.prepare_cached(
+            "SELECT pubkey FROM list_members WHERE list_id=?1 ORDER BY added_at ASC",
+        )?;
+        let rows = stmt.query_map(params![list_id], |row| row.get(0))?;
+        Ok(rows.collect::<Result<Vec<String>, _>>()?)
+    }
+
+    fn set_list_members(
+        db: &Connection,
+        list_id: &str,
+        members: &[String],
+        now: u64,
+    ) -> anyhow::Result<()> {
+        db.execute(
+            "DELETE FROM list_members WHERE list_id=?1",
+            params![list_id],
+        )?;
+        let mut stmt = db.prepare_cached(
+            "INSERT OR IGNORE INTO list_members (list_id, pubkey, added_at)
+             VALUES (?1, ?2, ?3)",
+        )?;
+        for pubkey in members {
+            stmt.execute(params![list_id, pubkey, now as i64])?;
+        }
+        Ok(())
+    }
+
+    pub fn create_list(&self, list: &UserList) -> anyhow::Result<()> {
+        let mut db = self.db.lock().unwrap();
+        let tx = db.transaction()?;
+        tx.execute(
+            "INSERT INTO lists (id, name, created_at) VALUES (?1, ?2, ?3)",
+            params![list.id, list.name, list.created_at as i64],
+        )?;
+        Self::set_list_members(&tx, &list.id, &list.members, list.created_at)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Rename a list and replace its members. Returns false if it doesn't
+    /// exist.
+    pub fn update_list(
+        &self,
+        id: &str,
+        name: &str,
+        members: &[String],
+        now: u64,
+    ) -> anyhow::Result<bool> {
+        let mut db = self.db.lock().unwrap();
+        let tx = db.transaction()?;
+        if tx.execute("UPDATE lists SET name=?2 WHERE id=?1", params![id, name])? == 0 {
+            return Ok(false);
+        }
+        Self::set_list_members(&tx, id, members, now)?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    pub fn delete_list(&self, id: &str) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let count = db.execute("DELETE FROM lists WHERE id=?1", params![id])?;
+        Ok(count > 0)
+    }
+
+    pub fn get_list(&self, id: &str) -> anyhow::Result<Option<UserList>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare("SELECT id, name, created_at FROM lists WHERE id=?1")?;
+        let mut rows = stmt.query(params![id])?;
+        let Some(row) = rows.next()? else {
+            return Ok(None);
+        };
+        Ok(Some(UserList {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            members: Self::list_members(&db, id)?,
+            created_at: row.get::<_, i64>(2)? as u64,
+        }))
+    }
+
+    pub fn get_lists(&self) -> anyhow::Result<Vec<UserList>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt =
+            db.prepare("SELECT id, name, created_at FROM lists ORDER BY created_at ASC")?;
+        let mut rows = stmt.query([])?;
+        let mut lists = Vec::new();
+        while let Some(row) = rows.next()? {
+            let id: String = row.get(0)?;
+            lists.push(UserList {
+                members: Self::list_members(&db, &id)?,
+                id,
+                name: row.get(1)?,
+                created_at: row.get::<_, i64>(2)? as u64,
+            });
+        }
+        Ok(lists)
+    }
+
+    /// Everyone we sync posts from: our follows, newest first, then members
+    /// of our lists we don't follow, since nothing else brings us their posts.
+    pub fn get_sync_peers(&self) -> anyhow::Result<Vec<String>> {
+        let mut peers: Vec<String> = self.get_follows()?.into_iter().map(|f| f.pubkey).collect();
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(
+            "SELECT DISTINCT pubkey FROM list_members
+             WHERE pubkey NOT IN (SELECT pubkey FROM follows)
+             ORDER BY pubkey",
+        )?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        peers.extend(rows.collect::<Result<Vec<String>, _>>()?);
+        Ok(peers)
+    }
+
+    fn row_to_custom_feed(row: &Row) -> anyhow::Result<CustomFeed> {
+        let list_ids_json: String = row.get(2)?;
+        let hashtags_json: String = row.get(3)?;
+        Ok(CustomFeed {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            list_ids: serde_json::from_str(&list_ids_json).unwrap_or_default(),
+            hashtags: serde_json::from_str(&hashtags_json).unwrap_or_default(),
+            media_only: row.get::<_, i64>(4)? != 0,
+            exclude_replies: row.get::<_, i64>(5)? != 0,
+            created_at: row.get::<_, i64>(6)? as u64,
+        })
+    }
+
+    pub fn create_custom_feed(&self, feed: &CustomFeed) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "INSERT INTO custom_feeds
+             (id, name, list_ids_json, hashtags_json, media_only, exclude_replies, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                feed.id,
+                feed.name,
+                serde_json::to_string(&feed.list_ids)?,
+                serde_json::to_string(&feed.hashtags)?,
+                feed.media_only as i64,
+                feed.exclude_replies as i64,
+                feed.created_at as i64,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Replace a custom feed's name and filters. Returns false if it doesn't
+    /// exist.
+    pub fn update_custom_feed(&self, feed: &CustomFeed) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let count = db.execute(
+            "UPDATE custom_feeds SET name=?2, list_ids_json=?3, hashtags_json=?4,
+                 media_only=?5, exclude_replies=?6
+             WHERE id=?1",
+            params![
+                feed.id,
+                feed.name,
+                serde_json::to_string(&feed.list_ids)?,
+                serde_json::to_string(&feed.hashtags)?,
+                feed.media_only as i64,
+                feed.exclude_replies as i64,
+            ],
+        )?;
+        Ok(count > 0)
+    }
+
+    pub fn delete_custom_feed(&self, id: &str) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let count = db.execute("DELETE FROM custom_feeds WHERE id=?1", params![id])?;
+        Ok(count > 0)
+    }
+
+    pub fn get_custom_feed(&self, id: &str) -> anyhow::Result<Option<CustomFeed>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(&format!(
+            "SELECT {CUSTOM_FEED_COLUMNS} FROM custom_feeds WHERE id=?1"
+        ))?;
+        let mut rows = stmt.query(params![id])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(Self::row_to_custom_feed(row)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_custom_feeds(&self) -> anyhow::Result<Vec<CustomFeed>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(&format!(
+            "SELECT {CUSTOM_FEED_COLUMNS} FROM custom_feeds ORDER BY created_at ASC"
+        ))?;
+        let mut rows = stmt.query([])?;
+        let mut feeds = Vec::new();
+        while let Some(row) = rows.next()? {
+            feeds.push(Self::row_to_custom_feed(row)?);
+        }
+        Ok(feeds)
+    }
+}
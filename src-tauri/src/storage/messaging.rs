@@ -1,8 +1,8 @@
 use iroh_social_types::{ConversationMeta, MediaAttachment, StoredMessage};
-use rusqlite::params;
+use rusqlite::{OptionalExtension, params};
 use sha2::{Digest, Sha256};
 
-use super::Storage;
+use super::{OutboxEntry, Storage};
 
 impl Storage {
     pub fn conversation_id(pubkey_a: &str, pubkey_b: &str) -> String {
@@ -16,31 +16,40 @@ impl Storage {
         format!("{:x}", hasher.finalize())
     }
 
+    /// Create or bump a conversation. `is_request` only holds a new
+    /// conversation as a message request; passing `false` for an existing
+    /// request (e.g. when we reply) accepts it.
     pub fn upsert_conversation(
         &self,
         peer_pubkey: &str,
         my_pubkey: &str,
         last_message_at: u64,
         preview: &str,
+        is_request: bool,
     ) -> anyhow::Result<()> {
         let conv_id = Self::conversation_id(my_pubkey, peer_pubkey);
         let db = self.db.lock().unwrap();
         db.execute(
-            "INSERT INTO dm_conversations (conversation_id, peer_pubkey, last_message_at, last_message_preview, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?3)
-             ON CONFLICT(conversation_id) DO UPDATE SET last_message_at=?3, last_message_preview=?4",
-            params![conv_id, peer_pubkey, last_message_at as i64, preview],
+            "INSERT INTO dm_conversations (conversation_id, peer_pubkey, last_message_at, last_message_preview, created_at, is_request)
+             VALUES (?1, ?2, ?3, ?4, ?3, ?5)
+             ON CONFLICT(conversation_id) DO UPDATE SET last_message_at=?3, last_message_preview=?4,
+                 is_request = is_request AND ?5",
+            params![conv_id, peer_pubkey, last_message_at as i64, preview, is_request as i32],
         )?;
         Ok(())
     }
 
-    pub fn get_conversations(&self) -> anyhow::Result<Vec<ConversationMeta>> {
+    /// Accepted conversations, or pending message requests.
+    pub fn get_conversations(&self, requests: bool) -> anyhow::Result<Vec<ConversationMeta>> {
         let db = self.db.lock().unwrap();
         let mut stmt = db.prepare(
-            "SELECT peer_pubkey, last_message_at, last_message_preview, unread_count
-             FROM dm_conversations ORDER BY last_message_at DESC",
+            "SELECT c.peer_pubkey, c.last_message_at, c.last_message_preview, c.unread_count, c.verified,
+                    EXISTS(SELECT 1 FROM follows f JOIN verified_aliases va ON va.alias = f.alias
+                           WHERE f.pubkey = c.peer_pubkey AND va.pubkey != c.peer_pubkey),
+                    c.is_request
+             FROM dm_conversations c WHERE c.is_request=?1 ORDER BY c.last_message_at DESC",
         )?;
-        let mut rows = stmt.query([])?;
+        let mut rows = stmt.query(params![requests as i32])?;
         let mut convos = Vec::new();
         while let Some(row) = rows.next()? {
             convos.push(ConversationMeta {
@@ -48,11 +57,123 @@ impl Storage {
                 last_message_at: row.get::<_, i64>(1)? as u64,
                 last_message_preview: row.get(2)?,
                 unread_count: row.get::<_, i32>(3)? as u32,
+                verified: row.get::<_, i32>(4)? != 0,
+                identity_changed: row.get::<_, i32>(5)? != 0,
+                is_request: row.get::<_, i32>(6)? != 0,
             });
         }
         Ok(convos)
     }
 
+    pub fn is_message_request(&self, peer_pubkey: &str, my_pubkey: &str) -> anyhow::Result<bool> {
+        let conv_id = Self::conversation_id(my_pubkey, peer_pubkey);
+        let db = self.db.lock().unwrap();
+        let is_request: Option<i32> = db
+            .query_row(
+                "SELECT is_request FROM dm_conversations WHERE conversation_id=?1",
+                params![conv_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(is_request.unwrap_or(0) != 0)
+    }
+
+    /// Move a message request into the inbox. Returns false if there was none.
+    pub fn accept_message_request(
+        &self,
+        peer_pubkey: &str,
+        my_pubkey: &str,
+    ) -> anyhow::Result<bool> {
+        let conv_id = Self::conversation_id(my_pubkey, peer_pubkey);
+        let db = self.db.lock().unwrap();
+        let count = db.execute(
+            "UPDATE dm_conversations SET is_request=0 WHERE conversation_id=?1 AND is_request=1",
+            params![conv_id],
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Delete a conversation and all its messages.
+    pub fn delete_conversation(&self, peer_pubkey: &str, my_pubkey: &str) -> anyhow::Result<()> {
+        let conv_id = Self::conversation_id(my_pubkey, peer_pubkey);
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "DELETE FROM dm_messages WHERE conversation_id=?1",
+            params![conv_id],
+        )?;
+        db.execute(
+            "DELETE FROM dm_conversations WHERE conversation_id=?1",
+            params![conv_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn is_conversation_verified(
+        &self,
+        peer_pubkey: &str,
+        my_pubkey: &str,
+    ) -> anyhow::Result<bool> {
+        let conv_id = Self::conversation_id(my_pubkey, peer_pubkey);
+        let db = self.db.lock().unwrap();
+        let verified: Option<i32> = db
+            .query_row(
+                "SELECT verified FROM dm_conversations WHERE conversation_id=?1",
+                params![conv_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(verified.unwrap_or(0) != 0)
+    }
+
+    /// Mark an existing conversation verified or unverified. Verifying also
+    /// pins the peer's current follow alias to its key, so a later follow
+    /// reusing that alias for a different key can be flagged. Returns false,
+    /// changing nothing, when there is no conversation with the peer.
+    pub fn set_conversation_verified(
+        &self,
+        peer_pubkey: &str,
+        my_pubkey: &str,
+        verified: bool,
+        now: u64,
+    ) -> anyhow::Result<bool> {
+        let conv_id = Self::conversation_id(my_pubkey, peer_pubkey);
+        let db = self.db.lock().unwrap();
+        let updated = db.execute(
+            "UPDATE dm_conversations SET verified=?2, verified_at=?3 WHERE conversation_id=?1",
+            params![conv_id, verified as i32, verified.then_some(now as i64)],
+        )?;
+        if updated == 0 {
+            return Ok(false);
+        }
+        if verified {
+            db.execute(
+                "INSERT INTO verified_aliases (alias, pubkey, verified_at)
+                 SELECT alias, pubkey, ?2 FROM follows WHERE pubkey=?1 AND alias IS NOT NULL AND alias != ''
+                 ON CONFLICT(alias) DO UPDATE SET pubkey=excluded.pubkey, verified_at=excluded.verified_at",
+                params![peer_pubkey, now as i64],
+            )?;
+        } else {
+            db.execute(
+                "DELETE FROM verified_aliases WHERE pubkey=?1",
+                params![peer_pubkey],
+            )?;
+        }
+        Ok(true)
+    }
+
+    /// The verified identity previously known by `alias`, if it is not `pubkey`.
+    pub fn get_alias_conflict(&self, alias: &str, pubkey: &str) -> anyhow::Result<Option<String>> {
+        let db = self.db.lock().unwrap();
+        let previous = db
+            .query_row(
+                "SELECT pubkey FROM verified_aliases WHERE alias=?1 AND pubkey != ?2",
+                params![alias, pubkey],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(previous)
+    }
+
     pub fn increment_unread(&self, conversation_id: &str) -> anyhow::Result<()> {
         let db = self.db.lock().unwrap();
         db.execute(
@@ -80,8 +201,8 @@ impl Storage {
         let db = self.db.lock().unwrap();
         let media_json = serde_json::to_string(&msg.media)?;
         db.execute(
-            "INSERT OR IGNORE INTO dm_messages (id, conversation_id, from_pubkey, to_pubkey, content, timestamp, media_json, read, delivered, reply_to)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            "INSERT OR IGNORE INTO dm_messages (id, conversation_id, from_pubkey, to_pubkey, content, timestamp, media_json, read, delivered, reply_to, failed)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
             params![
                 msg.id,
                 msg.conversation_id,
@@ -93,6 +214,7 @@ impl Storage {
                 msg.read as i32,
                 msg.delivered as i32,
                 msg.reply_to,
+                msg.failed as i32,
             ],
         )?;
         Ok(())
@@ -109,7 +231,7 @@ impl Storage {
         match before {
             Some(b) => {
                 let mut stmt = db.prepare(
-                    "SELECT id, conversation_id, from_pubkey, to_pubkey, content, timestamp, media_json, read, delivered, reply_to
+                    "SELECT id, conversation_id, from_pubkey, to_pubkey, content, timestamp, media_json, read, delivered, reply_to, failed
                      FROM dm_messages WHERE conversation_id=?1 AND timestamp < ?2
                      ORDER BY timestamp DESC LIMIT ?3",
                 )?;
@@ -120,7 +242,7 @@ impl Storage {
             }
             None => {
                 let mut stmt = db.prepare(
-                    "SELECT id, conversation_id, from_pubkey, to_pubkey, content, timestamp, media_json, read, delivered, reply_to
+                    "SELECT id, conversation_id, from_pubkey, to_pubkey, content, timestamp, media_json, read, delivered, reply_to, failed
                      FROM dm_messages WHERE conversation_id=?1
                      ORDER BY timestamp DESC LIMIT ?2",
                 )?;
@@ -148,6 +270,7 @@ impl Storage {
             read: row.get::<_, i32>(7)? != 0,
             delivered: row.get::<_, i32>(8)? != 0,
             reply_to: row.get(9)?,
+            failed: row.get::<_, i32>(10)? != 0,
         })
     }
 
@@ -178,7 +301,7 @@ impl Storage {
     pub fn get_total_unread_count(&self) -> anyhow::Result<u32> {
         let db = self.db.lock().unwrap();
         let count: i64 = db.query_row(
-            "SELECT COALESCE(SUM(unread_count), 0) FROM dm_conversations",
+            "SELECT COALESCE(SUM(unread_count), 0) FROM dm_conversations WHERE is_request=0",
             [],
             |row| row.get(0),
         )?;
@@ -208,25 +331,67 @@ impl Storage {
         Ok(())
     }
 
-    pub fn get_outbox_for_peer(
+    /// Queue a payload we can't seal yet because we have no session with
+    /// the peer. The flush seals it once a handshake succeeds.
+    pub fn insert_outbox_payload(
         &self,
+        id: &str,
         peer_pubkey: &str,
-    ) -> anyhow::Result<Vec<(String, String, String)>> {
+        payload_json: &str,
+        created_at: u64,
+        message_id: &str,
+    ) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "INSERT INTO dm_outbox (id, peer_pubkey, envelope_json, payload_json, created_at, message_id)
+             VALUES (?1, ?2, '', ?3, ?4, ?5)",
+            params![
+                id,
+                peer_pubkey,
+                payload_json,
+                created_at as i64,
+                message_id
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Store the envelope a queued payload was sealed into, so every retry
+    /// resends the same one.
+    pub fn seal_outbox_message(&self, id: &str, envelope_json: &str) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "UPDATE dm_outbox SET envelope_json=?2 WHERE id=?1",
+            params![id, envelope_json],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_outbox_for_peer(&self, peer_pubkey: &str) -> anyhow::Result<Vec<OutboxEntry>> {
         let db = self.db.lock().unwrap();
         let mut stmt = db.prepare(
-            "SELECT id, envelope_json, message_id FROM dm_outbox WHERE peer_pubkey=?1 ORDER BY created_at ASC",
+            "SELECT id, envelope_json, payload_json, message_id, retry_count, next_retry_at
+             FROM dm_outbox WHERE peer_pubkey=?1 AND failed=0 ORDER BY created_at ASC",
         )?;
         let mut rows = stmt.query(params![peer_pubkey])?;
         let mut entries = Vec::new();
         while let Some(row) = rows.next()? {
-            entries.push((row.get(0)?, row.get(1)?, row.get(2)?));
+            let envelope_json: String = row.get(1)?;
+            entries.push(OutboxEntry {
+                id: row.get(0)?,
+                envelope_json: (!envelope_json.is_empty()).then_some(envelope_json),
+                payload_json: row.get(2)?,
+                message_id: row.get(3)?,
+                retry_count: row.get::<_, i64>(4)? as u32,
+                next_retry_at: row.get::<_, i64>(5)? as u64,
+            });
         }
         Ok(entries)
     }
 
     pub fn get_all_outbox_peers(&self) -> anyhow::Result<Vec<String>> {
         let db = self.db.lock().unwrap();
-        let mut stmt = db.prepare("SELECT DISTINCT peer_pubkey FROM dm_outbox")?;
+        let mut stmt = db.prepare("SELECT DISTINCT peer_pubkey FROM dm_outbox WHERE failed=0")?;
         let mut rows = stmt.query([])?;
         let mut peers = Vec::new();
         while let Some(row) = rows.next()? {
@@ -235,9 +400,313 @@ impl Storage {
         Ok(peers)
     }
 
+    /// Peers with pending outbox entries whose backoff has elapsed.
+    pub fn get_due_outbox_peers(&self, now: u64) -> anyhow::Result<Vec<String>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(
+            "SELECT DISTINCT peer_pubkey FROM dm_outbox WHERE failed=0 AND next_retry_at <= ?1",
+        )?;
+        let mut rows = stmt.query(params![now as i64])?;
+        let mut peers = Vec::new();
+        while let Some(row) = rows.next()? {
+            peers.push(row.get(0)?);
+        }
+        Ok(peers)
+    }
+
+    /// Record a failed delivery attempt for one outbox entry.
+    pub fn record_outbox_failure(
+        &self,
+        id: &str,
+        retry_count: u32,
+        now: u64,
+        next_retry_at: u64,
+    ) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "UPDATE dm_outbox SET retry_count=?2, last_retry_at=?3, next_retry_at=?4
+             WHERE id=?1",
+            params![id, retry_count as i64, now as i64, next_retry_at as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Mark pending entries created before `cutoff` (and their messages) as failed.
+    /// Returns the `(peer_pubkey, message_id)` of each expired entry.
+    pub fn expire_outbox_before(&self, cutoff: u64) -> anyhow::Result<Vec<(String, String)>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(
+            "SELECT peer_pubkey, message_id FROM dm_outbox WHERE failed=0 AND created_at < ?1",
+        )?;
+        let mut rows = stmt.query(params![cutoff as i64])?;
+        let mut expired = Vec::new();
+        while let Some(row) = rows.next()? {
+            expired.push((row.get(0)?, row.get(1)?));
+        }
+        drop(rows);
+        drop(stmt);
+
+        db.execute(
+            "UPDATE dm_messages SET failed=1
+             WHERE id IN (SELECT message_id FROM dm_outbox WHERE failed=0 AND created_at < ?1)",
+            params![cutoff as i64],
+        )?;
+        db.execute(
+            "UPDATE dm_outbox SET failed=1 WHERE failed=0 AND created_at < ?1",
+            params![cutoff as i64],
+        )?;
+        Ok(expired)
+    }
+
+    /// Put a failed or backed-off message back in the queue for immediate retry.
+    /// Resets its backoff and restarts its expiry clock.
+    /// Returns the peer it is addressed to, or `None` if it is not in the outbox.
+    pub fn requeue_outbox_message(
+        &self,
+        message_id: &str,
+        now: u64,
+    ) -> anyhow::Result<Option<String>> {
+        let db = self.db.lock().unwrap();
+        let peer: Option<String> = db
+            .query_row(
+                "SELECT peer_pubkey FROM dm_outbox WHERE message_id=?1",
+                params![message_id],
+                |row| row.get(0),
+            )
+            .ok();
+        if peer.is_some() {
+            db.execute(
+                "UPDATE dm_outbox SET failed=0, retry_count=0, next_retry_at=0, created_at=?2
+                 WHERE message_id=?1",
+                params![message_id, now as i64],
+            )?;
+            db.execute(
+                "UPDATE dm_messages SET failed=0 WHERE id=?1",
+                params![message_id],
+            )?;
+        }
+        Ok(peer)
+    }
+
     pub fn remove_outbox_message(&self, id: &str) -> anyhow::Result<()> {
         let db = self.db.lock().unwrap();
         db.execute("DELETE FROM dm_outbox WHERE id=?1", params![id])?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_needs_an_existing_conversation() {
+        let storage = Storage::open(":memory:").unwrap();
+        assert!(
+            !storage
+                .set_conversation_verified("peer", "me", true, 1)
+                .unwrap()
+        );
+        assert!(storage.get_conversations(false).unwrap().is_empty());
+        assert!(storage.get_conversations(true).unwrap().is_empty());
+
+        storage
+            .upsert_conversation("peer", "me", 5, "hi", false)
+            .unwrap();
+        assert!(
+            storage
+                .set_conversation_verified("peer", "me", true, 6)
+                .unwrap()
+        );
+        assert!(storage.is_conversation_verified("peer", "me").unwrap());
+        let convos = storage.get_conversations(false).unwrap();
+        assert_eq!(convos.len(), 1);
+        assert_eq!(convos[0].last_message_preview, "hi");
+    }
+
+    #[test]
+    fn test_message_requests_stay_apart_until_accepted() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage
+            .upsert_conversation("stranger", "me", 5, "hello?", true)
+            .unwrap();
+        storage
+            .upsert_conversation("friend", "me", 6, "hi", false)
+            .unwrap();
+        assert!(storage.is_message_request("stranger", "me").unwrap());
+        assert!(!storage.is_message_request("friend", "me").unwrap());
+        let requests = storage.get_conversations(true).unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].peer_pubkey, "stranger");
+        assert!(requests[0].is_request);
+
+        // A later message under a stricter policy doesn't demote an accepted
+        // conversation back into requests.
+        storage
+            .upsert_conversation("friend", "me", 7, "again", true)
+            .unwrap();
+        assert!(!storage.is_message_request("friend", "me").unwrap());
+
+        assert!(storage.accept_message_request("stranger", "me").unwrap());
+        assert!(!storage.accept_message_request("stranger", "me").unwrap());
+        assert!(storage.get_conversations(true).unwrap().is_empty());
+        assert_eq!(storage.get_conversations(false).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_declining_a_request_deletes_its_messages() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage
+            .upsert_conversation("peer", "me", 1, "hi", true)
+            .unwrap();
+        storage.insert_dm_message(&message("m1")).unwrap();
+        storage.delete_conversation("peer", "me").unwrap();
+        assert!(storage.get_conversations(true).unwrap().is_empty());
+        let conv_id = Storage::conversation_id("me", "peer");
+        assert!(
+            storage
+                .get_dm_messages(&conv_id, 10, None)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    fn message(id: &str) -> StoredMessage {
+        StoredMessage {
+            id: id.to_string(),
+            conversation_id: Storage::conversation_id("me", "peer"),
+            from_pubkey: "me".to_string(),
+            to_pubkey: "peer".to_string(),
+            content: format!("message {id}"),
+            timestamp: 1,
+            media: vec![],
+            read: false,
+            delivered: false,
+            reply_to: None,
+            failed: false,
+        }
+    }
+
+    fn retry_counts(storage: &Storage) -> Vec<(String, u32)> {
+        let mut counts: Vec<(String, u32)> = storage
+            .get_outbox_for_peer("peer")
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.message_id, e.retry_count))
+            .collect();
+        counts.sort();
+        counts
+    }
+
+    #[test]
+    fn test_outbox_entries_back_off_on_their_own() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage
+            .insert_outbox_message("o1", "peer", "{}", 1, "m1")
+            .unwrap();
+        storage.record_outbox_failure("o1", 3, 10, 500).unwrap();
+        storage
+            .insert_outbox_message("o2", "peer", "{}", 20, "m2")
+            .unwrap();
+
+        // A new entry starts fresh instead of inheriting the peer's backoff
+        assert_eq!(
+            retry_counts(&storage),
+            [("m1".to_string(), 3), ("m2".to_string(), 0)]
+        );
+        assert_eq!(storage.get_due_outbox_peers(100).unwrap(), ["peer"]);
+        storage.record_outbox_failure("o2", 1, 100, 200).unwrap();
+        assert!(storage.get_due_outbox_peers(150).unwrap().is_empty());
+        assert_eq!(storage.get_due_outbox_peers(200).unwrap(), ["peer"]);
+
+        // Retrying one message by hand resets only that one
+        assert_eq!(
+            storage
+                .requeue_outbox_message("m1", 300)
+                .unwrap()
+                .as_deref(),
+            Some("peer")
+        );
+        assert_eq!(
+            retry_counts(&storage),
+            [("m1".to_string(), 0), ("m2".to_string(), 1)]
+        );
+        assert_eq!(
+            storage.requeue_outbox_message("missing", 300).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_expired_outbox_entries_fail_their_messages() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage
+            .upsert_conversation("peer", "me", 1, "hi", false)
+            .unwrap();
+        for (id, created_at) in [("old", 1), ("new", 100)] {
+            storage.insert_dm_message(&message(id)).unwrap();
+            storage
+                .insert_outbox_message(&format!("o-{id}"), "peer", "{}", created_at, id)
+                .unwrap();
+        }
+
+        assert_eq!(
+            storage.expire_outbox_before(50).unwrap(),
+            [("peer".to_string(), "old".to_string())]
+        );
+        assert!(storage.expire_outbox_before(50).unwrap().is_empty());
+        assert_eq!(retry_counts(&storage), [("new".to_string(), 0)]);
+        let failed: Vec<(String, bool)> = storage
+            .get_dm_messages(&Storage::conversation_id("me", "peer"), 10, None)
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.id, m.failed))
+            .collect();
+        assert!(failed.contains(&("old".to_string(), true)));
+        assert!(failed.contains(&("new".to_string(), false)));
+
+        // Retrying an expired message puts it back in the queue
+        storage.requeue_outbox_message("old", 200).unwrap();
+        assert_eq!(storage.get_outbox_for_peer("peer").unwrap().len(), 2);
+        assert!(
+            storage
+                .get_dm_messages(&Storage::conversation_id("me", "peer"), 10, None)
+                .unwrap()
+                .iter()
+                .all(|m| !m.failed)
+        );
+    }
+
+    #[test]
+    fn test_outbox_payload_is_sealed_once() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage
+            .insert_outbox_message("sealed", "peer", "{\"env\":1}", 1, "msg-1")
+            .unwrap();
+        storage
+            .insert_outbox_payload("queued", "peer", "{\"post\":1}", 2, "")
+            .unwrap();
+        storage
+            .insert_outbox_payload("other", "carol", "{}", 3, "")
+            .unwrap();
+
+        let entries = storage.get_outbox_for_peer("peer").unwrap();
+        let ids: Vec<&str> = entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, ["sealed", "queued"]);
+        assert_eq!(entries[0].envelope_json.as_deref(), Some("{\"env\":1}"));
+        assert_eq!(entries[0].payload_json, None);
+        assert_eq!(entries[0].message_id, "msg-1");
+        assert_eq!(entries[1].envelope_json, None);
+        assert_eq!(entries[1].payload_json.as_deref(), Some("{\"post\":1}"));
+        assert_eq!(entries[1].message_id, "");
+
+        // A queued payload is due like any other entry, and keeps its
+        // envelope once sealed
+        assert_eq!(storage.get_due_outbox_peers(0).unwrap().len(), 2);
+        storage
+            .seal_outbox_message("queued", "{\"env\":2}")
+            .unwrap();
+        let entries = storage.get_outbox_for_peer("peer").unwrap();
+        assert_eq!(entries[1].envelope_json.as_deref(), Some("{\"env\":2}"));
+    }
+}
@@ -1,29 +1,221 @@
+mod audiences;
 mod crypto;
+mod drafts;
+mod feed_keys;
 mod interactions;
+mod lists;
 mod messaging;
 mod moderation;
+mod muted_words;
 mod notifications;
 mod posts;
 mod profiles;
+mod revisions;
+mod settings;
 mod social;
+#[cfg(test)]
+pub(crate) mod test_util;
 
+use iroh_social_types::{MediaAttachment, Poll, Post};
+use muted_words::WordFilters;
 use rusqlite::{Connection, params};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::sync::Mutex;
 
+/// A post as this node shows it: the signed `Post` plus local state that
+/// never goes out with it. Serializes as the post's fields with these
+/// alongside.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostView {
+    #[serde(flatten)]
+    pub post: Post,
+    /// The reader's `SensitiveMode` for this author calls for the post to
+    /// start collapsed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub collapsed: bool,
+    /// Set when `content`/`media` come from the latest `PostEdit`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<u64>,
+    /// Shown as one of its author's pinned posts.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// On a repost standing in for every repost of the same target in the
+    /// feed: their authors, newest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reposted_by: Vec<String>,
+    /// Name of the audience list the author shared the post with; `None`
+    /// for public posts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audience: Option<String>,
+    /// The pattern of the reader's muted word that matched. The post is
+    /// shown collapsed behind it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filtered_by: Option<String>,
+}
+
+impl PostView {
+    pub fn new(post: Post) -> Self {
+        Self {
+            post,
+            collapsed: false,
+            edited_at: None,
+            pinned: false,
+            reposted_by: vec![],
+            audience: None,
+            filtered_by: None,
+        }
+    }
+}
+
+impl std::ops::Deref for PostView {
+    type Target = Post;
+
+    fn deref(&self) -> &Post {
+        &self.post
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PostCounts {
     pub likes: u32,
     pub replies: u32,
     pub reposts: u32,
+    pub quotes: u32,
     pub liked_by_me: bool,
     pub reposted_by_me: bool,
+    /// Per-emoji reaction counts, most used first.
+    pub reactions: Vec<ReactionCount>,
+}
+
+/// What the ranked feed knows about a post, all from local data.
+#[derive(Debug, Clone, Default)]
+pub struct RankSignals {
+    pub timestamp: u64,
+    /// Likes from people we follow.
+    pub followed_likes: u32,
+    /// Reposts and quotes by people we follow.
+    pub followed_reposts: u32,
+    pub replies: u32,
+    /// Our likes of any of the author's posts.
+    pub author_likes: u32,
+    /// Our replies to the author.
+    pub author_replies: u32,
+}
+
+/// Local tally of a post's poll from the votes this node holds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PollResults {
+    /// Votes per option, in option order.
+    pub counts: Vec<u32>,
+    pub voters: u32,
+    pub my_vote: Option<Vec<u32>>,
+    pub closed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReactionCount {
+    pub emoji: String,
+    pub count: u32,
+    pub reacted_by_me: bool,
 }
 
+/// The author-controlled fields of a post, before it gets an id, timestamp
+/// and signature. Drafts and scheduled posts store this.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostBody {
+    pub content: String,
+    #[serde(default)]
+    pub media: Vec<MediaAttachment>,
+    #[serde(default)]
+    pub reply_to: Option<String>,
+    #[serde(default)]
+    pub reply_to_author: Option<String>,
+    #[serde(default)]
+    pub quote_of: Option<String>,
+    #[serde(default)]
+    pub quote_of_author: Option<String>,
+    #[serde(default)]
+    pub poll: Option<Poll>,
+    #[serde(default)]
+    pub content_warning: Option<String>,
+    #[serde(default)]
+    pub sensitive_media: bool,
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Draft {
+    pub id: String,
+    pub body: PostBody,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledPost {
+    /// Becomes the id of the published post.
+    pub id: String,
+    pub body: PostBody,
+    pub publish_at: u64,
+    pub created_at: u64,
+    pub last_error: Option<String>,
+}
+
+/// Which replies a feed shows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplyFilter {
+    #[default]
+    All,
+    None,
+    /// Only replies to people we follow.
+    Following,
+}
+
+/// Filters the user picks for the home feed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeedFilters {
+    pub replies: ReplyFilter,
+    pub exclude_reposts: bool,
+    pub media_only: bool,
+    pub hide_own: bool,
+}
+
+#[derive(Default)]
 pub struct FeedQuery {
     pub limit: usize,
+    /// Cursor: posts older than this timestamp, or at it with a smaller id
+    /// than `before_id`, so posts sharing a millisecond aren't skipped.
     pub before: Option<u64>,
+    pub before_id: Option<String>,
+    /// Only posts by members of these lists, when any are given.
+    pub list_ids: Vec<String>,
+    /// Only posts carrying one of these normalized hashtags, when any are given.
+    pub hashtags: Vec<String>,
+    pub media_only: bool,
+    pub replies: ReplyFilter,
+    pub exclude_reposts: bool,
+    /// Leave out posts by this author, normally ourselves.
+    pub exclude_author: Option<String>,
+}
+
+/// A pending outbox entry for one peer.
+#[derive(Debug, Clone)]
+pub struct OutboxEntry {
+    pub id: String,
+    /// The sealed envelope, or `None` until a session with the peer lets
+    /// the flush seal `payload_json`.
+    pub envelope_json: Option<String>,
+    /// The payload, for entries queued before they could be sealed.
+    pub payload_json: Option<String>,
+    /// Empty for payloads that are not a stored message.
+    pub message_id: String,
+    /// Failed delivery attempts so far; each entry backs off on its own.
+    pub retry_count: u32,
+    pub next_retry_at: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,6 +231,7 @@ pub struct Notification {
 
 pub struct Storage {
     db: Mutex<Connection>,
+    word_filters: Mutex<WordFilters>,
 }
 
 impl std::fmt::Debug for Storage {
@@ -82,6 +275,91 @@ impl Storage {
             "009_notifications",
             include_str!("../../migrations/009_notifications.sql"),
         ),
+        (
+            "010_dm_outbox_retry",
+            include_str!("../../migrations/010_dm_outbox_retry.sql"),
+        ),
+        (
+            "011_dm_verification",
+            include_str!("../../migrations/011_dm_verification.sql"),
+        ),
+        (
+            "012_message_requests",
+            include_str!("../../migrations/012_message_requests.sql"),
+        ),
+        (
+            "013_reactions",
+            include_str!("../../migrations/013_reactions.sql"),
+        ),
+        ("014_polls", include_str!("../../migrations/014_polls.sql")),
+        (
+            "015_post_revisions",
+            include_str!("../../migrations/015_post_revisions.sql"),
+        ),
+        (
+            "016_content_warnings",
+            include_str!("../../migrations/016_content_warnings.sql"),
+        ),
+        (
+            "017_drafts_scheduled",
+            include_str!("../../migrations/017_drafts_scheduled.sql"),
+        ),
+        (
+            "018_pinned_posts",
+            include_str!("../../migrations/018_pinned_posts.sql"),
+        ),
+        (
+            "019_ephemeral_posts",
+            include_str!("../../migrations/019_ephemeral_posts.sql"),
+        ),
+        (
+            "020_link_previews",
+            include_str!("../../migrations/020_link_previews.sql"),
+        ),
+        (
+            "021_post_kinds",
+            include_str!("../../migrations/021_post_kinds.sql"),
+        ),
+        (
+            "022_follow_requests",
+            include_str!("../../migrations/022_follow_requests.sql"),
+        ),
+        (
+            "023_feed_keys",
+            include_str!("../../migrations/023_feed_keys.sql"),
+        ),
+        (
+            "024_audiences",
+            include_str!("../../migrations/024_audiences.sql"),
+        ),
+        (
+            "025_lists_custom_feeds",
+            include_str!("../../migrations/025_lists_custom_feeds.sql"),
+        ),
+        (
+            "026_muted_words",
+            include_str!("../../migrations/026_muted_words.sql"),
+        ),
+        (
+            "027_mute_scopes",
+            include_str!("../../migrations/027_mute_scopes.sql"),
+        ),
+        (
+            "028_dm_received_envelopes",
+            include_str!("../../migrations/028_dm_received_envelopes.sql"),
+        ),
+        (
+            "029_dm_outbox_payloads",
+            include_str!("../../migrations/029_dm_outbox_payloads.sql"),
+        ),
+        (
+            "030_mute_scope_key",
+            include_str!("../../migrations/030_mute_scope_key.sql"),
+        ),
+        (
+            "031_profile_updated_at",
+            include_str!("../../migrations/031_profile_updated_at.sql"),
+        ),
     ];
 
     pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
@@ -92,6 +370,7 @@ impl Storage {
         Self::run_migrations(&conn)?;
         Ok(Self {
             db: Mutex::new(conn),
+            word_filters: Mutex::new(WordFilters::default()),
         })
     }
 
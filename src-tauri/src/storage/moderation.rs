@@ -1,6 +1,29 @@
+use iroh_social_types::{Mute, MuteScope, now_millis};
 use rusqlite::params;
 
 use super::Storage;
+use super::posts::NOT_EXPIRED;
+
+/// Mute scopes that cover `scope`, as a SQL list: a `Posts` mute hides
+/// everything but DMs.
+fn covering_scopes(scope: MuteScope) -> &'static str {
+    match scope {
+        MuteScope::Posts => "'posts'",
+        MuteScope::Reposts => "'posts','reposts'",
+        MuteScope::Notifications => "'posts','notifications'",
+        MuteScope::Dms => "'dms'",
+    }
+}
+
+/// SQL selecting the pubkeys whose content under `scope` is hidden: blocked,
+/// or muted for it and not yet expired.
+pub(crate) fn hidden_pubkeys_sql(scope: MuteScope) -> String {
+    format!(
+        "SELECT pubkey FROM mutes WHERE scope IN ({}) AND {NOT_EXPIRED}
+         UNION SELECT pubkey FROM blocks",
+        covering_scopes(scope)
+    )
+}
 
 impl Storage {
     pub fn toggle_bookmark(&self, post_id: &str) -> anyhow::Result<bool> {
@@ -36,38 +59,89 @@ impl Storage {
         Ok(exists)
     }
 
-    pub fn mute_user(&self, pubkey: &str) -> anyhow::Result<()> {
+    /// Mute `pubkey` for `scope`, replacing any earlier mute of them for that
+    /// scope. Mutes for other scopes stay.
+    pub fn mute_user(
+        &self,
+        pubkey: &str,
+        scope: MuteScope,
+        expires_at: Option<u64>,
+    ) -> anyhow::Result<()> {
         let db = self.db.lock().unwrap();
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as i64;
         db.execute(
-            "INSERT OR IGNORE INTO mutes (pubkey, created_at) VALUES (?1, ?2)",
-            params![pubkey, now],
+            "INSERT INTO mutes (pubkey, scope, expires_at, created_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(pubkey, scope) DO UPDATE SET expires_at=?3, created_at=?4",
+            params![
+                pubkey,
+                scope.as_str(),
+                expires_at.map(|t| t as i64),
+                now_millis() as i64
+            ],
         )?;
         Ok(())
     }
 
+    /// Lift every mute of `pubkey`.
     pub fn unmute_user(&self, pubkey: &str) -> anyhow::Result<()> {
         let db = self.db.lock().unwrap();
         db.execute("DELETE FROM mutes WHERE pubkey=?1", params![pubkey])?;
         Ok(())
     }
 
+    /// Lift the mute of `pubkey` for `scope` only.
+    pub fn unmute_user_for(&self, pubkey: &str, scope: MuteScope) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "DELETE FROM mutes WHERE pubkey=?1 AND scope=?2",
+            params![pubkey, scope.as_str()],
+        )?;
+        Ok(())
+    }
+
+    /// Drop mutes whose expiry has passed.
+    pub fn delete_expired_mutes(&self, now: u64) -> anyhow::Result<usize> {
+        let db = self.db.lock().unwrap();
+        let count = db.execute(
+            "DELETE FROM mutes WHERE expires_at IS NOT NULL AND expires_at <= ?1",
+            params![now as i64],
+        )?;
+        Ok(count)
+    }
+
+    /// The mutes of `pubkey` still in effect, one per scope, oldest first.
+    pub fn get_user_mutes(&self, pubkey: &str) -> anyhow::Result<Vec<Mute>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(&format!(
+            "SELECT pubkey, scope, expires_at, created_at FROM mutes
+             WHERE pubkey=?1 AND {NOT_EXPIRED} ORDER BY created_at ASC"
+        ))?;
+        let mut rows = stmt.query(params![pubkey])?;
+        let mut mutes = Vec::new();
+        while let Some(row) = rows.next()? {
+            mutes.extend(Self::row_to_mute(row)?);
+        }
+        Ok(mutes)
+    }
+
+    /// Whether `pubkey` has any mute still in effect.
     pub fn is_muted(&self, pubkey: &str) -> anyhow::Result<bool> {
         let db = self.db.lock().unwrap();
-        let exists: bool = db.query_row(
-            "SELECT COUNT(*) > 0 FROM mutes WHERE pubkey=?1",
+        let muted: bool = db.query_row(
+            &format!("SELECT COUNT(*) > 0 FROM mutes WHERE pubkey=?1 AND {NOT_EXPIRED}"),
             params![pubkey],
             |row| row.get(0),
         )?;
-        Ok(exists)
+        Ok(muted)
     }
 
+    /// Pubkeys with any mute still in effect, most recently muted first.
     pub fn get_muted_pubkeys(&self) -> anyhow::Result<Vec<String>> {
         let db = self.db.lock().unwrap();
-        let mut stmt = db.prepare("SELECT pubkey FROM mutes ORDER BY created_at DESC")?;
+        let mut stmt = db.prepare(&format!(
+            "SELECT pubkey FROM mutes WHERE {NOT_EXPIRED}
+             GROUP BY pubkey ORDER BY MAX(created_at) DESC"
+        ))?;
         let mut rows = stmt.query([])?;
         let mut keys = Vec::new();
         while let Some(row) = rows.next()? {
@@ -76,6 +150,49 @@ impl Storage {
         Ok(keys)
     }
 
+    /// Mutes still in effect, newest first.
+    pub fn get_mutes(&self) -> anyhow::Result<Vec<Mute>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(&format!(
+            "SELECT pubkey, scope, expires_at, created_at FROM mutes
+             WHERE {NOT_EXPIRED} ORDER BY created_at DESC"
+        ))?;
+        let mut rows = stmt.query([])?;
+        let mut mutes = Vec::new();
+        while let Some(row) = rows.next()? {
+            mutes.extend(Self::row_to_mute(row)?);
+        }
+        Ok(mutes)
+    }
+
+    fn row_to_mute(row: &rusqlite::Row) -> anyhow::Result<Option<Mute>> {
+        let scope: String = row.get(1)?;
+        let Some(scope) = MuteScope::parse(&scope) else {
+            return Ok(None);
+        };
+        Ok(Some(Mute {
+            pubkey: row.get(0)?,
+            scope,
+            expires_at: row.get::<_, Option<i64>>(2)?.map(|t| t as u64),
+            created_at: row.get::<_, i64>(3)? as u64,
+        }))
+    }
+
+    /// Whether an active mute of `pubkey` covers `scope`.
+    pub fn is_muted_for(&self, pubkey: &str, scope: MuteScope) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let muted: bool = db.query_row(
+            &format!(
+                "SELECT COUNT(*) > 0 FROM mutes
+                 WHERE pubkey=?1 AND scope IN ({}) AND {NOT_EXPIRED}",
+                covering_scopes(scope)
+            ),
+            params![pubkey],
+            |row| row.get(0),
+        )?;
+        Ok(muted)
+    }
+
     pub fn block_user(&self, pubkey: &str) -> anyhow::Result<()> {
         let db = self.db.lock().unwrap();
         let now = std::time::SystemTime::now()
@@ -116,16 +233,225 @@ impl Storage {
         Ok(keys)
     }
 
+    /// Whether content from `pubkey` should be dropped on arrival: they are
+    /// blocked or muted for good. Temporary and narrower mutes only hide at
+    /// read time, so nothing is missing once they lift.
     pub fn is_hidden(&self, pubkey: &str) -> anyhow::Result<bool> {
         let db = self.db.lock().unwrap();
         let exists: bool = db.query_row(
-            "SELECT COUNT(*) > 0 FROM mutes WHERE pubkey=?1
-             UNION ALL
-             SELECT COUNT(*) > 0 FROM blocks WHERE pubkey=?1
-             LIMIT 1",
+            "SELECT EXISTS(SELECT 1 FROM mutes
+                           WHERE pubkey=?1 AND scope='posts' AND expires_at IS NULL)
+                 OR EXISTS(SELECT 1 FROM blocks WHERE pubkey=?1)",
             params![pubkey],
             |row| row.get(0),
         )?;
         Ok(exists)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FeedQuery;
+    use crate::storage::test_util::post;
+    use iroh_social_types::Post;
+
+    const SCOPES: [MuteScope; 4] = [
+        MuteScope::Posts,
+        MuteScope::Reposts,
+        MuteScope::Notifications,
+        MuteScope::Dms,
+    ];
+
+    fn hidden(storage: &Storage, scope: MuteScope) -> Vec<String> {
+        let db = storage.db.lock().unwrap();
+        let mut stmt = db
+            .prepare(&format!("{} ORDER BY 1", hidden_pubkeys_sql(scope)))
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn feed_ids(storage: &Storage) -> Vec<String> {
+        storage
+            .get_feed(&FeedQuery {
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .into_iter()
+            .map(|p| p.post.id)
+            .collect()
+    }
+
+    #[test]
+    fn test_is_muted_for_each_scope() {
+        // What a mute of each scope covers, in `SCOPES` order
+        let covers = [
+            (MuteScope::Posts, [true, true, true, false]),
+            (MuteScope::Reposts, [false, true, false, false]),
+            (MuteScope::Notifications, [false, false, true, false]),
+            (MuteScope::Dms, [false, false, false, true]),
+        ];
+        for (muted, expected) in covers {
+            let storage = Storage::open(":memory:").unwrap();
+            storage.mute_user("bob", muted, None).unwrap();
+            for (scope, covered) in SCOPES.into_iter().zip(expected) {
+                assert_eq!(
+                    storage.is_muted_for("bob", scope).unwrap(),
+                    covered,
+                    "{muted:?} mute checked for {scope:?}"
+                );
+                assert!(!storage.is_muted_for("carol", scope).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_mutes_for_several_scopes_coexist() {
+        let storage = Storage::open(":memory:").unwrap();
+        let later = now_millis() + 60_000;
+        storage.mute_user("bob", MuteScope::Reposts, None).unwrap();
+        storage
+            .mute_user("bob", MuteScope::Dms, Some(later))
+            .unwrap();
+
+        let scopes = |storage: &Storage| -> Vec<MuteScope> {
+            let mut mutes = storage.get_user_mutes("bob").unwrap();
+            mutes.sort_by_key(|m| m.scope.as_str());
+            mutes.into_iter().map(|m| m.scope).collect()
+        };
+        assert_eq!(scopes(&storage), [MuteScope::Dms, MuteScope::Reposts]);
+        assert!(storage.is_muted_for("bob", MuteScope::Reposts).unwrap());
+        assert!(storage.is_muted_for("bob", MuteScope::Dms).unwrap());
+        assert!(
+            !storage
+                .is_muted_for("bob", MuteScope::Notifications)
+                .unwrap()
+        );
+        assert!(storage.is_muted("bob").unwrap());
+        assert_eq!(storage.get_muted_pubkeys().unwrap(), ["bob"]);
+        assert_eq!(storage.get_mutes().unwrap().len(), 2);
+
+        // Muting a scope again only replaces that scope's mute
+        storage
+            .mute_user("bob", MuteScope::Reposts, Some(later))
+            .unwrap();
+        let mutes = storage.get_user_mutes("bob").unwrap();
+        assert_eq!(mutes.len(), 2);
+        assert!(mutes.iter().all(|m| m.expires_at == Some(later)));
+
+        // Lifting one scope leaves the other
+        storage.unmute_user_for("bob", MuteScope::Dms).unwrap();
+        assert_eq!(scopes(&storage), [MuteScope::Reposts]);
+        assert!(!storage.is_muted_for("bob", MuteScope::Dms).unwrap());
+
+        storage.unmute_user("bob").unwrap();
+        assert!(!storage.is_muted("bob").unwrap());
+        assert!(storage.get_muted_pubkeys().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_hidden_pubkeys_by_scope() {
+        let storage = Storage::open(":memory:").unwrap();
+        for (pubkey, scope) in [
+            ("a-posts", MuteScope::Posts),
+            ("b-reposts", MuteScope::Reposts),
+            ("c-notifications", MuteScope::Notifications),
+            ("d-dms", MuteScope::Dms),
+        ] {
+            storage.mute_user(pubkey, scope, None).unwrap();
+        }
+        storage.block_user("e-blocked").unwrap();
+
+        assert_eq!(hidden(&storage, MuteScope::Posts), ["a-posts", "e-blocked"]);
+        assert_eq!(
+            hidden(&storage, MuteScope::Reposts),
+            ["a-posts", "b-reposts", "e-blocked"]
+        );
+        assert_eq!(
+            hidden(&storage, MuteScope::Notifications),
+            ["a-posts", "c-notifications", "e-blocked"]
+        );
+        assert_eq!(hidden(&storage, MuteScope::Dms), ["d-dms", "e-blocked"]);
+    }
+
+    #[test]
+    fn test_temporary_mute_expires() {
+        let storage = Storage::open(":memory:").unwrap();
+        let now = now_millis();
+        storage
+            .mute_user("lapsed", MuteScope::Posts, Some(now - 1_000))
+            .unwrap();
+        storage
+            .mute_user("running", MuteScope::Posts, Some(now + 60_000))
+            .unwrap();
+
+        // Lapsed mutes stop applying before the sweep removes them
+        assert!(!storage.is_muted_for("lapsed", MuteScope::Posts).unwrap());
+        assert!(storage.get_user_mutes("lapsed").unwrap().is_empty());
+        assert!(!storage.is_muted("lapsed").unwrap());
+        assert!(storage.is_muted_for("running", MuteScope::Posts).unwrap());
+        assert_eq!(hidden(&storage, MuteScope::Posts), ["running"]);
+        let mutes: Vec<String> = storage
+            .get_mutes()
+            .unwrap()
+            .into_iter()
+            .map(|m| m.pubkey)
+            .collect();
+        assert_eq!(mutes, ["running"]);
+
+        assert_eq!(storage.delete_expired_mutes(now).unwrap(), 1);
+        assert_eq!(storage.delete_expired_mutes(now).unwrap(), 0);
+        assert_eq!(storage.delete_expired_mutes(now + 60_000).unwrap(), 1);
+        assert!(!storage.is_muted_for("running", MuteScope::Posts).unwrap());
+    }
+
+    #[test]
+    fn test_muted_authors_hidden_from_quotes_and_reposts() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage.insert_post(&post("a1", "alice", 100)).unwrap();
+        storage.insert_post(&post("b1", "bob", 150)).unwrap();
+        let sharing = |id: &str, author: &str, content: &str, ts: u64| Post {
+            content: content.to_string(),
+            quote_of: Some("a1".to_string()),
+            quote_of_author: Some("alice".to_string()),
+            ..post(id, author, ts)
+        };
+        storage
+            .insert_post(&sharing("quote", "bob", "look", 200))
+            .unwrap();
+        storage
+            .insert_post(&sharing("repost", "carol", "", 300))
+            .unwrap();
+        assert_eq!(feed_ids(&storage), ["repost", "quote", "b1", "a1"]);
+
+        // Muting alice's posts hides quotes and reposts of them too
+        storage.mute_user("alice", MuteScope::Posts, None).unwrap();
+        assert_eq!(feed_ids(&storage), ["b1"]);
+
+        // A reposts-only mute hides carol's repost but not her quotes,
+        // and not the post she reposted
+        storage.unmute_user("alice").unwrap();
+        storage
+            .mute_user("carol", MuteScope::Reposts, None)
+            .unwrap();
+        storage
+            .insert_post(&sharing("carol-quote", "carol", "agreed", 400))
+            .unwrap();
+        assert_eq!(feed_ids(&storage), ["carol-quote", "quote", "b1", "a1"]);
+
+        // Notification and DM mutes leave the feed alone
+        storage.unmute_user("carol").unwrap();
+        storage
+            .mute_user("alice", MuteScope::Notifications, None)
+            .unwrap();
+        storage.mute_user("bob", MuteScope::Dms, None).unwrap();
+        assert_eq!(
+            feed_ids(&storage),
+            ["carol-quote", "repost", "quote", "b1", "a1"]
+        );
+    }
+}
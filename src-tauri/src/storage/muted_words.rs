@@ -0,0 +1,160 @@
+use iroh_social_types::{FilterScope, MutedWord, MutedWordKind, Post, now_millis};
+use rusqlite::{Connection, params};
+use std::sync::Arc;
+
+use super::{PostView, Storage};
+use crate::word_filter::WordFilter;
+
+/// Muted words compiled for each scope they are matched in. A filter is
+/// kept until the words change or the first of them expires.
+#[derive(Default)]
+pub(super) struct WordFilters {
+    feed: Option<CachedFilter>,
+    notifications: Option<CachedFilter>,
+}
+
+struct CachedFilter {
+    filter: Arc<WordFilter>,
+    /// Earliest expiry among the words compiled in.
+    expires_at: Option<u64>,
+}
+
+impl Storage {
+    pub fn add_muted_word(&self, word: &MutedWord) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "INSERT INTO muted_words (id, pattern, kind, scope, expires_at, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                word.id,
+                word.pattern,
+                word.kind.as_str(),
+                word.scope.as_str(),
+                word.expires_at.map(|t| t as i64),
+                word.created_at as i64,
+            ],
+        )?;
+        self.invalidate_word_filters();
+        Ok(())
+    }
+
+    pub fn remove_muted_word(&self, id: &str) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let count = db.execute("DELETE FROM muted_words WHERE id=?1", params![id])?;
+        self.invalidate_word_filters();
+        Ok(count > 0)
+    }
+
+    /// Drop muted words whose expiry has passed.
+    pub fn delete_expired_muted_words(&self, now: u64) -> anyhow::Result<usize> {
+        let db = self.db.lock().unwrap();
+        let count = db.execute(
+            "DELETE FROM muted_words WHERE expires_at IS NOT NULL AND expires_at <= ?1",
+            params![now as i64],
+        )?;
+        if count > 0 {
+            self.invalidate_word_filters();
+        }
+        Ok(count)
+    }
+
+    /// Muted words still in effect, oldest first.
+    pub fn get_muted_words(&self) -> anyhow::Result<Vec<MutedWord>> {
+        let db = self.db.lock().unwrap();
+        Self::active_muted_words(&db, None)
+    }
+
+    fn active_muted_words(
+        db: &Connection,
+        scope: Option<FilterScope>,
+    ) -> anyhow::Result<Vec<MutedWord>> {
+        let mut stmt = db.prepare_cached(
+            "SELECT id, pattern, kind, scope, expires_at, created_at FROM muted_words
+             WHERE (expires_at IS NULL OR expires_at > ?1)
+               AND (?2 IS NULL OR scope = ?2 OR scope = 'both')
+             ORDER BY created_at ASC",
+        )?;
+        let mut rows = stmt.query(params![now_millis() as i64, scope.map(|s| s.as_str())])?;
+        let mut words = Vec::new();
+        while let Some(row) = rows.next()? {
+            let kind: String = row.get(2)?;
+            let scope: String = row.get(3)?;
+            let (Some(kind), Some(scope)) =
+                (MutedWordKind::parse(&kind), FilterScope::parse(&scope))
+            else {
+                continue;
+            };
+            words.push(MutedWord {
+                id: row.get(0)?,
+                pattern: row.get(1)?,
+                kind,
+                scope,
+                expires_at: row.get::<_, Option<i64>>(4)?.map(|t| t as u64),
+                created_at: row.get::<_, i64>(5)? as u64,
+            });
+        }
+        Ok(words)
+    }
+
+    /// Drop the compiled muted words after they change.
+    fn invalidate_word_filters(&self) {
+        *self.word_filters.lock().unwrap() = WordFilters::default();
+    }
+
+    /// The muted words matched in `scope`, compiled on first use and reused
+    /// until they change.
+    fn word_filter(&self, db: &Connection, scope: FilterScope) -> anyhow::Result<Arc<WordFilter>> {
+        let mut filters = self.word_filters.lock().unwrap();
+        let slot = match scope {
+            FilterScope::Notifications => &mut filters.notifications,
+            FilterScope::Feed | FilterScope::Both => &mut filters.feed,
+        };
+        let now = now_millis();
+        if let Some(cached) = slot
+            .as_ref()
+            .filter(|c| c.expires_at.is_none_or(|t| t > now))
+        {
+            return Ok(cached.filter.clone());
+        }
+        let words = Self::active_muted_words(db, Some(scope))?;
+        let filter = Arc::new(WordFilter::new(&words));
+        *slot = Some(CachedFilter {
+            filter: filter.clone(),
+            expires_at: words.iter().filter_map(|w| w.expires_at).min(),
+        });
+        Ok(filter)
+    }
+
+    /// Mark posts matching a feed-scoped muted word with the pattern that
+    /// matched, so they are shown collapsed with a "show anyway" bypass.
+    pub(crate) fn apply_muted_words(
+        &self,
+        db: &Connection,
+        posts: &mut [PostView],
+    ) -> anyhow::Result<()> {
+        let filter = self.word_filter(db, FilterScope::Feed)?;
+        if filter.is_empty() {
+            return Ok(());
+        }
+        for post in posts.iter_mut() {
+            let texts = [
+                post.content.as_str(),
+                post.content_warning.as_deref().unwrap_or(""),
+            ];
+            post.filtered_by = filter.find(texts).map(str::to_string);
+        }
+        Ok(())
+    }
+
+    /// The pattern of the notification-scoped muted word `post` matches, if
+    /// any. Such posts raise no notification.
+    pub fn notification_muted_by(&self, post: &Post) -> anyhow::Result<Option<String>> {
+        let db = self.db.lock().unwrap();
+        let filter = self.word_filter(&db, FilterScope::Notifications)?;
+        let texts = [
+            post.content.as_str(),
+            post.content_warning.as_deref().unwrap_or(""),
+        ];
+        Ok(filter.find(texts).map(str::to_string))
+    }
+}
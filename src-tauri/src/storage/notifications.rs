@@ -1,6 +1,8 @@
+use iroh_social_types::MuteScope;
 use rusqlite::params;
 use sha2::{Digest, Sha256};
 
+use super::moderation::hidden_pubkeys_sql;
 use super::{Notification, Storage};
 
 impl Storage {
@@ -33,8 +35,10 @@ impl Storage {
         before: Option<u64>,
     ) -> anyhow::Result<Vec<Notification>> {
         let db = self.db.lock().unwrap();
-        let hidden =
-            "AND n.actor NOT IN (SELECT pubkey FROM mutes UNION SELECT pubkey FROM blocks)";
+        let hidden = format!(
+            "AND n.actor NOT IN ({})",
+            hidden_pubkeys_sql(MuteScope::Notifications)
+        );
         let mut notifications = Vec::new();
         match before {
             Some(b) => {
@@ -1,13 +1,67 @@
-use iroh_social_types::{MediaAttachment, Post};
-use rusqlite::params;
+use iroh_social_types::{MediaAttachment, MuteScope, Post, PostKind, SensitiveMode};
+use rusqlite::{Connection, params};
+use std::collections::HashSet;
 
-use super::{FeedQuery, Storage};
+use super::moderation::hidden_pubkeys_sql;
+use super::settings::SENSITIVE_MODE_KEY;
+use super::{FeedQuery, PostView, RankSignals, ReplyFilter, Storage};
+
+/// Columns read by `row_to_post`, in order. Queries alias `posts` as `p`.
+pub(crate) const POST_COLUMNS: &str = "p.id, p.author, p.content, p.timestamp, p.media_json, p.reply_to, p.reply_to_author, p.quote_of, p.quote_of_author, p.signature, p.poll_json, p.content_warning, p.sensitive_media, p.expires_at, p.link_preview_json, p.audience";
+
+/// SQL condition leaving out posts past their `expires_at` that the sweep
+/// has not removed yet.
+pub(crate) const NOT_EXPIRED: &str =
+    "(expires_at IS NULL OR expires_at > CAST(strftime('%s','now') AS INTEGER) * 1000)";
+
+/// SQL condition on `p` for posts that may be served to the viewer in
+/// parameter `?{param}`: public posts, and our audience posts whose list
+/// includes them. A NULL viewer means no filter.
+fn visible_to_sql(param: usize) -> String {
+    format!(
+        "(p.audience IS NULL OR ?{param} IS NULL OR EXISTS (
+            SELECT 1 FROM audience_posts ap
+            JOIN audience_members am ON am.audience_id = ap.audience_id
+            WHERE ap.post_id = p.id AND am.pubkey = ?{param}))"
+    )
+}
+
+/// SQL for the reader's `SensitiveMode` towards the author in the `author`
+/// expression: the per-author override, else the global setting, else the
+/// default.
+fn sensitive_mode_sql(author: &str) -> String {
+    format!(
+        "COALESCE((SELECT mode FROM sensitive_overrides WHERE pubkey={author}),
+                  (SELECT value FROM settings WHERE key='{SENSITIVE_MODE_KEY}'), 'collapse')"
+    )
+}
+
+/// SQL conditions leaving out posts the reader shouldn't see in a feed: by
+/// muted or blocked authors, reposts or quotes of them, reposts by people
+/// whose reposts are muted, expired, or sensitive under a "hide" mode.
+fn feed_hidden_sql() -> String {
+    let hidden = hidden_pubkeys_sql(MuteScope::Posts);
+    format!(
+        "AND p.author NOT IN ({hidden})
+         AND (p.quote_of_author IS NULL OR p.quote_of_author NOT IN ({hidden}))
+         AND (p.kind != 'repost' OR p.author NOT IN ({}))
+         AND {NOT_EXPIRED}
+         AND NOT ((p.content_warning IS NOT NULL OR p.sensitive_media=1)
+                  AND {}='hide')",
+        hidden_pubkeys_sql(MuteScope::Reposts),
+        sensitive_mode_sql("p.author")
+    )
+}
 
 impl Storage {
-    pub(crate) fn row_to_post(row: &rusqlite::Row) -> anyhow::Result<Post> {
+    pub(crate) fn row_to_post(row: &rusqlite::Row) -> anyhow::Result<PostView> {
         let media_json: String = row.get(4)?;
         let media: Vec<MediaAttachment> = serde_json::from_str(&media_json)?;
-        Ok(Post {
+        let poll_json: Option<String> = row.get(10)?;
+        let poll = poll_json.map(|j| serde_json::from_str(&j)).transpose()?;
+        let preview_json: Option<String> = row.get(14)?;
+        let link_preview = preview_json.map(|j| serde_json::from_str(&j)).transpose()?;
+        let post = Post {
             id: row.get(0)?,
             author: row.get(1)?,
             content: row.get(2)?,
@@ -18,15 +72,70 @@ impl Storage {
             quote_of: row.get(7)?,
             quote_of_author: row.get(8)?,
             signature: row.get(9)?,
+            poll,
+            content_warning: row.get(11)?,
+            sensitive_media: row.get(12)?,
+            expires_at: row.get::<_, Option<i64>>(13)?.map(|t| t as u64),
+            link_preview,
+        };
+        Ok(PostView {
+            audience: row.get(15)?,
+            ..PostView::new(post)
         })
     }
 
+    /// Mark posts with a content warning or sensitive media as collapsed
+    /// unless the reader chose to show them for that author.
+    pub(crate) fn apply_sensitive_modes(
+        db: &Connection,
+        posts: &mut [PostView],
+    ) -> anyhow::Result<()> {
+        let sql = format!("SELECT {}", sensitive_mode_sql("?1"));
+        let mut stmt = db.prepare_cached(&sql)?;
+        for post in posts {
+            if post.content_warning.is_none() && !post.sensitive_media {
+                continue;
+            }
+            let mode: String = stmt.query_row(params![post.author], |row| row.get(0))?;
+            post.collapsed = SensitiveMode::parse(&mode).unwrap_or_default() != SensitiveMode::Show;
+        }
+        Ok(())
+    }
+
     pub fn insert_post(&self, post: &Post) -> anyhow::Result<()> {
         let db = self.db.lock().unwrap();
+        Self::insert_post_row(&db, post, None)
+    }
+
+    /// Store a post shared with us as a member of the author's `audience`
+    /// list, or one of our own shared with our list of that name.
+    pub fn insert_audience_post(&self, post: &Post, audience: &str) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        Self::insert_post_row(&db, post, Some(audience))
+    }
+
+    /// Insert several posts in one transaction, e.g. the parts of a thread.
+    pub fn insert_posts(&self, posts: &[Post]) -> anyhow::Result<()> {
+        let mut db = self.db.lock().unwrap();
+        let tx = db.transaction()?;
+        for post in posts {
+            Self::insert_post_row(&tx, post, None)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn insert_post_row(db: &Connection, post: &Post, audience: Option<&str>) -> anyhow::Result<()> {
         let media_json = serde_json::to_string(&post.media)?;
+        let poll_json = post.poll.as_ref().map(serde_json::to_string).transpose()?;
+        let preview_json = post
+            .link_preview
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
         db.execute(
-            "INSERT OR IGNORE INTO posts (id, author, content, timestamp, media_json, reply_to, reply_to_author, quote_of, quote_of_author, signature)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            "INSERT OR IGNORE INTO posts (id, author, content, timestamp, media_json, reply_to, reply_to_author, quote_of, quote_of_author, signature, poll_json, content_warning, sensitive_media, expires_at, link_preview_json, kind, audience)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
             params![
                 post.id,
                 post.author,
@@ -38,15 +147,21 @@ impl Storage {
                 post.quote_of,
                 post.quote_of_author,
                 post.signature,
+                poll_json,
+                post.content_warning,
+                post.sensitive_media,
+                post.expires_at.map(|t| t as i64),
+                preview_json,
+                post.kind().as_str(),
+                audience,
             ],
         )?;
         Ok(())
     }
 
-    pub fn get_post_by_id(&self, id: &str) -> anyhow::Result<Option<Post>> {
+    pub fn get_post_by_id(&self, id: &str) -> anyhow::Result<Option<PostView>> {
         let db = self.db.lock().unwrap();
-        let mut stmt =
-            db.prepare("SELECT id, author, content, timestamp, media_json, reply_to, reply_to_author, quote_of, quote_of_author, signature FROM posts WHERE id=?1")?;
+        let mut stmt = db.prepare(&format!("SELECT {POST_COLUMNS} FROM posts p WHERE id=?1"))?;
         let mut rows = stmt.query(params![id])?;
         match rows.next()? {
             Some(row) => Ok(Some(Self::row_to_post(row)?)),
@@ -54,27 +169,84 @@ impl Storage {
         }
     }
 
-    pub fn get_feed(&self, q: &FeedQuery) -> anyhow::Result<Vec<Post>> {
+    pub fn get_feed(&self, q: &FeedQuery) -> anyhow::Result<Vec<PostView>> {
         let db = self.db.lock().unwrap();
-        let hidden =
-            "AND p.author NOT IN (SELECT pubkey FROM mutes UNION SELECT pubkey FROM blocks)";
-
-        let (sql, p): (String, Vec<Box<dyn rusqlite::types::ToSql>>) = match q.before {
-            Some(b) => (
-                format!(
-                    "SELECT p.id, p.author, p.content, p.timestamp, p.media_json, p.reply_to, p.reply_to_author, p.quote_of, p.quote_of_author, p.signature FROM posts p
-                     WHERE p.timestamp < ?1 {hidden} ORDER BY p.timestamp DESC LIMIT ?2"
-                ),
-                vec![Box::new(b as i64), Box::new(q.limit as i64)],
-            ),
-            None => (
-                format!(
-                    "SELECT p.id, p.author, p.content, p.timestamp, p.media_json, p.reply_to, p.reply_to_author, p.quote_of, p.quote_of_author, p.signature FROM posts p
-                     WHERE 1=1 {hidden} ORDER BY p.timestamp DESC LIMIT ?1"
-                ),
-                vec![Box::new(q.limit as i64)],
+        let hidden = format!(
+            "{}
+             AND (p.kind != 'repost' OR p.id = (
+                 SELECT r.id FROM posts r
+                 WHERE r.kind='repost' AND r.quote_of=p.quote_of
+                   AND r.author NOT IN ({})
+                 ORDER BY r.timestamp DESC, r.id DESC LIMIT 1))",
+            feed_hidden_sql(),
+            hidden_pubkeys_sql(MuteScope::Reposts)
+        );
+
+        let mut filters = String::new();
+        let mut p: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+        match (q.before, &q.before_id) {
+            (Some(b), Some(id)) => {
+                p.push(Box::new(b as i64));
+                p.push(Box::new(id.clone()));
+                filters.push_str(&format!(
+                    " AND (p.timestamp < ?{ts} OR (p.timestamp = ?{ts} AND p.id < ?{id}))",
+                    ts = p.len() - 1,
+                    id = p.len()
+                ));
+            }
+            (Some(b), None) => {
+                p.push(Box::new(b as i64));
+                filters.push_str(&format!(" AND p.timestamp < ?{}", p.len()));
+            }
+            (None, _) => {}
+        }
+        if !q.list_ids.is_empty() {
+            let mut slots = Vec::new();
+            for id in &q.list_ids {
+                p.push(Box::new(id.clone()));
+                slots.push(format!("?{}", p.len()));
+            }
+            filters.push_str(&format!(
+                " AND p.author IN (SELECT pubkey FROM list_members WHERE list_id IN ({}))",
+                slots.join(", ")
+            ));
+        }
+        if !q.hashtags.is_empty() {
+            // Tags are normalized to [a-z0-9_], so they are safe inside a
+            // GLOB pattern; the brackets keep `#rust` from matching `#rustacean`.
+            let mut tag_matches = Vec::new();
+            for tag in &q.hashtags {
+                p.push(Box::new(tag.clone()));
+                tag_matches.push(format!(
+                    "(' ' || lower(p.content) || ' ') GLOB '*[^a-z0-9_]#' || ?{} || '[^a-z0-9_]*'",
+                    p.len()
+                ));
+            }
+            filters.push_str(&format!(" AND ({})", tag_matches.join(" OR ")));
+        }
+        if q.media_only {
+            filters.push_str(" AND p.media_json != '[]'");
+        }
+        match q.replies {
+            ReplyFilter::All => {}
+            ReplyFilter::None => filters.push_str(" AND p.reply_to IS NULL"),
+            ReplyFilter::Following => filters.push_str(
+                " AND (p.reply_to IS NULL OR p.reply_to_author IN (SELECT pubkey FROM follows))",
             ),
-        };
+        }
+        if q.exclude_reposts {
+            filters.push_str(" AND p.kind != 'repost'");
+        }
+        if let Some(author) = &q.exclude_author {
+            p.push(Box::new(author.clone()));
+            filters.push_str(&format!(" AND p.author != ?{}", p.len()));
+        }
+        p.push(Box::new(q.limit as i64));
+        let sql = format!(
+            "SELECT {POST_COLUMNS} FROM posts p
+             WHERE 1=1 {filters} {hidden} ORDER BY p.timestamp DESC, p.id DESC LIMIT ?{}",
+            p.len()
+        );
         let mut stmt = db.prepare(&sql)?;
         let p_refs: Vec<&dyn rusqlite::types::ToSql> = p.iter().map(|b| b.as_ref()).collect();
         let rows = stmt.query_and_then(p_refs.as_slice(), Self::row_to_post)?;
@@ -82,19 +254,180 @@ impl Storage {
         for row in rows {
             posts.push(row?);
         }
+        Self::apply_latest_edits(&db, &mut posts)?;
+        Self::apply_sensitive_modes(&db, &mut posts)?;
+        self.apply_muted_words(&db, &mut posts)?;
+        Self::apply_reposters(&db, &mut posts)?;
         Ok(posts)
     }
 
+    /// Candidates for the ranked feed: visible posts by others since `since`,
+    /// newest first, with the local signals they are scored on. Reposts are
+    /// left out; they count towards the post they share instead.
+    pub fn get_rank_candidates(
+        &self,
+        my_pubkey: &str,
+        since: u64,
+        limit: usize,
+    ) -> anyhow::Result<Vec<(PostView, RankSignals)>> {
+        let db = self.db.lock().unwrap();
+        let sql = format!(
+            "SELECT {POST_COLUMNS},
+                (SELECT COUNT(*) FROM interactions i
+                 WHERE i.target_post_id=p.id AND i.kind='Like'
+                   AND i.author IN (SELECT pubkey FROM follows)),
+                (SELECT COUNT(*) FROM posts r
+                 WHERE r.quote_of=p.id AND r.kind IN ('repost', 'quote')
+                   AND r.author IN (SELECT pubkey FROM follows)),
+                (SELECT COUNT(*) FROM posts r WHERE r.reply_to=p.id),
+                (SELECT COUNT(*) FROM interactions i
+                 WHERE i.author=?1 AND i.kind='Like' AND i.target_author=p.author),
+                (SELECT COUNT(*) FROM posts r
+                 WHERE r.author=?1 AND r.reply_to_author=p.author)
+             FROM posts p
+             WHERE p.timestamp >= ?2 AND p.author != ?1 AND p.kind != 'repost'
+               {}
+             ORDER BY p.timestamp DESC, p.id DESC LIMIT ?3",
+            feed_hidden_sql()
+        );
+        let mut stmt = db.prepare(&sql)?;
+        let rows = stmt.query_and_then(
+            params![my_pubkey, since as i64, limit as i64],
+            |row| -> anyhow::Result<(PostView, RankSignals)> {
+                let post = Self::row_to_post(row)?;
+                let signals = RankSignals {
+                    timestamp: post.timestamp,
+                    followed_likes: row.get(16)?,
+                    followed_reposts: row.get(17)?,
+                    replies: row.get(18)?,
+                    author_likes: row.get(19)?,
+                    author_replies: row.get(20)?,
+                };
+                Ok((post, signals))
+            },
+        )?;
+        let mut posts = Vec::new();
+        let mut signals = Vec::new();
+        for row in rows {
+            let (post, s) = row?;
+            posts.push(post);
+            signals.push(s);
+        }
+        Self::apply_latest_edits(&db, &mut posts)?;
+        Self::apply_sensitive_modes(&db, &mut posts)?;
+        self.apply_muted_words(&db, &mut posts)?;
+        Ok(posts.into_iter().zip(signals).collect())
+    }
+
+    /// Fill `reposted_by` on each repost with the visible authors of every
+    /// repost of the same target, newest first.
+    fn apply_reposters(db: &Connection, posts: &mut [PostView]) -> anyhow::Result<()> {
+        let mut stmt = db.prepare(&format!(
+            "SELECT author FROM posts
+             WHERE kind='repost' AND quote_of=?1
+               AND author NOT IN ({})
+             ORDER BY timestamp DESC, id DESC",
+            hidden_pubkeys_sql(MuteScope::Reposts)
+        ))?;
+        for post in posts.iter_mut() {
+            let Some(target) = post.post.quote_of.as_deref() else {
+                continue;
+            };
+            if post.kind() != PostKind::Repost {
+                continue;
+            }
+            post.reposted_by = stmt
+                .query_map(params![target], |row| row.get(0))?
+                .collect::<Result<_, _>>()?;
+        }
+        Ok(())
+    }
+
     pub fn delete_post(&self, id: &str) -> anyhow::Result<bool> {
         let db = self.db.lock().unwrap();
         db.execute(
             "DELETE FROM notifications WHERE post_id=?1 OR target_post_id=?1",
             params![id],
         )?;
+        db.execute("DELETE FROM post_revisions WHERE post_id=?1", params![id])?;
+        db.execute("DELETE FROM audience_posts WHERE post_id=?1", params![id])?;
         let count = db.execute("DELETE FROM posts WHERE id=?1", params![id])?;
         Ok(count > 0)
     }
 
+    /// Remove every post whose `expires_at` has passed, as `delete_post`
+    /// does. Returns the removed posts.
+    pub fn delete_expired_posts(&self, now: u64) -> anyhow::Result<Vec<PostView>> {
+        let mut db = self.db.lock().unwrap();
+        let tx = db.transaction()?;
+        let expired = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT {POST_COLUMNS} FROM posts p WHERE p.expires_at <= ?1"
+            ))?;
+            let rows = stmt.query_and_then(params![now as i64], Self::row_to_post)?;
+            rows.collect::<anyhow::Result<Vec<_>>>()?
+        };
+        for post in &expired {
+            tx.execute(
+                "DELETE FROM notifications WHERE post_id=?1 OR target_post_id=?1",
+                params![post.id],
+            )?;
+            tx.execute(
+                "DELETE FROM post_revisions WHERE post_id=?1",
+                params![post.id],
+            )?;
+            tx.execute(
+                "DELETE FROM audience_posts WHERE post_id=?1",
+                params![post.id],
+            )?;
+            tx.execute("DELETE FROM posts WHERE id=?1", params![post.id])?;
+        }
+        tx.commit()?;
+        Ok(expired)
+    }
+
+    /// Whether a post, edit, message, avatar, draft or scheduled post still
+    /// refers to the blob.
+    pub fn is_blob_referenced(&self, hash: &str) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let pattern = format!("%\"hash\":\"{hash}\"%");
+        let found: bool = db.query_row(
+            "SELECT EXISTS(SELECT 1 FROM posts WHERE media_json LIKE ?1 OR link_preview_json LIKE ?1)
+                 OR EXISTS(SELECT 1 FROM post_revisions WHERE media_json LIKE ?1)
+                 OR EXISTS(SELECT 1 FROM dm_messages WHERE media_json LIKE ?1)
+                 OR EXISTS(SELECT 1 FROM profiles WHERE avatar_hash=?2)
+                 OR EXISTS(SELECT 1 FROM drafts WHERE body_json LIKE ?1)
+                 OR EXISTS(SELECT 1 FROM scheduled_posts WHERE body_json LIKE ?1)",
+            params![pattern, hash],
+            |row| row.get(0),
+        )?;
+        Ok(found)
+    }
+
+    /// Every blob hash `is_blob_referenced` would report. Blob garbage
+    /// collection keeps these and drops the rest.
+    pub fn referenced_blob_hashes(&self) -> anyhow::Result<HashSet<String>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(
+            "SELECT json_extract(m.value, '$.hash') FROM posts p, json_each(p.media_json) m
+             UNION SELECT json_extract(link_preview_json, '$.image.hash') FROM posts
+             UNION SELECT json_extract(m.value, '$.hash')
+                   FROM post_revisions r, json_each(r.media_json) m
+             UNION SELECT json_extract(m.value, '$.hash')
+                   FROM dm_messages d, json_each(d.media_json) m
+             UNION SELECT avatar_hash FROM profiles
+             UNION SELECT json_extract(m.value, '$.hash')
+                   FROM drafts d, json_each(d.body_json, '$.media') m
+             UNION SELECT json_extract(m.value, '$.hash')
+                   FROM scheduled_posts s, json_each(s.body_json, '$.media') m",
+        )?;
+        let hashes = stmt
+            .query_map([], |row| row.get::<_, Option<String>>(0))?
+            .filter_map(Result::transpose)
+            .collect::<Result<_, _>>()?;
+        Ok(hashes)
+    }
+
     pub fn delete_repost_by_target(
         &self,
         author: &str,
@@ -103,7 +436,7 @@ impl Storage {
         let db = self.db.lock().unwrap();
         let id: Option<String> = db
             .query_row(
-                "SELECT id FROM posts WHERE author=?1 AND quote_of=?2",
+                "SELECT id FROM posts WHERE author=?1 AND quote_of=?2 AND kind='repost'",
                 params![author, quote_of],
                 |row| row.get(0),
             )
@@ -120,7 +453,7 @@ impl Storage {
         limit: usize,
         before: Option<u64>,
         media_filter: Option<&str>,
-    ) -> anyhow::Result<Vec<Post>> {
+    ) -> anyhow::Result<Vec<PostView>> {
         let filter_clause = match media_filter {
             Some("images") => " AND media_json LIKE '%image/%'",
             Some("videos") => " AND media_json LIKE '%video/%'",
@@ -137,8 +470,9 @@ impl Storage {
         match before {
             Some(b) => {
                 let sql = format!(
-                    "SELECT id, author, content, timestamp, media_json, reply_to, reply_to_author, quote_of, quote_of_author, signature FROM posts
-                     WHERE author=?1 AND timestamp < ?2{filter_clause} ORDER BY timestamp DESC LIMIT ?3"
+                    "SELECT {POST_COLUMNS} FROM posts p
+                     WHERE author=?1 AND timestamp < ?2 AND {NOT_EXPIRED}{filter_clause}
+                     ORDER BY timestamp DESC LIMIT ?3"
                 );
                 let mut stmt = db.prepare(&sql)?;
                 let mut rows = stmt.query(params![author, b as i64, limit as i64])?;
@@ -148,8 +482,9 @@ impl Storage {
             }
             None => {
                 let sql = format!(
-                    "SELECT id, author, content, timestamp, media_json, reply_to, reply_to_author, quote_of, quote_of_author, signature FROM posts
-                     WHERE author=?1{filter_clause} ORDER BY timestamp DESC LIMIT ?2"
+                    "SELECT {POST_COLUMNS} FROM posts p
+                     WHERE author=?1 AND {NOT_EXPIRED}{filter_clause}
+                     ORDER BY timestamp DESC LIMIT ?2"
                 );
                 let mut stmt = db.prepare(&sql)?;
                 let mut rows = stmt.query(params![author, limit as i64])?;
@@ -158,6 +493,8 @@ impl Storage {
                 }
             }
         }
+        Self::apply_latest_edits(&db, &mut posts)?;
+        Self::apply_sensitive_modes(&db, &mut posts)?;
         Ok(posts)
     }
 
@@ -172,11 +509,15 @@ impl Storage {
         Ok(ids)
     }
 
-    pub fn count_posts_by_author(&self, author: &str) -> anyhow::Result<u64> {
+    /// Posts by `author`, counting only those `viewer` may be served when set.
+    pub fn count_posts_by_author(&self, author: &str, viewer: Option<&str>) -> anyhow::Result<u64> {
         let db = self.db.lock().unwrap();
         let count: i64 = db.query_row(
-            "SELECT COUNT(*) FROM posts WHERE author=?1",
-            params![author],
+            &format!(
+                "SELECT COUNT(*) FROM posts p WHERE author=?1 AND {NOT_EXPIRED} AND {}",
+                visible_to_sql(2)
+            ),
+            params![author, viewer],
             |row| row.get(0),
         )?;
         Ok(count as u64)
@@ -192,11 +533,19 @@ impl Storage {
         Ok(ts.unwrap_or(0) as u64)
     }
 
-    pub fn count_posts_after(&self, author: &str, after_ts: u64) -> anyhow::Result<u64> {
+    pub fn count_posts_after(
+        &self,
+        author: &str,
+        after_ts: u64,
+        viewer: Option<&str>,
+    ) -> anyhow::Result<u64> {
         let db = self.db.lock().unwrap();
         let count: i64 = db.query_row(
-            "SELECT COUNT(*) FROM posts WHERE author=?1 AND timestamp > ?2",
-            params![author, after_ts as i64],
+            &format!(
+                "SELECT COUNT(*) FROM posts p WHERE author=?1 AND timestamp > ?2 AND {NOT_EXPIRED} AND {}",
+                visible_to_sql(3)
+            ),
+            params![author, after_ts as i64, viewer],
             |row| row.get(0),
         )?;
         Ok(count as u64)
@@ -206,20 +555,23 @@ impl Storage {
         &self,
         author: &str,
         after_ts: u64,
+        viewer: Option<&str>,
         limit: usize,
         offset: usize,
-    ) -> anyhow::Result<Vec<Post>> {
+    ) -> anyhow::Result<Vec<PostView>> {
         let db = self.db.lock().unwrap();
-        let mut stmt = db.prepare(
-            "SELECT id, author, content, timestamp, media_json, reply_to, reply_to_author, quote_of, quote_of_author, signature
-             FROM posts WHERE author=?1 AND timestamp > ?2
+        let mut stmt = db.prepare(&format!(
+            "SELECT {POST_COLUMNS}
+             FROM posts p WHERE author=?1 AND timestamp > ?2 AND {NOT_EXPIRED} AND {}
              ORDER BY timestamp ASC LIMIT ?3 OFFSET ?4",
-        )?;
+            visible_to_sql(5)
+        ))?;
         let mut rows = stmt.query(params![
             author,
             after_ts as i64,
             limit as i64,
-            offset as i64
+            offset as i64,
+            viewer
         ])?;
         let mut posts = Vec::new();
         while let Some(row) = rows.next()? {
@@ -232,17 +584,19 @@ impl Storage {
         &self,
         author: &str,
         known_ids: &[String],
+        viewer: Option<&str>,
         limit: usize,
         offset: usize,
-    ) -> anyhow::Result<Vec<Post>> {
+    ) -> anyhow::Result<Vec<PostView>> {
         let db = self.db.lock().unwrap();
+        let visible = visible_to_sql(4);
         if known_ids.is_empty() {
-            let mut stmt = db.prepare(
-                "SELECT id, author, content, timestamp, media_json, reply_to, reply_to_author, quote_of, quote_of_author, signature
-                 FROM posts WHERE author=?1
-                 ORDER BY timestamp ASC LIMIT ?2 OFFSET ?3",
-            )?;
-            let mut rows = stmt.query(params![author, limit as i64, offset as i64])?;
+            let mut stmt = db.prepare(&format!(
+                "SELECT {POST_COLUMNS}
+                 FROM posts p WHERE author=?1 AND {NOT_EXPIRED} AND {visible}
+                 ORDER BY timestamp ASC LIMIT ?2 OFFSET ?3"
+            ))?;
+            let mut rows = stmt.query(params![author, limit as i64, offset as i64, viewer])?;
             let mut posts = Vec::new();
             while let Some(row) = rows.next()? {
                 posts.push(Self::row_to_post(row)?);
@@ -259,12 +613,13 @@ impl Storage {
         }
         drop(insert);
 
-        let mut stmt = db.prepare(
-            "SELECT p.id, p.author, p.content, p.timestamp, p.media_json, p.reply_to, p.reply_to_author, p.quote_of, p.quote_of_author, p.signature             FROM posts p
+        let mut stmt = db.prepare(&format!(
+            "SELECT {POST_COLUMNS} FROM posts p
              WHERE p.author=?1 AND p.id NOT IN (SELECT id FROM _sync_known_ids)
-             ORDER BY p.timestamp ASC LIMIT ?2 OFFSET ?3",
-        )?;
-        let mut rows = stmt.query(params![author, limit as i64, offset as i64])?;
+               AND {NOT_EXPIRED} AND {visible}
+             ORDER BY p.timestamp ASC LIMIT ?2 OFFSET ?3"
+        ))?;
+        let mut rows = stmt.query(params![author, limit as i64, offset as i64, viewer])?;
         let mut posts = Vec::new();
         while let Some(row) = rows.next()? {
             posts.push(Self::row_to_post(row)?);
@@ -274,19 +629,112 @@ impl Storage {
         Ok(posts)
     }
 
+    /// A post followed by its author's own replies beneath it, in order:
+    /// the chain published as a thread, plus any later self-replies.
+    pub fn get_thread(&self, root_id: &str) -> anyhow::Result<Vec<PostView>> {
+        let db = self.db.lock().unwrap();
+        // UNION (not UNION ALL) stops on reply_to cycles forged by peers
+        let mut stmt = db.prepare(&format!(
+            "WITH RECURSIVE thread(id) AS (
+                 SELECT id FROM posts WHERE id=?1
+                 UNION
+                 SELECT c.id FROM posts c
+                 JOIN thread t ON c.reply_to = t.id
+                 WHERE c.author = (SELECT author FROM posts WHERE id=?1)
+             )
+             SELECT {POST_COLUMNS} FROM thread t JOIN posts p ON p.id = t.id
+             ORDER BY p.timestamp ASC"
+        ))?;
+        let rows = stmt.query_and_then(params![root_id], Self::row_to_post)?;
+        let mut posts = Vec::new();
+        for row in rows {
+            posts.push(row?);
+        }
+        Self::apply_latest_edits(&db, &mut posts)?;
+        Self::apply_sensitive_modes(&db, &mut posts)?;
+        Ok(posts)
+    }
+
+    /// The reply chain above a post, root first, ending with the post itself.
+    /// Stops at `max_depth`, at the first ancestor we don't hold, or where the
+    /// chain loops back to a post already on it.
+    pub fn get_ancestors(&self, post_id: &str, max_depth: u32) -> anyhow::Result<Vec<PostView>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(&format!(
+            "WITH RECURSIVE chain(id, reply_to, depth, path) AS (
+                 SELECT id, reply_to, 0, ',' || id || ',' FROM posts WHERE id=?1
+                 UNION ALL
+                 SELECT p.id, p.reply_to, c.depth + 1, c.path || p.id || ',' FROM posts p
+                 JOIN chain c ON p.id = c.reply_to
+                 WHERE c.depth < ?2 AND instr(c.path, ',' || p.id || ',') = 0
+             )
+             SELECT {POST_COLUMNS} FROM chain c JOIN posts p ON p.id = c.id
+             ORDER BY c.depth DESC"
+        ))?;
+        let rows = stmt.query_and_then(params![post_id, max_depth], Self::row_to_post)?;
+        let mut posts = Vec::new();
+        for row in rows {
+            posts.push(row?);
+        }
+        Self::apply_latest_edits(&db, &mut posts)?;
+        Self::apply_sensitive_modes(&db, &mut posts)?;
+        Ok(posts)
+    }
+
+    /// All replies below a post down to `max_depth` levels, shallowest first.
+    /// Muted and blocked authors are left out. Each branch stops at a post it
+    /// already passed through, so reply cycles (possible since `reply_to` is
+    /// whatever the author signed) never bring the focus post or a repeat back.
+    pub fn get_descendants(
+        &self,
+        post_id: &str,
+        max_depth: u32,
+        limit: usize,
+    ) -> anyhow::Result<Vec<PostView>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(&format!(
+            "WITH RECURSIVE tree(id, depth, path) AS (
+                 SELECT id, 0, ',' || id || ',' FROM posts WHERE id=?1
+                 UNION ALL
+                 SELECT p.id, t.depth + 1, t.path || p.id || ',' FROM posts p
+                 JOIN tree t ON p.reply_to = t.id
+                 WHERE t.depth < ?2 AND instr(t.path, ',' || p.id || ',') = 0
+             )
+             SELECT {POST_COLUMNS} FROM tree t JOIN posts p ON p.id = t.id
+             WHERE t.depth > 0
+               AND p.author NOT IN ({})
+             GROUP BY p.id
+             ORDER BY MIN(t.depth) ASC, p.timestamp ASC
+             LIMIT ?3",
+            hidden_pubkeys_sql(MuteScope::Posts)
+        ))?;
+        let rows =
+            stmt.query_and_then(params![post_id, max_depth, limit as i64], Self::row_to_post)?;
+        let mut posts = Vec::new();
+        for row in rows {
+            posts.push(row?);
+        }
+        Self::apply_latest_edits(&db, &mut posts)?;
+        Self::apply_sensitive_modes(&db, &mut posts)?;
+        Ok(posts)
+    }
+
     pub fn get_replies(
         &self,
         parent_post_id: &str,
         limit: usize,
         before: Option<u64>,
-    ) -> anyhow::Result<Vec<Post>> {
+    ) -> anyhow::Result<Vec<PostView>> {
         let db = self.db.lock().unwrap();
-        let hidden = "AND author NOT IN (SELECT pubkey FROM mutes UNION SELECT pubkey FROM blocks)";
+        let hidden = format!(
+            "AND author NOT IN ({})",
+            hidden_pubkeys_sql(MuteScope::Posts)
+        );
         let mut posts = Vec::new();
         match before {
             Some(b) => {
                 let sql = format!(
-                    "SELECT id, author, content, timestamp, media_json, reply_to, reply_to_author, quote_of, quote_of_author, signature FROM posts
+                    "SELECT {POST_COLUMNS} FROM posts p
                      WHERE reply_to=?1 AND timestamp < ?2 {hidden} ORDER BY timestamp ASC LIMIT ?3"
                 );
                 let mut stmt = db.prepare(&sql)?;
@@ -297,7 +745,7 @@ impl Storage {
             }
             None => {
                 let sql = format!(
-                    "SELECT id, author, content, timestamp, media_json, reply_to, reply_to_author, quote_of, quote_of_author, signature FROM posts
+                    "SELECT {POST_COLUMNS} FROM posts p
                      WHERE reply_to=?1 {hidden} ORDER BY timestamp ASC LIMIT ?2"
                 );
                 let mut stmt = db.prepare(&sql)?;
@@ -307,6 +755,702 @@ impl Storage {
                 }
             }
         }
+        Self::apply_latest_edits(&db, &mut posts)?;
+        Self::apply_sensitive_modes(&db, &mut posts)?;
+        self.apply_muted_words(&db, &mut posts)?;
         Ok(posts)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::PostBody;
+    use crate::storage::test_util::post;
+    use iroh_social_types::{
+        Audience, FilterScope, FollowEntry, LinkPreview, MutedWord, MutedWordKind, PostEdit,
+        Profile, StoredMessage, UserList, now_millis,
+    };
+
+    fn media(hash: &str) -> MediaAttachment {
+        MediaAttachment {
+            hash: hash.to_string(),
+            ticket: String::new(),
+            mime_type: "image/png".to_string(),
+            filename: format!("{hash}.png"),
+            size: 1,
+            key: None,
+        }
+    }
+
+    fn edit(id: &str, post_id: &str, author: &str, media: Vec<MediaAttachment>) -> PostEdit {
+        PostEdit {
+            id: id.to_string(),
+            post_id: post_id.to_string(),
+            author: author.to_string(),
+            content: "edited".to_string(),
+            media,
+            timestamp: 700,
+            signature: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_delete_expired_posts() {
+        let storage = Storage::open(":memory:").unwrap();
+        for (id, expires_at) in [
+            ("gone", Some(1_000)),
+            ("kept", Some(5_000)),
+            ("forever", None),
+        ] {
+            storage
+                .insert_post(&Post {
+                    expires_at,
+                    ..post(id, "alice", 500)
+                })
+                .unwrap();
+        }
+        storage
+            .insert_post_edit(&edit("e1", "gone", "alice", vec![]))
+            .unwrap();
+        storage
+            .insert_notification("reply", "bob", Some("gone"), Some("r1"), 600)
+            .unwrap();
+        storage
+            .insert_notification("like", "bob", Some("kept"), None, 600)
+            .unwrap();
+
+        let expired = storage.delete_expired_posts(2_000).unwrap();
+        let ids: Vec<&str> = expired.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["gone"]);
+        assert!(storage.get_post_by_id("gone").unwrap().is_none());
+        assert!(storage.get_post_by_id("kept").unwrap().is_some());
+        assert!(storage.get_post_by_id("forever").unwrap().is_some());
+        assert!(storage.get_post_edits("gone", "alice").unwrap().is_empty());
+        let notifications: Vec<Option<String>> = storage
+            .get_notifications(10, None)
+            .unwrap()
+            .into_iter()
+            .map(|n| n.target_post_id)
+            .collect();
+        assert_eq!(notifications, [Some("kept".to_string())]);
+
+        assert!(storage.delete_expired_posts(2_000).unwrap().is_empty());
+        let later = storage.delete_expired_posts(5_000).unwrap();
+        assert_eq!(later.len(), 1);
+        assert_eq!(later[0].id, "kept");
+    }
+
+    #[test]
+    fn test_is_blob_referenced() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage
+            .insert_post(&Post {
+                media: vec![media("h1")],
+                link_preview: Some(Box::new(LinkPreview {
+                    url: "https://example.com".to_string(),
+                    title: "Example".to_string(),
+                    description: None,
+                    image: Some(media("h2")),
+                })),
+                ..post("p1", "alice", 500)
+            })
+            .unwrap();
+        storage
+            .insert_post_edit(&edit("e1", "p1", "alice", vec![media("h3")]))
+            .unwrap();
+        storage
+            .upsert_conversation("bob", "alice", 600, "", false)
+            .unwrap();
+        storage
+            .insert_dm_message(&StoredMessage {
+                id: "m1".to_string(),
+                conversation_id: Storage::conversation_id("alice", "bob"),
+                from_pubkey: "bob".to_string(),
+                to_pubkey: "alice".to_string(),
+                content: String::new(),
+                timestamp: 600,
+                media: vec![media("h4")],
+                read: false,
+                delivered: false,
+                reply_to: None,
+                failed: false,
+            })
+            .unwrap();
+        storage
+            .save_profile(
+                "carol",
+                &Profile {
+                    display_name: "carol".to_string(),
+                    bio: String::new(),
+                    avatar_hash: Some("h5".to_string()),
+                    avatar_ticket: None,
+                    is_private: false,
+                    pinned_posts: vec![],
+                    updated_at: None,
+                    signature: None,
+                },
+            )
+            .unwrap();
+
+        let body = |hash: &str| PostBody {
+            content: String::new(),
+            media: vec![media(hash)],
+            reply_to: None,
+            reply_to_author: None,
+            quote_of: None,
+            quote_of_author: None,
+            poll: None,
+            content_warning: None,
+            sensitive_media: false,
+            expires_at: None,
+        };
+        storage.save_draft("d1", &body("h7"), 700).unwrap();
+        storage
+            .insert_scheduled_post("s1", &body("h8"), 900, 700)
+            .unwrap();
+
+        for hash in ["h1", "h2", "h3", "h4", "h5", "h7", "h8"] {
+            assert!(storage.is_blob_referenced(hash).unwrap(), "{hash}");
+        }
+        // Only whole hashes match
+        assert!(!storage.is_blob_referenced("h").unwrap());
+        assert!(!storage.is_blob_referenced("h6").unwrap());
+        let all = storage.referenced_blob_hashes().unwrap();
+        assert_eq!(
+            all,
+            HashSet::from(["h1", "h2", "h3", "h4", "h5", "h7", "h8"].map(String::from))
+        );
+
+        // Deleting the post drops its media, preview and edits
+        storage.delete_post("p1").unwrap();
+        for hash in ["h1", "h2", "h3"] {
+            assert!(!storage.is_blob_referenced(hash).unwrap(), "{hash}");
+        }
+        assert!(storage.is_blob_referenced("h4").unwrap());
+        let left = storage.referenced_blob_hashes().unwrap();
+        assert!(["h1", "h2", "h3"].iter().all(|h| !left.contains(*h)));
+    }
+
+    fn repost(id: &str, author: &str, target: &str, timestamp: u64) -> Post {
+        Post {
+            content: String::new(),
+            quote_of: Some(target.to_string()),
+            quote_of_author: Some("alice".to_string()),
+            ..post(id, author, timestamp)
+        }
+    }
+
+    fn feed(storage: &Storage, exclude_reposts: bool) -> Vec<PostView> {
+        storage
+            .get_feed(&FeedQuery {
+                limit: 20,
+                exclude_reposts,
+                ..Default::default()
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_reposts_of_one_target_collapse_to_newest() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage.insert_post(&post("p1", "alice", 100)).unwrap();
+        storage
+            .insert_post(&repost("r1", "bob", "p1", 200))
+            .unwrap();
+        storage
+            .insert_post(&repost("r2", "carol", "p1", 300))
+            .unwrap();
+        storage
+            .insert_post(&Post {
+                content: "so true".to_string(),
+                quote_of: Some("p1".to_string()),
+                quote_of_author: Some("alice".to_string()),
+                ..post("q1", "dave", 250)
+            })
+            .unwrap();
+
+        let posts = feed(&storage, false);
+        let ids: Vec<&str> = posts.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["r2", "q1", "p1"]);
+        assert_eq!(posts[0].reposted_by, ["carol", "bob"]);
+        // Quotes are not reposts and carry no reposters
+        assert!(posts[1].reposted_by.is_empty());
+
+        let ids: Vec<String> = feed(&storage, true)
+            .into_iter()
+            .map(|p| p.post.id)
+            .collect();
+        assert_eq!(ids, ["q1", "p1"]);
+    }
+
+    #[test]
+    fn test_reposts_by_muted_reposter_are_skipped() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage.insert_post(&post("p1", "alice", 100)).unwrap();
+        storage
+            .insert_post(&repost("r1", "bob", "p1", 200))
+            .unwrap();
+        storage
+            .insert_post(&repost("r2", "carol", "p1", 300))
+            .unwrap();
+        storage
+            .mute_user("carol", MuteScope::Reposts, None)
+            .unwrap();
+
+        // The newest visible repost stands in for the rest
+        let posts = feed(&storage, false);
+        let ids: Vec<&str> = posts.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["r1", "p1"]);
+        assert_eq!(posts[0].reposted_by, ["bob"]);
+
+        storage.mute_user("bob", MuteScope::Reposts, None).unwrap();
+        let ids: Vec<String> = feed(&storage, false)
+            .into_iter()
+            .map(|p| p.post.id)
+            .collect();
+        assert_eq!(ids, ["p1"]);
+    }
+
+    #[test]
+    fn test_audience_posts_served_only_to_members() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage.insert_post(&post("public", "me", 100)).unwrap();
+        storage
+            .create_audience(&Audience {
+                id: "a1".to_string(),
+                name: "close".to_string(),
+                members: vec!["bob".to_string()],
+                created_at: 1,
+            })
+            .unwrap();
+        storage
+            .insert_audience_post(&post("shared", "me", 200), "close")
+            .unwrap();
+        storage.set_post_audience("shared", "a1").unwrap();
+
+        let served = |viewer: Option<&str>| -> Vec<String> {
+            storage
+                .get_posts_not_in("me", &[], viewer, 10, 0)
+                .unwrap()
+                .into_iter()
+                .map(|p| p.post.id)
+                .collect()
+        };
+        assert_eq!(served(None), ["public", "shared"]);
+        assert_eq!(served(Some("bob")), ["public", "shared"]);
+        assert_eq!(served(Some("carol")), ["public"]);
+        assert_eq!(storage.count_posts_by_author("me", Some("bob")).unwrap(), 2);
+        assert_eq!(
+            storage.count_posts_by_author("me", Some("carol")).unwrap(),
+            1
+        );
+        assert_eq!(
+            storage.count_posts_after("me", 150, Some("carol")).unwrap(),
+            0
+        );
+        let after: Vec<String> = storage
+            .get_posts_after("me", 0, Some("bob"), 10, 0)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.post.id)
+            .collect();
+        assert_eq!(after, ["public", "shared"]);
+
+        // Dropping a member stops serving them
+        storage.update_audience("a1", "close", &[], 2).unwrap();
+        assert_eq!(served(Some("bob")), ["public"]);
+
+        // Audience posts shared with us have no list here, so they are not
+        // served to anyone
+        storage
+            .insert_audience_post(&post("theirs", "alice", 300), "friends")
+            .unwrap();
+        assert_eq!(
+            storage.count_posts_by_author("alice", Some("bob")).unwrap(),
+            0
+        );
+        assert_eq!(storage.count_posts_by_author("alice", None).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_hashtag_filter_matches_whole_tags_in_any_case() {
+        let storage = Storage::open(":memory:").unwrap();
+        let contents = [
+            ("upper", "#Rust is great"),
+            ("end", "ending on #rust"),
+            ("punct", "(#rust)."),
+            ("shout", "#RUST!"),
+            ("longer", "hello #rustacean"),
+            ("snake", "#rust_lang"),
+            ("glued", "x#rust"),
+            ("other", "#golang"),
+            ("go", "plain #go"),
+        ];
+        for (i, (id, content)) in contents.iter().enumerate() {
+            storage
+                .insert_post(&Post {
+                    content: content.to_string(),
+                    ..post(id, "alice", i as u64 + 1)
+                })
+                .unwrap();
+        }
+        let tagged = |tags: &[&str]| -> Vec<String> {
+            let mut ids: Vec<String> = storage
+                .get_feed(&FeedQuery {
+                    limit: 20,
+                    hashtags: tags.iter().map(|t| t.to_string()).collect(),
+                    ..Default::default()
+                })
+                .unwrap()
+                .into_iter()
+                .map(|p| p.post.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(tagged(&["rust"]), ["end", "punct", "shout", "upper"]);
+        assert_eq!(tagged(&["rustacean"]), ["longer"]);
+        // Any of several tags
+        assert_eq!(tagged(&["go", "rust_lang"]), ["go", "snake"]);
+    }
+
+    #[test]
+    fn test_list_filter_keeps_members_posts() {
+        let storage = Storage::open(":memory:").unwrap();
+        for (i, author) in ["bob", "carol", "dave"].iter().enumerate() {
+            storage
+                .insert_post(&post(&format!("{author}-post"), author, i as u64 + 1))
+                .unwrap();
+        }
+        for (id, members) in [("l1", vec!["bob"]), ("l2", vec!["carol", "bob"])] {
+            storage
+                .create_list(&UserList {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    members: members.into_iter().map(String::from).collect(),
+                    created_at: 1,
+                })
+                .unwrap();
+        }
+        let listed = |list_ids: &[&str]| -> Vec<String> {
+            storage
+                .get_feed(&FeedQuery {
+                    limit: 20,
+                    list_ids: list_ids.iter().map(|t| t.to_string()).collect(),
+                    ..Default::default()
+                })
+                .unwrap()
+                .into_iter()
+                .map(|p| p.post.id)
+                .collect()
+        };
+        assert_eq!(listed(&["l1"]), ["bob-post"]);
+        assert_eq!(listed(&["l1", "l2"]), ["carol-post", "bob-post"]);
+        assert_eq!(listed(&[]), ["dave-post", "carol-post", "bob-post"]);
+
+        // Lists don't depend on follows: following and then unfollowing a
+        // member leaves them on their lists
+        storage
+            .follow(&FollowEntry {
+                pubkey: "bob".to_string(),
+                alias: None,
+                followed_at: 1,
+            })
+            .unwrap();
+        storage.unfollow("bob").unwrap();
+        assert_eq!(listed(&["l1"]), ["bob-post"]);
+        assert_eq!(listed(&["l1", "l2"]), ["carol-post", "bob-post"]);
+    }
+
+    #[test]
+    fn test_sync_peers_include_unfollowed_list_members() {
+        let storage = Storage::open(":memory:").unwrap();
+        for (pubkey, followed_at) in [("alice", 1), ("bob", 2)] {
+            storage
+                .follow(&FollowEntry {
+                    pubkey: pubkey.to_string(),
+                    alias: None,
+                    followed_at,
+                })
+                .unwrap();
+        }
+        storage
+            .create_list(&UserList {
+                id: "l1".to_string(),
+                name: "l1".to_string(),
+                members: ["carol", "alice", "dave", "carol"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                created_at: 1,
+            })
+            .unwrap();
+        assert_eq!(
+            storage.get_sync_peers().unwrap(),
+            ["bob", "alice", "carol", "dave"]
+        );
+    }
+
+    fn reply(id: &str, parent: &str, timestamp: u64) -> Post {
+        Post {
+            reply_to: Some(parent.to_string()),
+            ..post(id, "alice", timestamp)
+        }
+    }
+
+    fn ids(posts: Vec<PostView>) -> Vec<String> {
+        posts.into_iter().map(|p| p.post.id).collect()
+    }
+
+    #[test]
+    fn test_conversation_ancestors_and_descendants() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage.insert_post(&post("root", "alice", 1)).unwrap();
+        for (id, parent, ts) in [
+            ("a", "root", 2),
+            ("b", "root", 3),
+            ("a1", "a", 4),
+            ("a1x", "a1", 5),
+        ] {
+            storage.insert_post(&reply(id, parent, ts)).unwrap();
+        }
+        storage
+            .insert_post(&Post {
+                author: "bob".to_string(),
+                ..reply("muted", "a", 6)
+            })
+            .unwrap();
+        storage.mute_user("bob", MuteScope::Posts, None).unwrap();
+
+        assert_eq!(
+            ids(storage.get_ancestors("a1x", 100).unwrap()),
+            ["root", "a", "a1", "a1x"]
+        );
+        assert_eq!(ids(storage.get_ancestors("a1x", 1).unwrap()), ["a1", "a1x"]);
+
+        // Shallowest first, then oldest; muted authors left out
+        assert_eq!(
+            ids(storage.get_descendants("root", 6, 100).unwrap()),
+            ["a", "b", "a1", "a1x"]
+        );
+        assert_eq!(
+            ids(storage.get_descendants("root", 1, 100).unwrap()),
+            ["a", "b"]
+        );
+        assert_eq!(
+            ids(storage.get_descendants("root", 6, 3).unwrap()),
+            ["a", "b", "a1"]
+        );
+    }
+
+    #[test]
+    fn test_conversation_queries_stop_at_reply_cycles() {
+        let storage = Storage::open(":memory:").unwrap();
+        // x -> y -> z -> x, with w hanging off z
+        storage.insert_post(&reply("x", "z", 1)).unwrap();
+        storage.insert_post(&reply("y", "x", 2)).unwrap();
+        storage.insert_post(&reply("z", "y", 3)).unwrap();
+        storage.insert_post(&reply("w", "z", 4)).unwrap();
+
+        // The focus never comes back as its own descendant, and no post
+        // repeats however deep we ask
+        assert_eq!(
+            ids(storage.get_descendants("x", 50, 100).unwrap()),
+            ["y", "z", "w"]
+        );
+        assert_eq!(
+            ids(storage.get_ancestors("x", 100).unwrap()),
+            ["y", "z", "x"]
+        );
+    }
+
+    #[test]
+    fn test_thread_follows_the_authors_own_replies() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage
+            .insert_posts(&[
+                post("t1", "alice", 10),
+                reply("t2", "t1", 11),
+                reply("t3", "t2", 12),
+            ])
+            .unwrap();
+        storage
+            .insert_post(&Post {
+                author: "bob".to_string(),
+                ..reply("bob-reply", "t2", 13)
+            })
+            .unwrap();
+        storage.insert_post(&reply("t1-aside", "t1", 14)).unwrap();
+
+        // Other people's replies are not part of the thread
+        assert_eq!(
+            ids(storage.get_thread("t1").unwrap()),
+            ["t1", "t2", "t3", "t1-aside"]
+        );
+        assert_eq!(ids(storage.get_thread("t2").unwrap()), ["t2", "t3"]);
+        assert!(storage.get_thread("missing").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_thread_stops_at_reply_cycles() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage
+            .insert_posts(&[reply("x", "y", 1), reply("y", "x", 2)])
+            .unwrap();
+        assert_eq!(ids(storage.get_thread("x").unwrap()), ["x", "y"]);
+    }
+
+    #[test]
+    fn test_feed_cursor_pages_through_tied_timestamps() {
+        let storage = Storage::open(":memory:").unwrap();
+        for id in ["a", "b", "c", "d"] {
+            storage.insert_post(&post(id, "alice", 500)).unwrap();
+        }
+        storage.insert_post(&post("old", "alice", 400)).unwrap();
+        storage.insert_post(&post("new", "alice", 600)).unwrap();
+
+        let mut seen = Vec::new();
+        let (mut before, mut before_id) = (None, None);
+        loop {
+            let page = storage
+                .get_feed(&FeedQuery {
+                    limit: 2,
+                    before,
+                    before_id: before_id.clone(),
+                    ..Default::default()
+                })
+                .unwrap();
+            let Some(last) = page.last() else {
+                break;
+            };
+            before = Some(last.timestamp);
+            before_id = Some(last.id.clone());
+            seen.extend(page.into_iter().map(|p| p.post.id));
+        }
+        // Every post exactly once, ties broken by id
+        assert_eq!(seen, ["new", "d", "c", "b", "a", "old"]);
+
+        // A timestamp-only cursor skips the rest of the tie
+        let page: Vec<String> = storage
+            .get_feed(&FeedQuery {
+                limit: 10,
+                before: Some(500),
+                ..Default::default()
+            })
+            .unwrap()
+            .into_iter()
+            .map(|p| p.post.id)
+            .collect();
+        assert_eq!(page, ["old"]);
+    }
+
+    #[test]
+    fn test_reply_filter() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage
+            .follow(&FollowEntry {
+                pubkey: "bob".to_string(),
+                alias: None,
+                followed_at: 1,
+            })
+            .unwrap();
+        storage.insert_post(&post("root", "alice", 100)).unwrap();
+        for (id, to, ts) in [("to-bob", "bob", 200), ("to-carol", "carol", 300)] {
+            storage
+                .insert_post(&Post {
+                    reply_to: Some(format!("{to}-post")),
+                    reply_to_author: Some(to.to_string()),
+                    ..post(id, "alice", ts)
+                })
+                .unwrap();
+        }
+        let replies = |replies: ReplyFilter| -> Vec<String> {
+            storage
+                .get_feed(&FeedQuery {
+                    limit: 10,
+                    replies,
+                    ..Default::default()
+                })
+                .unwrap()
+                .into_iter()
+                .map(|p| p.post.id)
+                .collect()
+        };
+        assert_eq!(replies(ReplyFilter::All), ["to-carol", "to-bob", "root"]);
+        assert_eq!(replies(ReplyFilter::None), ["root"]);
+        assert_eq!(replies(ReplyFilter::Following), ["to-bob", "root"]);
+    }
+
+    #[test]
+    fn test_media_and_own_post_filters() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage.insert_post(&post("mine", "me", 100)).unwrap();
+        storage.insert_post(&post("text", "alice", 200)).unwrap();
+        storage
+            .insert_post(&Post {
+                media: vec![media("h1")],
+                ..post("photo", "alice", 300)
+            })
+            .unwrap();
+        let filtered = |media_only: bool, exclude_author: Option<&str>| -> Vec<String> {
+            storage
+                .get_feed(&FeedQuery {
+                    limit: 10,
+                    media_only,
+                    exclude_author: exclude_author.map(String::from),
+                    ..Default::default()
+                })
+                .unwrap()
+                .into_iter()
+                .map(|p| p.post.id)
+                .collect()
+        };
+        assert_eq!(filtered(false, None), ["photo", "text", "mine"]);
+        assert_eq!(filtered(true, None), ["photo"]);
+        assert_eq!(filtered(false, Some("me")), ["photo", "text"]);
+    }
+
+    #[test]
+    fn test_muted_word_changes_reach_cached_filter() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage
+            .insert_post(&Post {
+                content: "spoilers ahead".to_string(),
+                ..post("p1", "alice", 100)
+            })
+            .unwrap();
+        let filtered_by = || {
+            storage
+                .get_feed(&FeedQuery {
+                    limit: 10,
+                    ..Default::default()
+                })
+                .unwrap()
+                .remove(0)
+                .filtered_by
+        };
+        let word = |id: &str, expires_at: Option<u64>| MutedWord {
+            id: id.to_string(),
+            pattern: "spoilers".to_string(),
+            kind: MutedWordKind::Word,
+            scope: FilterScope::Both,
+            expires_at,
+            created_at: 1,
+        };
+        assert_eq!(filtered_by(), None);
+
+        storage.add_muted_word(&word("w1", None)).unwrap();
+        assert_eq!(filtered_by().as_deref(), Some("spoilers"));
+        storage.remove_muted_word("w1").unwrap();
+        assert_eq!(filtered_by(), None);
+
+        // A word that has expired no longer matches, even before the sweep
+        storage
+            .add_muted_word(&word("w2", Some(now_millis() + 50)))
+            .unwrap();
+        assert_eq!(filtered_by().as_deref(), Some("spoilers"));
+        std::thread::sleep(std::time::Duration::from_millis(60));
+        assert_eq!(filtered_by(), None);
+    }
+}
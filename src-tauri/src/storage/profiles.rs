@@ -4,21 +4,26 @@ use rusqlite::params;
 use super::Storage;
 
 impl Storage {
-    pub fn save_profile(&self, pubkey: &str, profile: &Profile) -> anyhow::Result<()> {
+    /// Store `pubkey`'s profile unless the one we hold is newer. Returns
+    /// false if it was older and left out.
+    pub fn save_profile(&self, pubkey: &str, profile: &Profile) -> anyhow::Result<bool> {
+        let pinned_json = serde_json::to_string(&profile.pinned_posts)?;
         let db = self.db.lock().unwrap();
-        db.execute(
-            "INSERT INTO profiles (pubkey, display_name, bio, avatar_hash, avatar_ticket, is_private)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-             ON CONFLICT(pubkey) DO UPDATE SET display_name=?2, bio=?3, avatar_hash=?4, avatar_ticket=?5, is_private=?6",
-            params![pubkey, profile.display_name, profile.bio, profile.avatar_hash, profile.avatar_ticket, profile.is_private as i32],
+        let count = db.execute(
+            "INSERT INTO profiles (pubkey, display_name, bio, avatar_hash, avatar_ticket, is_private, pinned_posts, signature, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT(pubkey) DO UPDATE SET display_name=?2, bio=?3, avatar_hash=?4, avatar_ticket=?5, is_private=?6, pinned_posts=?7, signature=?8, updated_at=?9
+             WHERE COALESCE(?9, 0) >= COALESCE(updated_at, 0)",
+            params![pubkey, profile.display_name, profile.bio, profile.avatar_hash, profile.avatar_ticket, profile.is_private as i32, pinned_json, profile.signature, profile.updated_at.map(|t| t as i64)],
         )?;
-        Ok(())
+        Ok(count > 0)
     }
 
     pub fn get_profile(&self, pubkey: &str) -> anyhow::Result<Option<Profile>> {
         let db = self.db.lock().unwrap();
         let mut stmt = db.prepare(
-            "SELECT display_name, bio, avatar_hash, avatar_ticket, is_private FROM profiles WHERE pubkey=?1",
+            "SELECT display_name, bio, avatar_hash, avatar_ticket, is_private, pinned_posts, signature, updated_at
+             FROM profiles WHERE pubkey=?1",
         )?;
         let mut rows = stmt.query(params![pubkey])?;
         match rows.next()? {
@@ -28,8 +33,70 @@ impl Storage {
                 avatar_hash: row.get(2)?,
                 avatar_ticket: row.get(3)?,
                 is_private: row.get::<_, i32>(4)? != 0,
+                pinned_posts: serde_json::from_str(&row.get::<_, String>(5)?).unwrap_or_default(),
+                signature: row.get(6)?,
+                updated_at: row.get::<_, Option<i64>>(7)?.map(|t| t as u64),
             })),
             None => Ok(None),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(name: &str, updated_at: Option<u64>) -> Profile {
+        Profile {
+            display_name: name.to_string(),
+            bio: String::new(),
+            avatar_hash: None,
+            avatar_ticket: None,
+            is_private: false,
+            pinned_posts: vec![],
+            updated_at,
+            signature: None,
+        }
+    }
+
+    fn name(storage: &Storage) -> String {
+        storage.get_profile("alice").unwrap().unwrap().display_name
+    }
+
+    #[test]
+    fn test_older_profile_is_ignored() {
+        let storage = Storage::open(":memory:").unwrap();
+        assert!(
+            storage
+                .save_profile("alice", &profile("old", None))
+                .unwrap()
+        );
+        assert!(
+            storage
+                .save_profile("alice", &profile("v2", Some(2000)))
+                .unwrap()
+        );
+        assert_eq!(name(&storage), "v2");
+
+        assert!(
+            !storage
+                .save_profile("alice", &profile("v1", Some(1000)))
+                .unwrap()
+        );
+        assert!(
+            !storage
+                .save_profile("alice", &profile("legacy", None))
+                .unwrap()
+        );
+        assert_eq!(name(&storage), "v2");
+        let stored = storage.get_profile("alice").unwrap().unwrap();
+        assert_eq!(stored.updated_at, Some(2000));
+
+        assert!(
+            storage
+                .save_profile("alice", &profile("v2 again", Some(2000)))
+                .unwrap()
+        );
+        assert_eq!(name(&storage), "v2 again");
+    }
+}
@@ -0,0 +1,233 @@
+use iroh_social_types::{MediaAttachment, PostEdit};
+use rusqlite::{Connection, OptionalExtension, params};
+
+use super::{PostView, Storage};
+
+impl Storage {
+    fn row_to_post_edit(row: &rusqlite::Row) -> anyhow::Result<PostEdit> {
+        let media_json: String = row.get(4)?;
+        Ok(PostEdit {
+            id: row.get(0)?,
+            post_id: row.get(1)?,
+            author: row.get(2)?,
+            content: row.get(3)?,
+            media: serde_json::from_str(&media_json)?,
+            timestamp: row.get::<_, i64>(5)? as u64,
+            signature: row.get(6)?,
+        })
+    }
+
+    /// Replace a post's content and media with its newest edit by the same
+    /// author, if any, and mark it edited.
+    pub(crate) fn apply_latest_edit(db: &Connection, post: &mut PostView) -> anyhow::Result<()> {
+        let latest: Option<(String, String, i64)> = db
+            .query_row(
+                "SELECT content, media_json, timestamp FROM post_revisions
+                 WHERE post_id=?1 AND author=?2 ORDER BY timestamp DESC LIMIT 1",
+                params![post.id, post.author],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        if let Some((content, media_json, timestamp)) = latest {
+            let media: Vec<MediaAttachment> = serde_json::from_str(&media_json)?;
+            post.post.content = content;
+            post.post.media = media;
+            post.edited_at = Some(timestamp as u64);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn apply_latest_edits(
+        db: &Connection,
+        posts: &mut [PostView],
+    ) -> anyhow::Result<()> {
+        for post in posts {
+            Self::apply_latest_edit(db, post)?;
+        }
+        Ok(())
+    }
+
+    /// Store an edit. Returns false if it was already known.
+    pub fn insert_post_edit(&self, edit: &PostEdit) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let media_json = serde_json::to_string(&edit.media)?;
+        let count = db.execute(
+            "INSERT OR IGNORE INTO post_revisions (id, post_id, author, content, media_json, timestamp, signature)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                edit.id,
+                edit.post_id,
+                edit.author,
+                edit.content,
+                media_json,
+                edit.timestamp as i64,
+                edit.signature,
+            ],
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Get a post with its newest edit applied, as displayed.
+    pub fn get_post_latest(&self, id: &str) -> anyhow::Result<Option<PostView>> {
+        let Some(mut post) = self.get_post_by_id(id)? else {
+            return Ok(None);
+        };
+        let db = self.db.lock().unwrap();
+        Self::apply_latest_edit(&db, &mut post)?;
+        Self::apply_sensitive_modes(&db, std::slice::from_mut(&mut post))?;
+        Ok(Some(post))
+    }
+
+    /// All edits of a post by its author, oldest first.
+    pub fn get_post_edits(&self, post_id: &str, author: &str) -> anyhow::Result<Vec<PostEdit>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(
+            "SELECT id, post_id, author, content, media_json, timestamp, signature
+             FROM post_revisions WHERE post_id=?1 AND author=?2 ORDER BY timestamp ASC",
+        )?;
+        let mut rows = stmt.query(params![post_id, author])?;
+        let mut edits = Vec::new();
+        while let Some(row) = rows.next()? {
+            edits.push(Self::row_to_post_edit(row)?);
+        }
+        Ok(edits)
+    }
+
+    pub fn newest_edit_timestamp(&self, author: &str) -> anyhow::Result<u64> {
+        let db = self.db.lock().unwrap();
+        let ts: Option<i64> = db.query_row(
+            "SELECT MAX(timestamp) FROM post_revisions WHERE author=?1",
+            params![author],
+            |row| row.get(0),
+        )?;
+        Ok(ts.unwrap_or(0) as u64)
+    }
+
+    pub fn count_edits_after(&self, author: &str, after_ts: u64) -> anyhow::Result<u64> {
+        let db = self.db.lock().unwrap();
+        let count: i64 = db.query_row(
+            "SELECT COUNT(*) FROM post_revisions WHERE author=?1 AND timestamp > ?2",
+            params![author, after_ts as i64],
+            |row| row.get(0),
+        )?;
+        Ok(count as u64)
+    }
+
+    pub fn get_edits_after(
+        &self,
+        author: &str,
+        after_ts: u64,
+        limit: usize,
+        offset: usize,
+    ) -> anyhow::Result<Vec<PostEdit>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(
+            "SELECT id, post_id, author, content, media_json, timestamp, signature
+             FROM post_revisions WHERE author=?1 AND timestamp > ?2
+             ORDER BY timestamp ASC LIMIT ?3 OFFSET ?4",
+        )?;
+        let mut rows = stmt.query(params![
+            author,
+            after_ts as i64,
+            limit as i64,
+            offset as i64
+        ])?;
+        let mut edits = Vec::new();
+        while let Some(row) = rows.next()? {
+            edits.push(Self::row_to_post_edit(row)?);
+        }
+        Ok(edits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_util::post;
+
+    fn edit(id: &str, author: &str, content: &str, timestamp: u64) -> PostEdit {
+        PostEdit {
+            id: id.to_string(),
+            post_id: "p1".to_string(),
+            author: author.to_string(),
+            content: content.to_string(),
+            media: vec![],
+            timestamp,
+            signature: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_newest_edit_by_the_author_is_shown() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage.insert_post(&post("p1", "alice", 100)).unwrap();
+        assert!(
+            storage
+                .get_post_latest("p1")
+                .unwrap()
+                .unwrap()
+                .edited_at
+                .is_none()
+        );
+
+        assert!(
+            storage
+                .insert_post_edit(&edit("e2", "alice", "second", 300))
+                .unwrap()
+        );
+        assert!(
+            storage
+                .insert_post_edit(&edit("e1", "alice", "first", 200))
+                .unwrap()
+        );
+        assert!(
+            !storage
+                .insert_post_edit(&edit("e1", "alice", "first", 200))
+                .unwrap()
+        );
+        // An edit by someone else never replaces the author's content.
+        storage
+            .insert_post_edit(&edit("e3", "mallory", "forged", 400))
+            .unwrap();
+
+        let latest = storage.get_post_latest("p1").unwrap().unwrap();
+        assert_eq!(latest.post.content, "second");
+        assert_eq!(latest.edited_at, Some(300));
+
+        let history: Vec<String> = storage
+            .get_post_edits("p1", "alice")
+            .unwrap()
+            .into_iter()
+            .map(|e| e.id)
+            .collect();
+        assert_eq!(history, vec!["e1", "e2"]);
+    }
+
+    #[test]
+    fn test_edits_after_page_in_timestamp_order() {
+        let storage = Storage::open(":memory:").unwrap();
+        assert_eq!(storage.newest_edit_timestamp("alice").unwrap(), 0);
+        for (i, ts) in [(1, 100), (2, 200), (3, 300)] {
+            storage
+                .insert_post_edit(&edit(&format!("e{i}"), "alice", "x", ts))
+                .unwrap();
+        }
+        storage
+            .insert_post_edit(&edit("other", "bob", "x", 500))
+            .unwrap();
+
+        assert_eq!(storage.newest_edit_timestamp("alice").unwrap(), 300);
+        assert_eq!(storage.count_edits_after("alice", 100).unwrap(), 2);
+        let page = |offset| -> Vec<String> {
+            storage
+                .get_edits_after("alice", 100, 1, offset)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.id)
+                .collect()
+        };
+        assert_eq!(page(0), vec!["e2"]);
+        assert_eq!(page(1), vec!["e3"]);
+        assert!(page(2).is_empty());
+    }
+}
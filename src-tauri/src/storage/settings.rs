@@ -0,0 +1,146 @@
+use iroh_social_types::{DmPolicy, SensitiveMode};
+use rusqlite::{OptionalExtension, params};
+
+use super::Storage;
+
+const DM_POLICY_KEY: &str = "dm_policy";
+pub(crate) const SENSITIVE_MODE_KEY: &str = "sensitive_content";
+const LINK_PREVIEWS_KEY: &str = "link_previews";
+
+impl Storage {
+    pub fn get_setting(&self, key: &str) -> anyhow::Result<Option<String>> {
+        let db = self.db.lock().unwrap();
+        let value = db
+            .query_row(
+                "SELECT value FROM settings WHERE key=?1",
+                params![key],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(value)
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value=?2",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_dm_policy(&self) -> anyhow::Result<DmPolicy> {
+        Ok(self
+            .get_setting(DM_POLICY_KEY)?
+            .and_then(|v| DmPolicy::parse(&v))
+            .unwrap_or_default())
+    }
+
+    pub fn set_dm_policy(&self, policy: DmPolicy) -> anyhow::Result<()> {
+        self.set_setting(DM_POLICY_KEY, policy.as_str())
+    }
+
+    /// Whether a new conversation from `peer` goes straight to the inbox
+    /// under the current accept policy, rather than into message requests.
+    pub fn accepts_dms_from(&self, peer_pubkey: &str) -> anyhow::Result<bool> {
+        Ok(match self.get_dm_policy()? {
+            DmPolicy::Everyone => true,
+            DmPolicy::Following => self.is_following(peer_pubkey)?,
+            DmPolicy::Mutuals => {
+                self.is_following(peer_pubkey)? && self.is_follower(peer_pubkey)?
+            }
+        })
+    }
+
+    pub fn get_sensitive_mode(&self) -> anyhow::Result<SensitiveMode> {
+        Ok(self
+            .get_setting(SENSITIVE_MODE_KEY)?
+            .and_then(|v| SensitiveMode::parse(&v))
+            .unwrap_or_default())
+    }
+
+    pub fn set_sensitive_mode(&self, mode: SensitiveMode) -> anyhow::Result<()> {
+        self.set_setting(SENSITIVE_MODE_KEY, mode.as_str())
+    }
+
+    /// Whether our posts get link previews. Off unless turned on, since
+    /// fetching a preview reveals our address to the linked site.
+    pub fn get_link_previews_enabled(&self) -> anyhow::Result<bool> {
+        Ok(self.get_setting(LINK_PREVIEWS_KEY)?.as_deref() == Some("on"))
+    }
+
+    pub fn set_link_previews_enabled(&self, enabled: bool) -> anyhow::Result<()> {
+        self.set_setting(LINK_PREVIEWS_KEY, if enabled { "on" } else { "off" })
+    }
+
+    pub fn get_sensitive_override(&self, pubkey: &str) -> anyhow::Result<Option<SensitiveMode>> {
+        let db = self.db.lock().unwrap();
+        let mode: Option<String> = db
+            .query_row(
+                "SELECT mode FROM sensitive_overrides WHERE pubkey=?1",
+                params![pubkey],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(mode.and_then(|m| SensitiveMode::parse(&m)))
+    }
+
+    /// Set or clear (with `None`) how one author's sensitive posts are shown.
+    pub fn set_sensitive_override(
+        &self,
+        pubkey: &str,
+        mode: Option<SensitiveMode>,
+    ) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        match mode {
+            Some(mode) => db.execute(
+                "INSERT INTO sensitive_overrides (pubkey, mode) VALUES (?1, ?2)
+                 ON CONFLICT(pubkey) DO UPDATE SET mode=?2",
+                params![pubkey, mode.as_str()],
+            )?,
+            None => db.execute(
+                "DELETE FROM sensitive_overrides WHERE pubkey=?1",
+                params![pubkey],
+            )?,
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh_social_types::FollowEntry;
+
+    #[test]
+    fn test_dm_policy_decides_who_lands_in_the_inbox() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage
+            .follow(&FollowEntry {
+                pubkey: "friend".to_string(),
+                alias: None,
+                followed_at: 1,
+            })
+            .unwrap();
+        storage.upsert_follower("friend", 1).unwrap();
+        storage
+            .follow(&FollowEntry {
+                pubkey: "followed".to_string(),
+                alias: None,
+                followed_at: 1,
+            })
+            .unwrap();
+
+        assert_eq!(storage.get_dm_policy().unwrap(), DmPolicy::Everyone);
+        assert!(storage.accepts_dms_from("stranger").unwrap());
+
+        storage.set_dm_policy(DmPolicy::Following).unwrap();
+        assert!(storage.accepts_dms_from("followed").unwrap());
+        assert!(!storage.accepts_dms_from("stranger").unwrap());
+
+        storage.set_dm_policy(DmPolicy::Mutuals).unwrap();
+        assert!(storage.accepts_dms_from("friend").unwrap());
+        assert!(!storage.accepts_dms_from("followed").unwrap());
+    }
+}
@@ -1,4 +1,7 @@
-use iroh_social_types::{FollowEntry, FollowerEntry};
+use iroh_social_types::{
+    ApprovedFollower, FollowEntry, FollowRequest, FollowRequestEntry, FollowRequestStatus,
+    FollowerEntry,
+};
 use rusqlite::params;
 
 use super::Storage;
@@ -26,6 +29,16 @@ impl Storage {
         Ok(exists)
     }
 
+    pub fn is_following(&self, pubkey: &str) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let exists: bool = db.query_row(
+            "SELECT COUNT(*) > 0 FROM follows WHERE pubkey=?1",
+            params![pubkey],
+            |row| row.get(0),
+        )?;
+        Ok(exists)
+    }
+
     pub fn follow(&self, entry: &FollowEntry) -> anyhow::Result<()> {
         let db = self.db.lock().unwrap();
         db.execute(
@@ -109,4 +122,178 @@ impl Storage {
         }
         Ok(followers)
     }
+
+    pub fn is_approved_follower(&self, pubkey: &str) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let exists: bool = db.query_row(
+            "SELECT COUNT(*) > 0 FROM approved_followers WHERE pubkey=?1",
+            params![pubkey],
+            |row| row.get(0),
+        )?;
+        Ok(exists)
+    }
+
+    /// Approve `pubkey` as a follower, settling any request from them.
+    pub fn approve_follower(&self, pubkey: &str, now: u64) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "INSERT OR IGNORE INTO approved_followers (pubkey, approved_at) VALUES (?1, ?2)",
+            params![pubkey, now as i64],
+        )?;
+        db.execute(
+            "UPDATE follow_requests SET status='approved' WHERE requester=?1",
+            params![pubkey],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_approved_follower(&self, pubkey: &str) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let count = db.execute(
+            "DELETE FROM approved_followers WHERE pubkey=?1",
+            params![pubkey],
+        )?;
+        db.execute(
+            "DELETE FROM follow_requests WHERE requester=?1",
+            params![pubkey],
+        )?;
+        Ok(count > 0)
+    }
+
+    pub fn get_approved_followers(&self) -> anyhow::Result<Vec<ApprovedFollower>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(
+            "SELECT pubkey, approved_at FROM approved_followers ORDER BY approved_at DESC",
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut followers = Vec::new();
+        while let Some(row) = rows.next()? {
+            followers.push(ApprovedFollower {
+                pubkey: row.get(0)?,
+                approved_at: row.get::<_, i64>(1)? as u64,
+            });
+        }
+        Ok(followers)
+    }
+
+    /// Record a verified follow request as pending. Returns false if one
+    /// from the same requester was already pending.
+    pub fn upsert_follow_request(&self, req: &FollowRequest) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let pending: bool = db.query_row(
+            "SELECT COUNT(*) > 0 FROM follow_requests WHERE requester=?1 AND status='pending'",
+            params![req.requester],
+            |row| row.get(0),
+        )?;
+        db.execute(
+            "INSERT INTO follow_requests (requester, timestamp, signature, status)
+             VALUES (?1, ?2, ?3, 'pending')
+             ON CONFLICT(requester) DO UPDATE SET timestamp=?2, signature=?3, status='pending'",
+            params![req.requester, req.timestamp as i64, req.signature],
+        )?;
+        Ok(!pending)
+    }
+
+    pub fn get_follow_request_status(
+        &self,
+        requester: &str,
+    ) -> anyhow::Result<Option<FollowRequestStatus>> {
+        let db = self.db.lock().unwrap();
+        let status: Option<String> = db
+            .query_row(
+                "SELECT status FROM follow_requests WHERE requester=?1",
+                params![requester],
+                |row| row.get(0),
+            )
+            .ok();
+        Ok(status.and_then(|s| FollowRequestStatus::parse(&s)))
+    }
+
+    /// Deny a follow request, revoking the approval if it was granted
+    /// earlier. Returns false if `requester` never asked.
+    pub fn deny_follow_request(&self, requester: &str) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let count = db.execute(
+            "UPDATE follow_requests SET status='denied' WHERE requester=?1",
+            params![requester],
+        )?;
+        if count > 0 {
+            db.execute(
+                "DELETE FROM approved_followers WHERE pubkey=?1",
+                params![requester],
+            )?;
+        }
+        Ok(count > 0)
+    }
+
+    pub fn get_pending_follow_requests(&self) -> anyhow::Result<Vec<FollowRequestEntry>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(
+            "SELECT requester, timestamp FROM follow_requests
+             WHERE status='pending' ORDER BY timestamp DESC",
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut requests = Vec::new();
+        while let Some(row) = rows.next()? {
+            requests.push(FollowRequestEntry {
+                pubkey: row.get(0)?,
+                timestamp: row.get::<_, i64>(1)? as u64,
+                status: FollowRequestStatus::Pending,
+            });
+        }
+        Ok(requests)
+    }
+
+    pub fn set_sent_follow_request(
+        &self,
+        target: &str,
+        status: FollowRequestStatus,
+        now: u64,
+    ) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "INSERT INTO sent_follow_requests (target, requested_at, status) VALUES (?1, ?2, ?3)
+             ON CONFLICT(target) DO UPDATE SET status=?3",
+            params![target, now as i64, status.as_str()],
+        )?;
+        Ok(())
+    }
+
+    /// Settle our request to follow `target`. Returns false if we never sent one.
+    pub fn update_sent_follow_request(
+        &self,
+        target: &str,
+        status: FollowRequestStatus,
+    ) -> anyhow::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let count = db.execute(
+            "UPDATE sent_follow_requests SET status=?2 WHERE target=?1",
+            params![target, status.as_str()],
+        )?;
+        Ok(count > 0)
+    }
+
+    pub fn get_sent_follow_request(
+        &self,
+        target: &str,
+    ) -> anyhow::Result<Option<FollowRequestStatus>> {
+        let db = self.db.lock().unwrap();
+        let status: Option<String> = db
+            .query_row(
+                "SELECT status FROM sent_follow_requests WHERE target=?1",
+                params![target],
+                |row| row.get(0),
+            )
+            .ok();
+        Ok(status.and_then(|s| FollowRequestStatus::parse(&s)))
+    }
+
+    pub fn delete_sent_follow_request(&self, target: &str) -> anyhow::Result<()> {
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "DELETE FROM sent_follow_requests WHERE target=?1",
+            params![target],
+        )?;
+        Ok(())
+    }
 }
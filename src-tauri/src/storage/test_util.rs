@@ -0,0 +1,12 @@
+use iroh_social_types::Post;
+
+/// An unsigned public post with just an id, author and timestamp set.
+pub(crate) fn post(id: &str, author: &str, timestamp: u64) -> Post {
+    Post {
+        id: id.to_string(),
+        author: author.to_string(),
+        content: format!("post {id}"),
+        timestamp,
+        ..Default::default()
+    }
+}
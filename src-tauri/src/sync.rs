@@ -1,11 +1,13 @@
-use crate::storage::Storage;
+use crate::follow::{open_sealed_message, seal_message};
+use crate::storage::{PostView, Storage};
 use iroh::{
     Endpoint, EndpointAddr, EndpointId,
     endpoint::Connection,
     protocol::{AcceptError, ProtocolHandler},
 };
 use iroh_social_types::{
-    Interaction, Post, Profile, SyncFrame, SyncMode, SyncRequest, SyncSummary, short_id,
+    AudiencePost, GossipMessage, Interaction, PostEdit, Profile, SyncFrame, SyncMode, SyncRequest,
+    SyncSummary, short_id, validate_audience_post,
 };
 use std::sync::Arc;
 
@@ -55,6 +57,20 @@ impl SyncHandler {
     pub fn new(storage: Arc<Storage>, node_id: String) -> Self {
         Self { storage, node_id }
     }
+
+    /// Seal the posts and edits of a frame under our feed key.
+    fn seal_frame(&self, frame: SyncFrame) -> anyhow::Result<SyncFrame> {
+        let messages: Vec<GossipMessage> = match frame {
+            SyncFrame::Posts(posts) => posts.into_iter().map(GossipMessage::NewPost).collect(),
+            SyncFrame::Edits(edits) => edits.into_iter().map(GossipMessage::EditPost).collect(),
+            other => return Ok(other),
+        };
+        let sealed = messages
+            .iter()
+            .map(|msg| seal_message(&self.storage, &self.node_id, msg))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(SyncFrame::Sealed(sealed))
+    }
 }
 
 impl ProtocolHandler for SyncHandler {
@@ -72,15 +88,18 @@ impl ProtocolHandler for SyncHandler {
             return Err(AcceptError::from_err(std::io::Error::other("blocked")));
         }
 
-        // Reject non-followers when profile is private
+        // Reject followers we haven't approved when profile is private
         if self
             .storage
             .is_private_profile(&self.node_id)
             .unwrap_or(false)
-            && !self.storage.is_follower(&remote_str).unwrap_or(false)
+            && !self
+                .storage
+                .is_approved_follower(&remote_str)
+                .unwrap_or(false)
         {
             log::warn!(
-                "[sync-server] rejecting non-follower {} (private profile)",
+                "[sync-server] rejecting unapproved follower {} (private profile)",
                 short_id(&remote_str)
             );
             return Err(AcceptError::from_err(std::io::Error::other("private")));
@@ -95,36 +114,74 @@ impl ProtocolHandler for SyncHandler {
             .map_err(AcceptError::from_err)?;
         let req: SyncRequest = serde_json::from_slice(&req_bytes).map_err(AcceptError::from_err)?;
 
+        // Only the owner of a private profile knows who may read it
+        if req.author != self.node_id
+            && self
+                .storage
+                .is_private_profile(&req.author)
+                .unwrap_or(false)
+        {
+            log::warn!(
+                "[sync-server] refusing to relay private author {} to {}",
+                short_id(&req.author),
+                short_id(&remote_str)
+            );
+            return Err(AcceptError::from_err(std::io::Error::other("private")));
+        }
+
         let map_err =
             |e: anyhow::Error| AcceptError::from_err(std::io::Error::other(e.to_string()));
+        let likes_only = !req.reactions;
+
+        // Our own private posts only leave sealed under the feed key
+        let sealed = req.author == self.node_id
+            && self
+                .storage
+                .is_private_profile(&self.node_id)
+                .unwrap_or(false);
+        if sealed && !req.sealed {
+            log::warn!(
+                "[sync-server] refusing unsealed sync of private profile to {}",
+                short_id(&remote_str)
+            );
+            return Err(AcceptError::from_err(std::io::Error::other("private")));
+        }
 
         let server_post_count = self
             .storage
-            .count_posts_by_author(&req.author)
+            .count_posts_by_author(&req.author, Some(&remote_str))
             .map_err(map_err)?;
         let server_interaction_count = self
             .storage
-            .count_interactions_by_author(&req.author)
+            .count_interactions_by_author(&req.author, likes_only)
             .map_err(map_err)?;
         let posts_after_count = if req.newest_timestamp > 0 {
             self.storage
-                .count_posts_after(&req.author, req.newest_timestamp)
+                .count_posts_after(&req.author, req.newest_timestamp, Some(&remote_str))
                 .map_err(map_err)?
         } else {
             server_post_count
         };
         let interactions_after_count = if req.newest_interaction_timestamp > 0 {
             self.storage
-                .count_interactions_after(&req.author, req.newest_interaction_timestamp)
+                .count_interactions_after(&req.author, req.newest_interaction_timestamp, likes_only)
                 .map_err(map_err)?
         } else {
             server_interaction_count
         };
 
+        let edits_after_count = if req.edits {
+            self.storage
+                .count_edits_after(&req.author, req.newest_edit_timestamp)
+                .map_err(map_err)?
+        } else {
+            0
+        };
+
         // Determine sync mode
         let posts_match = req.post_count == server_post_count;
         let interactions_match = req.interaction_count == server_interaction_count;
-        let mode = if posts_match && interactions_match {
+        let mode = if posts_match && interactions_match && edits_after_count == 0 {
             SyncMode::UpToDate
         } else if server_post_count >= req.post_count
             && (server_post_count - req.post_count) == posts_after_count
@@ -195,11 +252,23 @@ impl ProtocolHandler for SyncHandler {
             let batch = match mode {
                 SyncMode::TimestampCatchUp => self
                     .storage
-                    .get_posts_after(&req.author, req.newest_timestamp, BATCH_SIZE, offset)
+                    .get_posts_after(
+                        &req.author,
+                        req.newest_timestamp,
+                        Some(&remote_str),
+                        BATCH_SIZE,
+                        offset,
+                    )
                     .map_err(map_err)?,
                 SyncMode::NeedIdDiff => self
                     .storage
-                    .get_posts_not_in(&req.author, &known_ids, BATCH_SIZE, offset)
+                    .get_posts_not_in(
+                        &req.author,
+                        &known_ids,
+                        Some(&remote_str),
+                        BATCH_SIZE,
+                        offset,
+                    )
                     .map_err(map_err)?,
                 SyncMode::UpToDate => break,
             };
@@ -208,12 +277,38 @@ impl ProtocolHandler for SyncHandler {
                 break;
             }
 
-            total_sent += batch.len() as u64;
             offset += batch.len();
 
-            let frame = SyncFrame::Posts(batch);
-            let frame_bytes = serde_json::to_vec(&frame).map_err(AcceptError::from_err)?;
-            write_frame(&mut data_send, &frame_bytes).await?;
+            // Audience markers travel beside the signed posts, not in them
+            let mut posts = Vec::new();
+            let mut shared = Vec::new();
+            for view in batch {
+                match view.audience {
+                    Some(audience) => shared.push(AudiencePost {
+                        audience,
+                        post: view.post,
+                    }),
+                    None => posts.push(view.post),
+                }
+            }
+            if !req.audiences {
+                shared.clear();
+            }
+            total_sent += (posts.len() + shared.len()) as u64;
+
+            if !posts.is_empty() {
+                let mut frame = SyncFrame::Posts(posts);
+                if sealed {
+                    frame = self.seal_frame(frame).map_err(map_err)?;
+                }
+                let frame_bytes = serde_json::to_vec(&frame).map_err(AcceptError::from_err)?;
+                write_frame(&mut data_send, &frame_bytes).await?;
+            }
+            if !shared.is_empty() {
+                let frame = SyncFrame::AudiencePosts(shared);
+                let frame_bytes = serde_json::to_vec(&frame).map_err(AcceptError::from_err)?;
+                write_frame(&mut data_send, &frame_bytes).await?;
+            }
         }
 
         // Stream interactions (smart: timestamp catch-up or full)
@@ -228,13 +323,14 @@ impl ProtocolHandler for SyncHandler {
                         .get_interactions_after(
                             &req.author,
                             req.newest_interaction_timestamp,
+                            likes_only,
                             BATCH_SIZE,
                             ioffset,
                         )
                         .map_err(map_err)?
                 } else {
                     self.storage
-                        .get_interactions_paged(&req.author, BATCH_SIZE, ioffset)
+                        .get_interactions_paged(&req.author, likes_only, BATCH_SIZE, ioffset)
                         .map_err(map_err)?
                 };
 
@@ -250,6 +346,26 @@ impl ProtocolHandler for SyncHandler {
             }
         }
 
+        // Stream edits newer than the client's newest, after their posts
+        let mut eoffset = 0;
+        while eoffset < edits_after_count as usize {
+            let batch = self
+                .storage
+                .get_edits_after(&req.author, req.newest_edit_timestamp, BATCH_SIZE, eoffset)
+                .map_err(map_err)?;
+            if batch.is_empty() {
+                break;
+            }
+            eoffset += batch.len();
+
+            let mut frame = SyncFrame::Edits(batch);
+            if sealed {
+                frame = self.seal_frame(frame).map_err(map_err)?;
+            }
+            let frame_bytes = serde_json::to_vec(&frame).map_err(AcceptError::from_err)?;
+            write_frame(&mut data_send, &frame_bytes).await?;
+        }
+
         // End-of-stream marker
         write_frame(&mut data_send, &[]).await?;
         data_send.finish().map_err(AcceptError::from_err)?;
@@ -268,8 +384,11 @@ impl ProtocolHandler for SyncHandler {
 
 /// Result returned from a sync operation.
 pub struct SyncResult {
-    pub posts: Vec<Post>,
+    /// Posts by the author, with the audience marker on those they shared
+    /// with a list we are on.
+    pub posts: Vec<PostView>,
     pub interactions: Vec<Interaction>,
+    pub edits: Vec<PostEdit>,
     pub profile: Option<Profile>,
     pub remote_post_count: u64,
     pub mode: SyncMode,
@@ -310,10 +429,13 @@ pub async fn sync_from_peer(
     // Phase 1: Send summary request
     let (mut send, mut recv) = conn.open_bi().await?;
 
-    let post_count = storage.count_posts_by_author(author).unwrap_or(0);
-    let interaction_count = storage.count_interactions_by_author(author).unwrap_or(0);
+    let post_count = storage.count_posts_by_author(author, None).unwrap_or(0);
+    let interaction_count = storage
+        .count_interactions_by_author(author, false)
+        .unwrap_or(0);
     let newest_timestamp = storage.newest_post_timestamp(author).unwrap_or(0);
     let newest_interaction_timestamp = storage.newest_interaction_timestamp(author).unwrap_or(0);
+    let newest_edit_timestamp = storage.newest_edit_timestamp(author).unwrap_or(0);
 
     let req = SyncRequest {
         author: author.to_string(),
@@ -321,6 +443,11 @@ pub async fn sync_from_peer(
         interaction_count,
         newest_timestamp,
         newest_interaction_timestamp,
+        reactions: true,
+        edits: true,
+        newest_edit_timestamp,
+        sealed: true,
+        audiences: true,
     };
     let req_bytes = serde_json::to_vec(&req)?;
     send.write_all(&req_bytes).await?;
@@ -345,6 +472,7 @@ pub async fn sync_from_peer(
         return Ok(SyncResult {
             posts: Vec::new(),
             interactions: Vec::new(),
+            edits: Vec::new(),
             profile: summary.profile,
             remote_post_count: summary.server_post_count,
             mode: SyncMode::UpToDate,
@@ -365,15 +493,52 @@ pub async fn sync_from_peer(
     // Read streamed frames
     let mut all_posts = Vec::new();
     let mut all_interactions = Vec::new();
+    let mut all_edits = Vec::new();
     loop {
         match read_frame(&mut data_recv).await {
             Ok(Some(frame_bytes)) => {
                 let frame: SyncFrame = serde_json::from_slice(&frame_bytes)?;
                 match frame {
-                    SyncFrame::Posts(posts) => all_posts.extend(posts),
+                    SyncFrame::Posts(posts) => {
+                        all_posts.extend(posts.into_iter().map(PostView::new))
+                    }
                     SyncFrame::Interactions(interactions) => {
                         all_interactions.extend(interactions);
                     }
+                    SyncFrame::Edits(edits) => all_edits.extend(edits),
+                    SyncFrame::Sealed(messages) => {
+                        for sealed in messages.iter().filter(|m| m.author == author) {
+                            match open_sealed_message(storage, sealed) {
+                                Ok(GossipMessage::NewPost(post)) => {
+                                    all_posts.push(PostView::new(post))
+                                }
+                                Ok(GossipMessage::EditPost(edit)) => all_edits.push(edit),
+                                Ok(_) => {}
+                                Err(reason) => log::info!(
+                                    "[sync-client] cannot open sealed message from {}: {reason}",
+                                    short_id(author)
+                                ),
+                            }
+                        }
+                    }
+                    SyncFrame::AudiencePosts(shared) => {
+                        for shared in shared {
+                            if shared.post.author != author {
+                                continue;
+                            }
+                            if let Err(reason) = validate_audience_post(&shared) {
+                                log::error!(
+                                    "[sync-client] rejected audience post {}: {reason}",
+                                    &shared.post.id
+                                );
+                                continue;
+                            }
+                            all_posts.push(PostView {
+                                audience: Some(shared.audience),
+                                ..PostView::new(shared.post)
+                            });
+                        }
+                    }
                 }
             }
             Ok(None) => break, // End of stream
@@ -388,9 +553,10 @@ pub async fn sync_from_peer(
     }
 
     log::info!(
-        "[sync-client] received {} posts, {} interactions from {} in {:.1}s (mode={:?})",
+        "[sync-client] received {} posts, {} interactions, {} edits from {} in {:.1}s (mode={:?})",
         all_posts.len(),
         all_interactions.len(),
+        all_edits.len(),
         short_id(author),
         start.elapsed().as_secs_f64(),
         summary.mode,
@@ -401,6 +567,7 @@ pub async fn sync_from_peer(
     Ok(SyncResult {
         posts: all_posts,
         interactions: all_interactions,
+        edits: all_edits,
         profile: summary.profile,
         remote_post_count: summary.server_post_count,
         mode: summary.mode,
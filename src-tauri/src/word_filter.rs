@@ -0,0 +1,123 @@
+use iroh_social_types::{MAX_MUTED_WORD_LEN, MutedWord, MutedWordKind};
+use regex::{Regex, RegexBuilder};
+
+/// Compiled regexes are capped so a pathological pattern can't blow up memory.
+const REGEX_SIZE_LIMIT: usize = 256 * 1024;
+
+/// Build the regex a muted word matches with, or say why the pattern is
+/// unusable.
+pub fn compile(pattern: &str, kind: MutedWordKind) -> Result<Regex, String> {
+    let pattern = pattern.trim();
+    if pattern.is_empty() {
+        return Err("muted word is empty".to_string());
+    }
+    if pattern.len() > MAX_MUTED_WORD_LEN {
+        return Err(format!(
+            "muted word exceeds {MAX_MUTED_WORD_LEN} characters"
+        ));
+    }
+    let source = match kind {
+        MutedWordKind::Word => {
+            if pattern.split_whitespace().count() > 1 {
+                return Err("a muted word can't contain spaces; use a phrase".to_string());
+            }
+            // Not `\b`, so words ending in symbols like "c++" still match
+            format!(r"(?i)(?:^|\W){}(?:\W|$)", regex::escape(pattern))
+        }
+        MutedWordKind::Phrase => {
+            let words: Vec<String> = pattern.split_whitespace().map(regex::escape).collect();
+            format!(r"(?i){}", words.join(r"\s+"))
+        }
+        MutedWordKind::Regex => pattern.to_string(),
+    };
+    RegexBuilder::new(&source)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| format!("invalid regex: {e}"))
+}
+
+/// A set of muted words, compiled once and shared until they change.
+pub struct WordFilter {
+    entries: Vec<(Regex, String)>,
+}
+
+impl WordFilter {
+    /// Patterns that no longer compile are skipped.
+    pub fn new(words: &[MutedWord]) -> Self {
+        let entries = words
+            .iter()
+            .filter_map(|w| {
+                compile(&w.pattern, w.kind)
+                    .ok()
+                    .map(|re| (re, w.pattern.clone()))
+            })
+            .collect();
+        Self { entries }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The pattern of the first muted word found in any of `texts`.
+    pub fn find<'a>(&self, texts: impl IntoIterator<Item = &'a str> + Clone) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(re, _)| texts.clone().into_iter().any(|t| re.is_match(t)))
+            .map(|(_, pattern)| pattern.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh_social_types::FilterScope;
+
+    fn muted(pattern: &str, kind: MutedWordKind) -> MutedWord {
+        MutedWord {
+            id: pattern.to_string(),
+            pattern: pattern.to_string(),
+            kind,
+            scope: FilterScope::Both,
+            expires_at: None,
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_word_matches_whole_words_only() {
+        let filter = WordFilter::new(&[muted("cat", MutedWordKind::Word)]);
+        assert_eq!(filter.find(["My CAT is asleep"]), Some("cat"));
+        assert_eq!(filter.find(["cat."]), Some("cat"));
+        assert_eq!(filter.find(["a category"]), None);
+        let filter = WordFilter::new(&[muted("c++", MutedWordKind::Word)]);
+        assert_eq!(filter.find(["I like C++ a lot"]), Some("c++"));
+        assert_eq!(filter.find(["abc++"]), None);
+    }
+
+    #[test]
+    fn test_phrase_and_regex() {
+        let filter = WordFilter::new(&[
+            muted("spoiler  alert", MutedWordKind::Phrase),
+            muted(r"season \d+ finale", MutedWordKind::Regex),
+        ]);
+        assert_eq!(
+            filter.find(["Spoiler\nalert: it ends"]),
+            Some("spoiler  alert")
+        );
+        assert_eq!(
+            filter.find(["", "the season 4 finale!"]),
+            Some(r"season \d+ finale")
+        );
+        assert_eq!(filter.find(["Season 4 finale"]), None);
+    }
+
+    #[test]
+    fn test_compile_rejects_bad_patterns() {
+        assert!(compile("  ", MutedWordKind::Word).is_err());
+        assert!(compile("two words", MutedWordKind::Word).is_err());
+        assert!(compile("(unclosed", MutedWordKind::Regex).is_err());
+        assert!(compile("(unclosed", MutedWordKind::Phrase).is_ok());
+        assert!(compile(&"a".repeat(MAX_MUTED_WORD_LEN + 1), MutedWordKind::Phrase).is_err());
+    }
+}
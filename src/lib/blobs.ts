@@ -20,9 +20,12 @@ export function createBlobCache() {
   async function getBlobUrl(attachment: MediaAttachment): Promise<string> {
     const cached = cache.get(attachment.hash);
     if (cached) return cached;
-    const bytes: number[] = await invoke("fetch_blob_bytes", {
-      ticket: attachment.ticket,
-    });
+    const bytes: number[] = attachment.key
+      ? await invoke("fetch_dm_blob_bytes", {
+          ticket: attachment.ticket,
+          key: attachment.key,
+        })
+      : await invoke("fetch_blob_bytes", { ticket: attachment.ticket });
     const blob = new Blob([new Uint8Array(bytes)], {
       type: attachment.mime_type,
     });
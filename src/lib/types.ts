@@ -4,6 +4,7 @@ export interface MediaAttachment {
   mime_type: string;
   filename: string;
   size: number;
+  key?: string;
 }
 
 export interface Post {
@@ -17,6 +18,83 @@ export interface Post {
   quote_of: string | null;
   quote_of_author: string | null;
   signature: string;
+  poll?: Poll;
+  content_warning?: string;
+  sensitive_media?: boolean;
+  expires_at?: number;
+  link_preview?: LinkPreview;
+  collapsed?: boolean;
+  edited_at?: number;
+  pinned?: boolean;
+  reposted_by?: string[];
+  audience?: string;
+  filtered_by?: string;
+}
+
+/** Author-controlled fields of a post, as stored in drafts and the schedule. */
+export interface LinkPreview {
+  url: string;
+  title: string;
+  description?: string;
+  image?: MediaAttachment;
+}
+
+export interface PostBody {
+  content: string;
+  media: MediaAttachment[];
+  reply_to?: string | null;
+  reply_to_author?: string | null;
+  quote_of?: string | null;
+  quote_of_author?: string | null;
+  poll: Poll | null;
+  content_warning: string | null;
+  sensitive_media: boolean;
+  expires_at?: number | null;
+}
+
+export interface Draft {
+  id: string;
+  body: PostBody;
+  created_at: number;
+  updated_at: number;
+}
+
+export interface ScheduledPost {
+  id: string;
+  body: PostBody;
+  publish_at: number;
+  created_at: number;
+  last_error: string | null;
+}
+
+export type SensitiveMode = "show" | "collapse" | "hide";
+
+export interface PostEdit {
+  id: string;
+  post_id: string;
+  author: string;
+  content: string;
+  media: MediaAttachment[];
+  timestamp: number;
+  signature: string;
+}
+
+export interface PostHistory {
+  original: Post;
+  edits: PostEdit[];
+}
+
+export interface Poll {
+  options: string[];
+  closes_at: number;
+  multiple: boolean;
+}
+
+export interface PollResults {
+  counts: number[];
+  voters: number;
+  my_vote: number[] | null;
+  closed: boolean;
 }
 
 export interface PendingAttachment {
@@ -25,6 +103,7 @@ export interface PendingAttachment {
   mime_type: string;
   filename: string;
   size: number;
+  key?: string;
   previewUrl: string;
 }
 
@@ -34,6 +113,9 @@ export interface Profile {
   avatar_hash: string | null;
   avatar_ticket: string | null;
   is_private: boolean;
+  pinned_posts?: string[];
+  updated_at?: number;
+  signature?: string;
 }
 
 export interface FollowEntry {
@@ -49,6 +131,26 @@ export interface FollowerEntry {
   is_online: boolean;
 }
 
+export type FollowRequestStatus = "pending" | "approved" | "denied";
+
+export interface FollowRequestEntry {
+  pubkey: string;
+  timestamp: number;
+  status: FollowRequestStatus;
+}
+
+export interface ApprovedFollower {
+  pubkey: string;
+  approved_at: number;
+}
+
+export interface Audience {
+  id: string;
+  name: string;
+  members: string[];
+  created_at: number;
+}
+
 export interface NodeStatus {
   node_id: string;
   has_relay: boolean;
@@ -60,7 +162,7 @@ export interface NodeStatus {
 export interface Interaction {
   id: string;
   author: string;
-  kind: "Like";
+  kind: "Like" | { Reaction: string } | { Vote: number[] };
   target_post_id: string;
   target_author: string;
   timestamp: number;
@@ -71,13 +173,43 @@ export interface PostCounts {
   likes: number;
   replies: number;
   reposts: number;
+  quotes: number;
   liked_by_me: boolean;
   reposted_by_me: boolean;
+  reactions: ReactionCount[];
+}
+
+export interface ConversationNode {
+  post: Post;
+  counts: PostCounts;
+  children: ConversationNode[];
+}
+
+export interface Conversation {
+  ancestors: ConversationNode[];
+  focus: ConversationNode;
+  missing_ancestor: { id: string; author: string | null } | null;
+}
+
+export interface ReactionCount {
+  emoji: string;
+  count: number;
+  reacted_by_me: boolean;
 }
 
 export interface AppNotification {
   id: string;
-  kind: "mention" | "like" | "reply" | "quote" | "follower";
+  kind:
+    | "mention"
+    | "like"
+    | "reaction"
+    | "vote"
+    | "reply"
+    | "repost"
+    | "quote"
+    | "follower"
+    | "follow_request"
+    | "follow_approved";
   actor: string;
   target_post_id: string | null;
   post_id: string | null;
@@ -99,6 +231,18 @@ export interface ConversationMeta {
   last_message_at: number;
   last_message_preview: string;
   unread_count: number;
+  verified: boolean;
+  identity_changed: boolean;
+  is_request: boolean;
+}
+
+export type DmPolicy = "everyone" | "following" | "mutuals";
+
+export interface SafetyNumber {
+  peer_pubkey: string;
+  digits: string;
+  scannable: string;
+  verified: boolean;
 }
 
 export interface StoredMessage {
@@ -112,4 +256,63 @@ export interface StoredMessage {
   read: boolean;
   delivered: boolean;
   reply_to: string | null;
+  failed: boolean;
+}
+
+export interface UserList {
+  id: string;
+  name: string;
+  members: string[];
+  created_at: number;
+}
+
+export interface CustomFeed {
+  id: string;
+  name: string;
+  list_ids: string[];
+  hashtags: string[];
+  media_only: boolean;
+  exclude_replies: boolean;
+  created_at: number;
+}
+
+export interface FeedFilters {
+  replies: "all" | "none" | "following";
+  exclude_reposts: boolean;
+  media_only: boolean;
+  hide_own: boolean;
+}
+
+export interface RankReason {
+  kind:
+    | "followed_likes"
+    | "followed_reposts"
+    | "replies"
+    | "liked_author"
+    | "replied_to_author";
+  count: number;
+  points: number;
+}
+
+export interface RankedPost {
+  post: Post;
+  score: { value: number; decay: number; reasons: RankReason[] };
+}
+
+export interface MutedWord {
+  id: string;
+  pattern: string;
+  kind: "word" | "phrase" | "regex";
+  scope: "feed" | "notifications" | "both";
+  expires_at: number | null;
+  created_at: number;
+}
+
+export type MuteScope = "posts" | "reposts" | "notifications" | "dms";
+
+export interface Mute {
+  pubkey: string;
+  scope: MuteScope;
+  expires_at: number | null;
+  created_at: number;
 }
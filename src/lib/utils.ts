@@ -1,5 +1,5 @@
 import { invoke } from "@tauri-apps/api/core";
-import type { Profile, PendingAttachment } from "$lib/types";
+import type { Mute, MuteScope, Profile, PendingAttachment } from "$lib/types";
 
 const AVATAR_COLORS = [
   "#7c3aed",
@@ -198,6 +198,21 @@ export function formatSize(bytes: number): string {
   return (bytes / 1048576).toFixed(1) + " MB";
 }
 
+export const MUTE_SCOPE_LABELS: Record<MuteScope, string> = {
+  posts: "Everything",
+  reposts: "Reposts only",
+  notifications: "Notifications only",
+  dms: "Messages only",
+};
+
+/** What a mute hides and, if temporary, until when. */
+export function describeMute(mute: Mute): string {
+  const scope = MUTE_SCOPE_LABELS[mute.scope];
+  return mute.expires_at
+    ? `${scope} · until ${new Date(mute.expires_at).toLocaleString()}`
+    : scope;
+}
+
 /**
  * Sets up an IntersectionObserver for infinite scroll on a sentinel element.
  * Call from within a $effect; returns a cleanup function.
@@ -223,15 +238,16 @@ export function setupInfiniteScroll(
 
 export async function uploadFiles(
   files: FileList,
+  encrypted = false,
 ): Promise<PendingAttachment[]> {
   const results: PendingAttachment[] = [];
   for (const file of files) {
     const buffer = await file.arrayBuffer();
     const data = Array.from(new Uint8Array(buffer));
-    const result: { hash: string; ticket: string } = await invoke(
-      "add_blob_bytes",
-      { data },
-    );
+    const result: { hash: string; ticket: string; key?: string } =
+      await invoke(encrypted ? "add_dm_blob_bytes" : "add_blob_bytes", {
+        data,
+      });
     const previewUrl = URL.createObjectURL(file);
     results.push({
       hash: result.hash,
@@ -239,6 +255,7 @@ export async function uploadFiles(
       mime_type: file.type || "application/octet-stream",
       filename: file.name,
       size: file.size,
+      key: result.key,
       previewUrl,
     });
   }